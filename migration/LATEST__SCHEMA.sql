@@ -83,13 +83,46 @@ CREATE TABLE resource (
   memo_id INTEGER,
   storage_type TEXT NOT NULL DEFAULT '',
   reference TEXT NOT NULL DEFAULT '',
-  payload TEXT NOT NULL DEFAULT '{}'
+  payload TEXT NOT NULL DEFAULT '{}',
+  hash TEXT NOT NULL DEFAULT '',
+  trashed_ts BIGINT DEFAULT NULL,
+  location TEXT DEFAULT NULL
 );
 
 CREATE INDEX idx_resource_creator_id ON resource (creator_id);
 
 CREATE INDEX idx_resource_memo_id ON resource (memo_id);
 
+CREATE INDEX idx_resource_hash ON resource (hash);
+
+-- resource_blob
+CREATE TABLE resource_blob (
+  hash TEXT PRIMARY KEY,
+  size INTEGER NOT NULL DEFAULT 0,
+  blob BLOB DEFAULT NULL,
+  storage_type TEXT NOT NULL DEFAULT '',
+  reference TEXT NOT NULL DEFAULT '',
+  payload TEXT NOT NULL DEFAULT '{}',
+  ref_count INTEGER NOT NULL DEFAULT 0
+);
+
+CREATE TRIGGER resource_blob_ref AFTER INSERT ON resource
+WHEN new.hash <> '' BEGIN
+  UPDATE resource_blob SET ref_count = ref_count + 1 WHERE hash = new.hash;
+END;
+
+CREATE TRIGGER resource_blob_unref AFTER DELETE ON resource
+WHEN old.hash <> '' BEGIN
+  UPDATE resource_blob SET ref_count = ref_count - 1 WHERE hash = old.hash;
+END;
+
+-- user_quota
+CREATE TABLE user_quota (
+  user_id INTEGER PRIMARY KEY,
+  bytes_limit BIGINT NOT NULL DEFAULT 0,
+  files_limit INTEGER NOT NULL DEFAULT 0
+);
+
 -- activity
 CREATE TABLE activity (
   id INTEGER PRIMARY KEY AUTOINCREMENT,
@@ -109,6 +142,14 @@ CREATE TABLE idp (
   config TEXT NOT NULL DEFAULT '{}'
 );
 
+-- user_identity
+CREATE TABLE user_identity (
+  idp_id INTEGER NOT NULL,
+  identifier TEXT NOT NULL,
+  user_id INTEGER NOT NULL,
+  PRIMARY KEY (idp_id, identifier)
+);
+
 -- inbox
 CREATE TABLE inbox (
   id INTEGER PRIMARY KEY AUTOINCREMENT,
@@ -141,3 +182,39 @@ CREATE TABLE reaction (
   reaction_type TEXT NOT NULL,
   UNIQUE(creator_id, content_id, reaction_type)
 );
+
+-- memo_fts
+CREATE VIRTUAL TABLE memo_fts USING fts5(
+  content,
+  content = 'memo',
+  content_rowid = 'id',
+  tokenize = 'trigram'
+);
+
+CREATE TRIGGER memo_fts_insert AFTER INSERT ON memo BEGIN
+  INSERT INTO memo_fts (rowid, content) VALUES (new.id, new.content);
+END;
+
+CREATE TRIGGER memo_fts_delete AFTER DELETE ON memo BEGIN
+  INSERT INTO memo_fts (memo_fts, rowid, content) VALUES ('delete', old.id, old.content);
+END;
+
+CREATE TRIGGER memo_fts_update AFTER UPDATE OF content ON memo BEGIN
+  INSERT INTO memo_fts (memo_fts, rowid, content) VALUES ('delete', old.id, old.content);
+  INSERT INTO memo_fts (rowid, content) VALUES (new.id, new.content);
+END;
+
+-- memo_revision
+CREATE TABLE memo_revision (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  memo_id INTEGER NOT NULL,
+  created_ts BIGINT NOT NULL DEFAULT (strftime('%s', 'now')),
+  content TEXT NOT NULL DEFAULT ''
+);
+
+CREATE INDEX idx_memo_revision_memo_id ON memo_revision (memo_id);
+
+CREATE TRIGGER memo_revision_update AFTER UPDATE OF content ON memo
+WHEN old.content <> new.content BEGIN
+  INSERT INTO memo_revision (memo_id, content) VALUES (old.id, old.content);
+END;
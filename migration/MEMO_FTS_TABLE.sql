@@ -0,0 +1,21 @@
+create virtual table if not exists memo_fts using fts5(
+    content,
+    content = 'memo',
+    content_rowid = 'id',
+    tokenize = 'trigram'
+);
+
+create trigger if not exists memo_fts_insert after insert on memo begin
+    insert into memo_fts (rowid, content) values (new.id, new.content);
+end;
+
+create trigger if not exists memo_fts_delete after delete on memo begin
+    insert into memo_fts (memo_fts, rowid, content) values ('delete', old.id, old.content);
+end;
+
+create trigger if not exists memo_fts_update after update of content on memo begin
+    insert into memo_fts (memo_fts, rowid, content) values ('delete', old.id, old.content);
+    insert into memo_fts (rowid, content) values (new.id, new.content);
+end;
+
+insert into memo_fts (memo_fts) values ('rebuild');
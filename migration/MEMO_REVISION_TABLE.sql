@@ -0,0 +1,14 @@
+create table if not exists memo_revision (
+    id integer primary key autoincrement,
+    memo_id integer not null,
+    created_ts bigint not null default (strftime('%s', 'now')),
+    content text not null default ''
+);
+
+create index if not exists idx_memo_revision_memo_id on memo_revision (memo_id);
+
+-- 内容变更时保存旧内容
+create trigger if not exists memo_revision_update after update of content on memo
+when old.content <> new.content begin
+    insert into memo_revision (memo_id, content) values (old.id, old.content);
+end;
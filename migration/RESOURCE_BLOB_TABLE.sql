@@ -0,0 +1,24 @@
+alter table resource add column hash text not null default '';
+
+create index if not exists idx_resource_hash on resource (hash);
+
+-- 相同内容只保存一份，ref_count 由触发器维护
+create table if not exists resource_blob (
+    hash text primary key,
+    size integer not null default 0,
+    blob blob default null,
+    storage_type text not null default '',
+    reference text not null default '',
+    payload text not null default '{}',
+    ref_count integer not null default 0
+);
+
+create trigger if not exists resource_blob_ref after insert on resource
+when new.hash <> '' begin
+    update resource_blob set ref_count = ref_count + 1 where hash = new.hash;
+end;
+
+create trigger if not exists resource_blob_unref after delete on resource
+when old.hash <> '' begin
+    update resource_blob set ref_count = ref_count - 1 where hash = old.hash;
+end;
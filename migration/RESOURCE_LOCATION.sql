@@ -0,0 +1,2 @@
+-- 移除元数据前提取的拍摄位置，资源关联 memo 时写入 memo
+alter table resource add column location text default null;
@@ -0,0 +1,2 @@
+-- 从 memo 移除的资源先进入回收站，由定时任务在宽限期后清理
+alter table resource add column trashed_ts bigint default null;
@@ -0,0 +1,7 @@
+-- SSO 身份与本地用户的关联，仅关联过的用户可通过该身份登录
+create table if not exists user_identity (
+    idp_id integer not null,
+    identifier text not null,
+    user_id integer not null,
+    primary key (idp_id, identifier)
+);
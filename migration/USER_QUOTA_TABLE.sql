@@ -0,0 +1,6 @@
+-- 用户存储配额，0 表示不限制
+create table if not exists user_quota (
+    user_id integer primary key,
+    bytes_limit bigint not null default 0,
+    files_limit integer not null default 0
+);
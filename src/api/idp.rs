@@ -0,0 +1,110 @@
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{
+    api::prefix,
+    impl_extract_name,
+    model::idp::{
+        FieldMapping as FieldMappingModel, IdentityProvider as IdentityProviderModel,
+        IdentityProviderConfig as IdentityProviderConfigModel, OAuth2Config as OAuth2ConfigModel,
+    },
+};
+
+use super::{
+    prefix::FormatName,
+    v1::gen::{
+        identity_provider::Type, identity_provider_config::Config, DeleteIdentityProviderRequest,
+        FieldMapping, GetIdentityProviderRequest, IdentityProvider, IdentityProviderConfig,
+        OAuth2Config,
+    },
+};
+
+impl_extract_name!(IdentityProvider, prefix::IDENTITY_PROVIDER_NAME_PREFIX);
+impl_extract_name!(
+    GetIdentityProviderRequest,
+    prefix::IDENTITY_PROVIDER_NAME_PREFIX
+);
+impl_extract_name!(
+    DeleteIdentityProviderRequest,
+    prefix::IDENTITY_PROVIDER_NAME_PREFIX
+);
+
+impl FormatName for IdentityProviderModel {
+    fn get_name(&self) -> String {
+        format!("{}/{}", prefix::IDENTITY_PROVIDER_NAME_PREFIX, self.id)
+    }
+}
+
+impl From<IdentityProviderModel> for IdentityProvider {
+    fn from(value: IdentityProviderModel) -> Self {
+        Self {
+            name: value.get_name(),
+            r#type: value.r#type as i32,
+            title: value.name,
+            identifier_filter: value.identifier_filter,
+            config: Some(value.config.into()),
+        }
+    }
+}
+
+impl From<IdentityProviderConfigModel> for IdentityProviderConfig {
+    fn from(value: IdentityProviderConfigModel) -> Self {
+        Self {
+            config: value.oauth2_config.map(|c| {
+                Config::Oauth2Config(OAuth2Config {
+                    client_id: c.client_id,
+                    client_secret: c.client_secret,
+                    auth_url: c.auth_url,
+                    token_url: c.token_url,
+                    user_info_url: c.user_info_url,
+                    scopes: c.scopes,
+                    field_mapping: Some(FieldMapping {
+                        identifier: c.field_mapping.identifier,
+                        display_name: c.field_mapping.display_name,
+                        email: c.field_mapping.email,
+                    }),
+                })
+            }),
+        }
+    }
+}
+
+impl From<IdentityProviderConfig> for IdentityProviderConfigModel {
+    fn from(value: IdentityProviderConfig) -> Self {
+        let oauth2_config = value.config.map(|Config::Oauth2Config(c)| {
+            let field_mapping = c.field_mapping.unwrap_or_default();
+            OAuth2ConfigModel {
+                client_id: c.client_id,
+                client_secret: c.client_secret,
+                auth_url: c.auth_url,
+                token_url: c.token_url,
+                user_info_url: c.user_info_url,
+                scopes: c.scopes,
+                field_mapping: FieldMappingModel {
+                    identifier: field_mapping.identifier,
+                    display_name: field_mapping.display_name,
+                    email: field_mapping.email,
+                },
+            }
+        });
+        Self { oauth2_config }
+    }
+}
+
+impl Serialize for Type {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(self.as_str_name())
+    }
+}
+
+impl<'de> Deserialize<'de> for Type {
+    fn deserialize<D>(deserializer: D) -> Result<Type, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let r#type = String::deserialize(deserializer)?;
+        Ok(Type::from_str_name(&r#type).unwrap_or_default())
+    }
+}
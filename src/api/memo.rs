@@ -1,10 +1,12 @@
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use snafu::{ResultExt, Snafu};
+use tracing::error;
 
 use crate::{
     api::{prefix, to_timestamp},
     impl_extract_name,
-    model::memo::{CreateMemo, FindMemo, FindMemoPayload, SearchMemosFilter, UpdateMemo},
+    model::filter::{self, legacy, Filter},
+    model::memo::{CreateMemo, FindMemo, MemoRelation as MemoRelationModel, UpdateMemo},
     util::{self, md},
 };
 
@@ -15,8 +17,10 @@ use super::v1::{
 use super::{
     prefix::{get_id_parent_token, ExtractName, FormatName},
     v1::gen::{
-        DeleteMemoRequest, GetMemoRequest, ListMemosRequest, Memo, PageToken,
-        SetMemoResourcesRequest, UpdateMemoRequest, Visibility,
+        memo_relation, CreateMemoCommentRequest, DeleteMemoRequest, GetMemoRequest,
+        ListMemoCommentsRequest, ListMemoReactionsRequest, ListMemoRelationsRequest,
+        ListMemosRequest, Location, Memo, MemoRelation, PageToken, SetMemoRelationsRequest,
+        SetMemoResourcesRequest, UpdateMemoRequest, UpsertMemoReactionRequest, Visibility,
     },
 };
 
@@ -24,6 +28,12 @@ impl_extract_name!(Memo, prefix::MEMO_NAME_PREFIX);
 impl_extract_name!(GetMemoRequest, prefix::MEMO_NAME_PREFIX);
 impl_extract_name!(DeleteMemoRequest, prefix::MEMO_NAME_PREFIX);
 impl_extract_name!(SetMemoResourcesRequest, prefix::MEMO_NAME_PREFIX);
+impl_extract_name!(CreateMemoCommentRequest, prefix::MEMO_NAME_PREFIX);
+impl_extract_name!(ListMemoCommentsRequest, prefix::MEMO_NAME_PREFIX);
+impl_extract_name!(SetMemoRelationsRequest, prefix::MEMO_NAME_PREFIX);
+impl_extract_name!(ListMemoRelationsRequest, prefix::MEMO_NAME_PREFIX);
+impl_extract_name!(ListMemoReactionsRequest, prefix::MEMO_NAME_PREFIX);
+impl_extract_name!(UpsertMemoReactionRequest, prefix::MEMO_NAME_PREFIX);
 
 impl TryInto<CreateMemo> for &CreateMemoRequest {
     type Error = Error;
@@ -43,29 +53,45 @@ impl TryInto<CreateMemo> for &CreateMemoRequest {
     }
 }
 
+impl TryInto<CreateMemo> for &CreateMemoCommentRequest {
+    type Error = Error;
+
+    fn try_into(self) -> Result<CreateMemo, Self::Error> {
+        let memo = self.comment.as_ref().ok_or(Error::MemoDataLoss)?;
+        let content = memo.content.clone();
+        let payload = md::get_memo_property(&content);
+
+        Ok(CreateMemo {
+            creator_id: 0,
+            visibility: memo.visibility(),
+            content,
+            payload,
+            uid: util::uuid(),
+        })
+    }
+}
+
 impl TryInto<FindMemo> for &ListMemosRequest {
     type Error = Error;
 
     fn try_into(self) -> Result<FindMemo, Self::Error> {
-        let filter = if !self.old_filter.is_empty() {
-            let old_filter = &self.old_filter.replace('\'', "\"");
-            syn::parse_str::<SearchMemosFilter>(old_filter).context(FilterDecode)?
-        } else {
-            SearchMemosFilter::default()
+        // 新旧两种 filter 合并为同一个过滤条件
+        let (old_filter, options) = legacy::parse(&self.old_filter).context(InvalidFilter)?;
+        let filter = Filter::from_cel(&self.filter).context(InvalidFilter)?;
+        let filter = match (old_filter, filter) {
+            (Some(old_filter), Some(filter)) => Some(old_filter.and(filter)),
+            (old_filter, filter) => old_filter.or(filter),
         };
 
-        let creator_id = filter
-            .creator
-            .map(|s| get_id_parent_token(s, prefix::USER_NAME_PREFIX))
-            .transpose()
-            .context(InvalidUsername)?;
-
         let owner_id = get_id_parent_token(self.parent.clone(), prefix::USER_NAME_PREFIX).ok();
         let page_token = if !self.page_token.is_empty() {
             serde_json::from_str(&self.page_token).context(PageTokenDecode)?
         } else {
             PageToken {
-                limit: self.page_size,
+                limit: options
+                    .limit
+                    .filter(|_| self.page_size <= 0)
+                    .unwrap_or(self.page_size),
                 offset: 0,
             }
         };
@@ -75,38 +101,21 @@ impl TryInto<FindMemo> for &ListMemosRequest {
             direction = Direction::Desc;
         }
         Ok(FindMemo {
-            creator_id: creator_id.or(owner_id),
-            state: State::try_from(self.state).ok().or(filter.state),
-            content_search: filter.content_search.unwrap_or_default(),
-            visibility_list: filter.visibilities.unwrap_or_default(),
+            creator_id: options.creator_id.or(owner_id),
+            state: State::try_from(self.state)
+                .ok()
+                .filter(|s| *s != State::Unspecified),
             exclude_content: false,
             page_token: Some(page_token),
             //  默认使用更新时间过滤
             order_by_updated_ts: true,
-            updated_ts_after: filter.display_time_after,
-            updated_ts_before: filter.display_time_before,
-            payload_find: if filter.tag_search.is_some()
-                || filter.has_code.is_some()
-                || filter.has_link.is_some()
-                || filter.has_task_list.is_some()
-                || filter.has_incomplete_tasks.is_some()
-            {
-                Some(FindMemoPayload {
-                    raw: None,
-                    tags: filter.tag_search,
-                    has_link: filter.has_link.unwrap_or_default(),
-                    has_task_list: filter.has_task_list.unwrap_or_default(),
-                    has_code: filter.has_code.unwrap_or_default(),
-                    has_incomplete_tasks: filter.has_incomplete_tasks.unwrap_or_default(),
-                })
-            } else {
-                None
-            },
-            exclude_comments: true,
+            order_by_pinned: options.order_by_pinned.unwrap_or_default(),
+            random: options.random.unwrap_or_default(),
+            exclude_comments: !options.include_comments.unwrap_or_default(),
             only_payload: false,
             sort: self.sort.clone(),
             direction,
-            filter: self.filter.clone(),
+            filter,
             ..Default::default()
         })
     }
@@ -144,7 +153,9 @@ impl From<crate::model::memo::Memo> for Memo {
     fn from(value: crate::model::memo::Memo) -> Self {
         let name = value.get_name();
         let content = value.content;
-        let snippet = format!("{}...", content.get(0..99).unwrap_or(""));
+        let snippet = value
+            .snippet
+            .unwrap_or_else(|| format!("{}...", content.get(0..99).unwrap_or("")));
         let nodes = md::parse_document(&content);
         #[allow(deprecated)]
         Self {
@@ -163,9 +174,39 @@ impl From<crate::model::memo::Memo> for Memo {
             relations: vec![],
             reactions: vec![],
             property: value.payload.property.map(|p| p.into()),
-            parent: None,
+            parent: value
+                .parent_id
+                .map(|id| format!("{}/{}", prefix::MEMO_NAME_PREFIX, id)),
             snippet,
-            location: None,
+            location: value.payload.location.map(|l| l.into()),
+        }
+    }
+}
+
+impl MemoRelation {
+    pub fn get_related_memo_id(&self) -> Option<i32> {
+        self.related_memo.as_ref().and_then(|m| {
+            get_id_parent_token(&m.name, prefix::MEMO_NAME_PREFIX)
+                .inspect_err(|e| error!("{e}"))
+                .ok()
+        })
+    }
+}
+
+impl From<MemoRelationModel> for MemoRelation {
+    fn from(value: MemoRelationModel) -> Self {
+        Self {
+            memo: Some(memo_relation::Memo {
+                name: format!("{}/{}", prefix::MEMO_NAME_PREFIX, value.memo_id),
+                uid: value.memo_uid,
+                snippet: value.memo_snippet,
+            }),
+            related_memo: Some(memo_relation::Memo {
+                name: format!("{}/{}", prefix::MEMO_NAME_PREFIX, value.related_memo_id),
+                uid: value.related_memo_uid,
+                snippet: value.related_memo_snippet,
+            }),
+            r#type: value.r#type as i32,
         }
     }
 }
@@ -187,6 +228,16 @@ impl From<crate::model::gen::memo_payload::Property> for Property {
     }
 }
 
+impl From<crate::model::gen::memo_payload::Location> for Location {
+    fn from(value: crate::model::gen::memo_payload::Location) -> Self {
+        Self {
+            placeholder: value.placeholder,
+            latitude: value.latitude,
+            longitude: value.longitude,
+        }
+    }
+}
+
 impl Serialize for Visibility {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -208,12 +259,31 @@ impl<'de> Deserialize<'de> for Visibility {
     }
 }
 
+impl Serialize for memo_relation::Type {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let r#type = self.as_str_name();
+        serializer.serialize_str(r#type)
+    }
+}
+
+impl<'de> Deserialize<'de> for memo_relation::Type {
+    fn deserialize<D>(deserializer: D) -> Result<memo_relation::Type, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let r#type = String::deserialize(deserializer)?;
+        let r#type = memo_relation::Type::from_str_name(&r#type).unwrap_or_default();
+        Ok(r#type)
+    }
+}
+
 #[derive(Debug, Snafu)]
 pub enum Error {
-    #[snafu(display("Invalid username : {source}"), context(suffix(false)))]
-    InvalidUsername { source: prefix::Error },
-    #[snafu(display("Failed to decode filter : {source}"), context(suffix(false)))]
-    FilterDecode { source: syn::Error },
+    #[snafu(display("Invalid filter : {source}"), context(suffix(false)))]
+    InvalidFilter { source: filter::Error },
     #[snafu(
         display("Failed to decode page token : {source}"),
         context(suffix(false))
@@ -222,3 +292,33 @@ pub enum Error {
     #[snafu(display("Memo data loss"), context(suffix(false)))]
     MemoDataLoss,
 }
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        api::v1::gen::{ListMemosRequest, Visibility},
+        model::memo::FindMemo,
+    };
+
+    #[test]
+    fn legacy_creator() {
+        // 查看他人主页时只返回公开与受保护的 memo
+        let request = ListMemosRequest {
+            old_filter: r#"creator == "users/2""#.to_owned(),
+            ..Default::default()
+        };
+        let mut find: FindMemo = (&request).try_into().unwrap();
+        find.completed(Some(1), false);
+        assert_eq!(Some(2), find.creator_id);
+        assert_eq!(
+            vec![Visibility::Public, Visibility::Protected],
+            find.visibility_list
+        );
+        assert!(find.filter.is_none());
+
+        let mut find: FindMemo = (&request).try_into().unwrap();
+        find.completed(None, false);
+        assert_eq!(Some(2), find.creator_id);
+        assert_eq!(vec![Visibility::Public], find.visibility_list);
+    }
+}
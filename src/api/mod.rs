@@ -1,7 +1,9 @@
 pub mod auth;
+pub mod idp;
 pub mod inbox;
 pub mod memo;
 pub mod prefix;
+pub mod reaction;
 pub mod resource;
 pub mod user;
 pub mod v1;
@@ -7,7 +7,6 @@ pub const MEMO_NAME_PREFIX: &str = "memos";
 pub const RESOURCE_NAME_PREFIX: &str = "resources";
 #[allow(dead_code)]
 pub const STORAGE_NAME_PREFIX: &str = "storages";
-#[allow(dead_code)]
 pub const IDENTITY_PROVIDER_NAME_PREFIX: &str = "identityProviders";
 
 #[macro_export]
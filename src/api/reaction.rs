@@ -0,0 +1,14 @@
+use crate::api::prefix;
+
+use super::v1::gen::Reaction;
+
+impl From<crate::model::reaction::Reaction> for Reaction {
+    fn from(value: crate::model::reaction::Reaction) -> Self {
+        Self {
+            id: value.id,
+            creator: format!("{}/{}", prefix::USER_NAME_PREFIX, value.creator_id),
+            content_id: value.content_id,
+            reaction_type: value.reaction_type,
+        }
+    }
+}
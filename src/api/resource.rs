@@ -6,15 +6,17 @@ use crate::{
         to_timestamp,
     },
     impl_extract_name,
+    model::gen::ResourceStorageType,
 };
 
 use super::{
     prefix::FormatName,
-    v1::gen::{DeleteResourceRequest, GetResourceRequest, Resource},
+    v1::gen::{DeleteResourceRequest, GetResourceBinaryRequest, GetResourceRequest, Resource},
 };
 
 impl_extract_name!(GetResourceRequest, prefix::RESOURCE_NAME_PREFIX);
 impl_extract_name!(DeleteResourceRequest, prefix::RESOURCE_NAME_PREFIX);
+impl_extract_name!(GetResourceBinaryRequest, prefix::RESOURCE_NAME_PREFIX);
 impl_extract_name!(Resource, prefix::RESOURCE_NAME_PREFIX);
 
 impl Resource {
@@ -37,7 +39,12 @@ impl From<crate::model::resource::Resource> for Resource {
             create_time: to_timestamp(value.created_ts),
             filename: value.filename,
             content: value.blob,
-            external_link: value.reference,
+            // 本地与 S3 存储由服务端读取，仅外部链接直接暴露
+            external_link: if value.storage_type == ResourceStorageType::External {
+                value.reference
+            } else {
+                String::new()
+            },
             r#type: value.r#type,
             size: value.size as i64,
             memo: value
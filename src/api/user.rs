@@ -6,7 +6,10 @@ use crate::{
     model::{gen::UserSettingKey, user::UserSetting},
 };
 
-use super::v1::r#gen::GetUserStatsRequest;
+use super::v1::r#gen::{
+    CreateUserAccessTokenRequest, DeleteUserAccessTokenRequest, DeleteUserRequest,
+    GetUserStatsRequest, ListUserAccessTokensRequest,
+};
 use super::{
     prefix::FormatName,
     v1::gen::{
@@ -16,6 +19,11 @@ use super::{
 
 impl_extract_name!(GetUserRequest, prefix::USER_NAME_PREFIX);
 impl_extract_name!(GetUserStatsRequest, prefix::USER_NAME_PREFIX);
+impl_extract_name!(User, prefix::USER_NAME_PREFIX);
+impl_extract_name!(DeleteUserRequest, prefix::USER_NAME_PREFIX);
+impl_extract_name!(ListUserAccessTokensRequest, prefix::USER_NAME_PREFIX);
+impl_extract_name!(CreateUserAccessTokenRequest, prefix::USER_NAME_PREFIX);
+impl_extract_name!(DeleteUserAccessTokenRequest, prefix::USER_NAME_PREFIX);
 
 impl From<Vec<UserSetting>> for UserSettingApi {
     fn from(value: Vec<UserSetting>) -> Self {
@@ -42,7 +50,8 @@ impl From<crate::model::user::User> for User {
             nickname: value.nickname,
             avatar_url: value.avatar_url,
             description: value.description,
-            password: value.password_hash,
+            // 不返回密码摘要
+            password: String::new(),
             state: value.state as i32,
             create_time: to_timestamp(value.created_ts),
             update_time: to_timestamp(value.updated_ts),
@@ -6,19 +6,16 @@ use std::task::{Context, Poll};
 use async_trait::async_trait;
 use axum::{http::StatusCode, response::Result};
 use axum_login::tower_sessions::{SessionManager, SessionStore};
-use axum_login::{AuthManager, AuthManagerLayer, AuthUser, AuthnBackend, UserId};
-use hyper::{Request, Response};
-use pin_project_lite::pin_project;
+use axum_login::{AuthManager, AuthManagerLayer, AuthSession, AuthUser, AuthnBackend, UserId};
+use hyper::{header::AUTHORIZATION, HeaderMap, Request, Response};
 use tower::{Layer, Service};
 use tower_cookies::CookieManager;
 use tracing::info;
 
-use crate::api::v1::gen::SignInRequest;
+use crate::api::v1::gen::{SignInRequest, State};
 use crate::model::user::User;
 use crate::svc::user::{Error, UserService};
 
-use super::AuthSession;
-
 impl AuthUser for User {
     type Id = i32;
 
@@ -62,35 +59,53 @@ impl<U: UserService> AuthnBackend for Backend<U> {
 
     async fn get_user(&self, user_id: &UserId<Self>) -> Result<Option<Self::User>, Self::Error> {
         let user = self.svc.petch_user(*user_id).await?;
-        Ok(Some(user))
+        // 归档的用户会话随即失效
+        Ok((user.state != State::Archived).then_some(user))
     }
 }
 
 #[derive(Clone)]
 pub struct AuthLayer<T: UserService, SS: SessionStore> {
     auth_manager_layer: AuthManagerLayer<Backend<T>, SS>,
+    svc: Arc<T>,
     public_path: Vec<String>,
+    /// 只解析当前用户，是否需要登录交由处理函数判断
+    optional: bool,
 }
 
 impl<T: UserService, SS: SessionStore> AuthLayer<T, SS> {
     pub fn new(
         auth_manager_layer: AuthManagerLayer<Backend<T>, SS>,
+        svc: Arc<T>,
         public_path: Vec<String>,
     ) -> Self {
         Self {
             auth_manager_layer,
+            svc,
             public_path,
+            optional: false,
+        }
+    }
+
+    pub fn optional(auth_manager_layer: AuthManagerLayer<Backend<T>, SS>, svc: Arc<T>) -> Self {
+        Self {
+            auth_manager_layer,
+            svc,
+            public_path: Vec::new(),
+            optional: true,
         }
     }
 }
 
 impl<S, T: UserService, SS: SessionStore> Layer<S> for AuthLayer<T, SS> {
-    type Service = CookieManager<SessionManager<AuthManager<AuthService<S>, Backend<T>>, SS>>;
+    type Service = CookieManager<SessionManager<AuthManager<AuthService<S, T>, Backend<T>>, SS>>;
 
     fn layer(&self, inner: S) -> Self::Service {
         let auth_service = AuthService {
             inner,
+            svc: self.svc.clone(),
             public_path: self.public_path.clone(),
+            optional: self.optional,
         };
 
         self.auth_manager_layer.layer(auth_service)
@@ -98,92 +113,80 @@ impl<S, T: UserService, SS: SessionStore> Layer<S> for AuthLayer<T, SS> {
 }
 
 #[derive(Clone, Debug)]
-pub struct AuthService<S> {
+pub struct AuthService<S, T> {
     pub inner: S,
+    pub svc: Arc<T>,
     pub public_path: Vec<String>,
+    pub optional: bool,
 }
 
-impl<ReqBody, ResBody, S> Service<Request<ReqBody>> for AuthService<S>
+impl<ReqBody, ResBody, S, T> Service<Request<ReqBody>> for AuthService<S, T>
 where
-    S: Service<Request<ReqBody>, Response = Response<ResBody>>,
+    S: Service<Request<ReqBody>, Response = Response<ResBody>> + Clone + Send + 'static,
+    S::Future: Send,
+    ReqBody: Send + 'static,
     ResBody: Default + Send + 'static,
+    T: UserService,
 {
     type Response = Response<ResBody>;
     type Error = S::Error;
-    type Future = ResponseFuture<S::Future, ResBody>;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
 
     #[inline]
     fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
         self.inner.poll_ready(cx)
     }
 
-    fn call(&mut self, req: Request<ReqBody>) -> Self::Future {
+    fn call(&mut self, mut req: Request<ReqBody>) -> Self::Future {
+        // 取走已就绪的 inner，留下克隆供下次调用
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let svc = self.svc.clone();
         let path = req.uri().path().to_owned();
-        if self.public_path.contains(&path) {
-            info!("public path: {path}");
-            ResponseFuture::future(self.inner.call(req))
-        } else if let Some(AuthSession { user: Some(_), .. }) =
-            req.extensions().get::<AuthSession>()
-        {
-            ResponseFuture::future(self.inner.call(req))
-        } else {
-            let res = Response::builder()
-                .status(StatusCode::UNAUTHORIZED)
-                .body(ResBody::default())
-                .ok();
-            ResponseFuture::unauthorized(res)
-        }
-    }
-}
-
-pin_project! {
-    /// Response future for [`ValidateRequestHeader`].
-    pub struct ResponseFuture<F, B> {
-        #[pin]
-        kind: Kind<F, B>,
-    }
-}
-
-impl<F, B> ResponseFuture<F, B> {
-    fn future(future: F) -> Self {
-        Self {
-            kind: Kind::Future { future },
-        }
-    }
+        let public = self.optional || self.public_path.contains(&path);
+
+        Box::pin(async move {
+            // Bearer 令牌先于会话校验，令牌无效时不再回退到会话
+            if let Some(token) = bearer_token(req.headers()) {
+                match svc.authenticate_token(&token).await {
+                    Ok(user) => {
+                        if let Some(auth_session) =
+                            req.extensions_mut().get_mut::<AuthSession<Backend<T>>>()
+                        {
+                            auth_session.user = Some(user);
+                        }
+                    }
+                    Err(e) => {
+                        info!("{e}");
+                        return Ok(unauthorized());
+                    }
+                }
+            }
 
-    fn unauthorized(response: Option<Response<B>>) -> Self {
-        Self {
-            kind: Kind::Error { response },
-        }
+            if public {
+                info!("public path: {path}");
+                inner.call(req).await
+            } else if let Some(AuthSession { user: Some(_), .. }) =
+                req.extensions().get::<AuthSession<Backend<T>>>()
+            {
+                inner.call(req).await
+            } else {
+                Ok(unauthorized())
+            }
+        })
     }
 }
 
-pin_project! {
-    #[project = KindProj]
-    enum Kind<F, B> {
-        Future {
-            #[pin]
-            future: F,
-        },
-        Error {
-            response: Option<Response<B>>,
-        },
-    }
+fn bearer_token(headers: &HeaderMap) -> Option<String> {
+    let value = headers.get(AUTHORIZATION)?.to_str().ok()?;
+    let (scheme, token) = value.split_once(' ')?;
+    scheme
+        .eq_ignore_ascii_case("bearer")
+        .then(|| token.trim().to_owned())
 }
 
-impl<F, B, E> Future for ResponseFuture<F, B>
-where
-    F: Future<Output = Result<Response<B>, E>>,
-{
-    type Output = F::Output;
-
-    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        match self.project().kind.project() {
-            KindProj::Future { future } => future.poll(cx),
-            KindProj::Error { response } => {
-                let response = response.take().expect("future polled after completion");
-                Poll::Ready(Ok(response))
-            }
-        }
-    }
+fn unauthorized<B: Default>() -> Response<B> {
+    let mut res = Response::new(B::default());
+    *res.status_mut() = StatusCode::UNAUTHORIZED;
+    res
 }
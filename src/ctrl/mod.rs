@@ -1,11 +1,15 @@
 pub mod auth;
 
-use axum::http::StatusCode;
+use std::convert::Infallible;
+
+use axum::extract::FromRequestParts;
+use axum::http::{request::Parts, StatusCode};
 use axum::response::{IntoResponse, Response};
 use hyper::header;
 use tracing::error;
 
 use crate::dao::turso::Turso;
+use crate::model::user::User;
 use crate::svc::Service;
 
 use self::auth::Backend;
@@ -16,6 +20,23 @@ use self::auth::Backend;
 pub type AuthSession = axum_login::AuthSession<Backend<Service<Turso>>>;
 pub type AuthError = axum_login::Error<Backend<Service<Turso>>>;
 
+/// 当前登录用户，取自 AuthManager 写入请求扩展的 AuthSession
+///
+/// axum-login 0.15 基于 axum 0.7，AuthSession 不能直接作为 axum 0.8 的提取器
+pub struct CurrentUser(pub Option<User>);
+
+impl<S: Send + Sync> FromRequestParts<S> for CurrentUser {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let user = parts
+            .extensions
+            .get::<AuthSession>()
+            .and_then(|s| s.user.clone());
+        Ok(Self(user))
+    }
+}
+
 impl IntoResponse for crate::svc::session::Error {
     fn into_response(self) -> Response {
         error_response(StatusCode::BAD_REQUEST, self)
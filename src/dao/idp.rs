@@ -0,0 +1,82 @@
+use async_trait::async_trait;
+use snafu::Snafu;
+
+use crate::model::idp::{CreateIdentityProvider, IdentityProvider, UpdateIdentityProvider};
+use crate::model::user::{CreateUser, User};
+
+#[async_trait]
+pub trait IdpRepository: Clone + Send + Sync + 'static {
+    async fn list_idps(&self) -> Result<Vec<IdentityProvider>, ListIdpError>;
+    async fn find_idp(&self, id: i32) -> Result<Option<IdentityProvider>, FindIdpError>;
+    async fn create_idp(
+        &self,
+        create: CreateIdentityProvider,
+    ) -> Result<Option<IdentityProvider>, CreateIdpError>;
+    async fn update_idp(&self, update: UpdateIdentityProvider) -> Result<(), UpdateIdpError>;
+    /// 同时删除该身份提供方的用户关联
+    async fn delete_idp(&self, id: i32) -> Result<(), DeleteIdpError>;
+    /// 与该身份关联的本地用户
+    async fn find_identity_user(
+        &self,
+        idp_id: i32,
+        identifier: &str,
+    ) -> Result<Option<User>, FindIdentityUserError>;
+    /// 创建用户并关联到该身份
+    async fn create_identity_user(
+        &self,
+        idp_id: i32,
+        identifier: &str,
+        create: CreateUser,
+    ) -> Result<Option<User>, CreateIdentityUserError>;
+}
+
+#[derive(Debug, Snafu)]
+#[snafu(context(false), display("Failed to list identity providers: {source}"))]
+pub struct ListIdpError {
+    source: anyhow::Error,
+}
+
+#[derive(Debug, Snafu)]
+#[snafu(context(false), display("Failed to find identity provider: {source}"))]
+pub struct FindIdpError {
+    source: anyhow::Error,
+}
+
+#[derive(Debug, Snafu)]
+#[snafu(
+    context(false),
+    display("Failed to create identity provider: {source}")
+)]
+pub struct CreateIdpError {
+    source: anyhow::Error,
+}
+
+#[derive(Debug, Snafu)]
+#[snafu(
+    context(false),
+    display("Failed to update identity provider: {source}")
+)]
+pub struct UpdateIdpError {
+    source: anyhow::Error,
+}
+
+#[derive(Debug, Snafu)]
+#[snafu(
+    context(false),
+    display("Failed to delete identity provider: {source}")
+)]
+pub struct DeleteIdpError {
+    source: anyhow::Error,
+}
+
+#[derive(Debug, Snafu)]
+#[snafu(context(false), display("Failed to find identity user: {source}"))]
+pub struct FindIdentityUserError {
+    source: anyhow::Error,
+}
+
+#[derive(Debug, Snafu)]
+#[snafu(context(false), display("Failed to create identity user: {source}"))]
+pub struct CreateIdentityUserError {
+    source: anyhow::Error,
+}
@@ -7,8 +7,11 @@ use crate::model::memo::{CreateMemo, FindMemo, Memo, UpdateMemo};
 pub trait MemoRepository: Clone + Send + Sync + 'static {
     async fn create_memo(&self, memo: CreateMemo) -> Result<Option<Memo>, CreateMemoError>;
     async fn list_memos(&self, find: FindMemo) -> Result<Vec<Memo>, ListMemoError>;
-    async fn delete_memo(&self, memo_id: i32) -> Result<(), DeleteMemoError>;
     async fn update_memo(&self, update: UpdateMemo) -> Result<(), UpdateMemoError>;
+    /// 在同一事务中批量更新
+    async fn update_memos(&self, updates: Vec<UpdateMemo>) -> Result<(), UpdateMemoError>;
+    /// 在同一事务中删除 memo 及其关联、表态与修订
+    async fn delete_memos(&self, memo_ids: Vec<i32>) -> Result<(), DeleteMemoError>;
 }
 
 #[derive(Debug, Snafu)]
@@ -0,0 +1,44 @@
+use async_trait::async_trait;
+use snafu::Snafu;
+
+use crate::api::v1::gen::memo_relation::Type as RelationType;
+use crate::model::memo::{FindMemoRelation, MemoRelation};
+
+#[async_trait]
+pub trait MemoRelationRepository: Clone + Send + Sync + 'static {
+    async fn upsert_memo_relation(
+        &self,
+        memo_id: i32,
+        related_memo_id: i32,
+        r#type: RelationType,
+    ) -> Result<(), UpsertMemoRelationError>;
+    /// 以 related_memo_ids 覆盖 memo 该类型的全部关联
+    async fn set_memo_relations(
+        &self,
+        memo_id: i32,
+        r#type: RelationType,
+        related_memo_ids: Vec<i32>,
+    ) -> Result<(), SetMemoRelationError>;
+    async fn list_memo_relations(
+        &self,
+        find: FindMemoRelation,
+    ) -> Result<Vec<MemoRelation>, ListMemoRelationError>;
+}
+
+#[derive(Debug, Snafu)]
+#[snafu(context(false), display("Failed to upsert memo relation: {source}"))]
+pub struct UpsertMemoRelationError {
+    source: anyhow::Error,
+}
+
+#[derive(Debug, Snafu)]
+#[snafu(context(false), display("Failed to set memo relation: {source}"))]
+pub struct SetMemoRelationError {
+    source: anyhow::Error,
+}
+
+#[derive(Debug, Snafu)]
+#[snafu(context(false), display("Failed to list memo relation: {source}"))]
+pub struct ListMemoRelationError {
+    source: anyhow::Error,
+}
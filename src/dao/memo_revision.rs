@@ -0,0 +1,19 @@
+use async_trait::async_trait;
+use snafu::Snafu;
+
+use crate::model::memo::{FindMemoRevision, MemoRevision};
+
+#[async_trait]
+pub trait MemoRevisionRepository: Clone + Send + Sync + 'static {
+    /// 按 id 倒序，最近的修订在前
+    async fn list_memo_revisions(
+        &self,
+        find: FindMemoRevision,
+    ) -> Result<Vec<MemoRevision>, ListMemoRevisionError>;
+}
+
+#[derive(Debug, Snafu)]
+#[snafu(context(false), display("Failed to list memo revision: {source}"))]
+pub struct ListMemoRevisionError {
+    source: anyhow::Error,
+}
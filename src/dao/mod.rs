@@ -1,4 +1,9 @@
+pub mod idp;
 pub mod memo;
+pub mod memo_relation;
+pub mod memo_revision;
+pub mod quota;
+pub mod reaction;
 pub mod resource;
 pub mod session;
 pub mod turso;
@@ -0,0 +1,30 @@
+use async_trait::async_trait;
+use snafu::Snafu;
+
+use crate::model::user::{StorageUsage, UserQuota};
+
+#[async_trait]
+pub trait QuotaRepository: Clone + Send + Sync + 'static {
+    async fn find_user_quota(&self, user_id: i32) -> Result<Option<UserQuota>, FindUserQuotaError>;
+    async fn upsert_user_quota(&self, quota: UserQuota) -> Result<(), UpsertUserQuotaError>;
+    /// 含回收站中尚未清理的资源
+    async fn get_storage_usage(&self, user_id: i32) -> Result<StorageUsage, GetStorageUsageError>;
+}
+
+#[derive(Debug, Snafu)]
+#[snafu(context(false), display("Failed to find user quota: {source}"))]
+pub struct FindUserQuotaError {
+    source: anyhow::Error,
+}
+
+#[derive(Debug, Snafu)]
+#[snafu(context(false), display("Failed to upsert user quota: {source}"))]
+pub struct UpsertUserQuotaError {
+    source: anyhow::Error,
+}
+
+#[derive(Debug, Snafu)]
+#[snafu(context(false), display("Failed to get storage usage: {source}"))]
+pub struct GetStorageUsageError {
+    source: anyhow::Error,
+}
@@ -0,0 +1,32 @@
+use async_trait::async_trait;
+use snafu::Snafu;
+
+use crate::model::reaction::{FindReaction, Reaction};
+
+#[async_trait]
+pub trait ReactionRepository: Clone + Send + Sync + 'static {
+    async fn upsert_reaction(
+        &self,
+        reaction: Reaction,
+    ) -> Result<Option<Reaction>, UpsertReactionError>;
+    async fn list_reactions(&self, find: FindReaction) -> Result<Vec<Reaction>, ListReactionError>;
+    async fn delete_reaction(&self, id: i32, creator_id: i32) -> Result<(), DeleteReactionError>;
+}
+
+#[derive(Debug, Snafu)]
+#[snafu(context(false), display("Failed to upsert reaction: {source}"))]
+pub struct UpsertReactionError {
+    source: anyhow::Error,
+}
+
+#[derive(Debug, Snafu)]
+#[snafu(context(false), display("Failed to list reaction: {source}"))]
+pub struct ListReactionError {
+    source: anyhow::Error,
+}
+
+#[derive(Debug, Snafu)]
+#[snafu(context(false), display("Failed to delete reaction: {source}"))]
+pub struct DeleteReactionError {
+    source: anyhow::Error,
+}
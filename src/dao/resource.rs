@@ -3,7 +3,7 @@ use std::collections::HashMap;
 use async_trait::async_trait;
 use snafu::Snafu;
 
-use crate::model::resource::{FindResource, Resource};
+use crate::model::resource::{FindResource, Resource, ResourceBlob, UpdateResource};
 
 #[async_trait]
 pub trait ResourceRepository: Clone + Send + Sync + 'static {
@@ -19,11 +19,31 @@ pub trait ResourceRepository: Clone + Send + Sync + 'static {
     ) -> Result<(), SetResourceError>;
     async fn get_resource(&self, id: i32) -> Result<Option<Resource>, GetResourceError>;
     async fn list_resources(&self, find: FindResource) -> Result<Vec<Resource>, ListResourceError>;
+    /// 未关联 memo（或 memo 已删除）且在 before 之前更新或移入回收站的资源
+    async fn list_orphan_resources(&self, before: i64) -> Result<Vec<Resource>, ListResourceError>;
     async fn delete_resource(&self, id: i32, creator_id: i32) -> Result<(), DeleteResourceError>;
+    async fn update_resource(&self, update: UpdateResource) -> Result<(), UpdateResourceError>;
     async fn relate_resources(
         &self,
         memo_ids: Vec<i32>,
     ) -> Result<HashMap<i32, Vec<Resource>>, RelateResourceError>;
+    /// 不含 blob 列
+    async fn find_resource_blob(
+        &self,
+        hash: &str,
+    ) -> Result<Option<ResourceBlob>, FindResourceBlobError>;
+    /// 在同一事务中写入内容与引用它的资源，资源改为引用最终保存的内容。
+    /// 返回的 bool 表示 blob 是否为新写入；blob 为空且内容不存在时返回 None
+    async fn create_resource_with_blob(
+        &self,
+        resource: Resource,
+        blob: Option<ResourceBlob>,
+    ) -> Result<Option<(Resource, bool)>, CreateResourceError>;
+    /// 删除不再被引用的内容，返回被删除的记录以便清理存储
+    async fn release_resource_blob(
+        &self,
+        hash: &str,
+    ) -> Result<Option<ResourceBlob>, ReleaseResourceBlobError>;
 }
 
 #[derive(Debug, Snafu)]
@@ -56,8 +76,26 @@ pub struct DeleteResourceError {
     source: anyhow::Error,
 }
 
+#[derive(Debug, Snafu)]
+#[snafu(context(false), display("Failed to update resource: {source}"))]
+pub struct UpdateResourceError {
+    source: anyhow::Error,
+}
+
 #[derive(Debug, Snafu)]
 #[snafu(context(false), display("Failed to relate resource: {source}"))]
 pub struct RelateResourceError {
     source: anyhow::Error,
 }
+
+#[derive(Debug, Snafu)]
+#[snafu(context(false), display("Failed to find resource blob: {source}"))]
+pub struct FindResourceBlobError {
+    source: anyhow::Error,
+}
+
+#[derive(Debug, Snafu)]
+#[snafu(context(false), display("Failed to release resource blob: {source}"))]
+pub struct ReleaseResourceBlobError {
+    source: anyhow::Error,
+}
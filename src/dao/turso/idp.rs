@@ -0,0 +1,151 @@
+use async_trait::async_trait;
+use libsql::{params, Value};
+
+use crate::{
+    dao::idp::{
+        CreateIdentityUserError, CreateIdpError, DeleteIdpError, FindIdentityUserError,
+        FindIdpError, IdpRepository, ListIdpError, UpdateIdpError,
+    },
+    model::{
+        idp::{CreateIdentityProvider, IdentityProvider, UpdateIdentityProvider},
+        user::{CreateUser, User},
+    },
+};
+
+use super::{de, user::USER_FIELDS, Turso};
+
+const IDP_FIELDS: &str = "id, name, type, identifier_filter, config";
+
+#[async_trait]
+impl IdpRepository for Turso {
+    async fn list_idps(&self) -> Result<Vec<IdentityProvider>, ListIdpError> {
+        let sql = format!("select {IDP_FIELDS} from idp order by id");
+        Ok(self.query(sql, ()).await?)
+    }
+
+    async fn find_idp(&self, id: i32) -> Result<Option<IdentityProvider>, FindIdpError> {
+        let sql = format!("select {IDP_FIELDS} from idp where id = ?");
+        let mut rs = self.query(sql, [id]).await?;
+        Ok(rs.pop())
+    }
+
+    async fn create_idp(
+        &self,
+        CreateIdentityProvider {
+            name,
+            r#type,
+            identifier_filter,
+            config,
+        }: CreateIdentityProvider,
+    ) -> Result<Option<IdentityProvider>, CreateIdpError> {
+        let sql = format!("insert into idp (name, type, identifier_filter, config) values (?, ?, ?, ?) returning {IDP_FIELDS}");
+        let mut rs = self
+            .query(
+                sql,
+                params![name, r#type.as_str_name(), identifier_filter, config],
+            )
+            .await?;
+        Ok(rs.pop())
+    }
+
+    async fn update_idp(
+        &self,
+        UpdateIdentityProvider {
+            id,
+            name,
+            identifier_filter,
+            config,
+        }: UpdateIdentityProvider,
+    ) -> Result<(), UpdateIdpError> {
+        let mut sets = Vec::new();
+        let mut args = Vec::new();
+
+        if let Some(name) = name {
+            sets.push("name = ?");
+            args.push(Value::from(name));
+        }
+        if let Some(identifier_filter) = identifier_filter {
+            sets.push("identifier_filter = ?");
+            args.push(Value::from(identifier_filter));
+        }
+        if let Some(config) = config {
+            sets.push("config = ?");
+            args.push(Value::from(config));
+        }
+        if args.is_empty() {
+            return Ok(());
+        }
+
+        args.push(Value::from(id));
+        let sql = format!("update idp set {} where id = ?", sets.join(", "));
+        self.execute(sql, args).await?;
+        Ok(())
+    }
+
+    async fn delete_idp(&self, id: i32) -> Result<(), DeleteIdpError> {
+        let transaction = self.transaction().await?;
+        for sql in [
+            "delete from user_identity where idp_id = ?",
+            "delete from idp where id = ?",
+        ] {
+            let mut stmt = Self::tx_prepare(&transaction, sql).await?;
+            Self::statement_execute(&mut stmt, [id]).await?;
+        }
+        Self::commit(transaction).await?;
+        Ok(())
+    }
+
+    async fn find_identity_user(
+        &self,
+        idp_id: i32,
+        identifier: &str,
+    ) -> Result<Option<User>, FindIdentityUserError> {
+        let sql = format!("select {USER_FIELDS} from user where id = (select user_id from user_identity where idp_id = ? and identifier = ?)");
+        let mut users = self.query(sql, params![idp_id, identifier]).await?;
+        Ok(users.pop())
+    }
+
+    async fn create_identity_user(
+        &self,
+        idp_id: i32,
+        identifier: &str,
+        CreateUser {
+            username,
+            role,
+            email,
+            nickname,
+            avatar_url,
+            description,
+            password_hash,
+        }: CreateUser,
+    ) -> Result<Option<User>, CreateIdentityUserError> {
+        let transaction = self.transaction().await?;
+        let sql = format!("insert into user (username, role, email, nickname, avatar_url, description, password_hash) values (?, ?, ?, ?, ?, ?, ?) returning {USER_FIELDS}");
+        let mut stmt = Self::tx_prepare(&transaction, sql).await?;
+        let rows = Self::statement_query(
+            &mut stmt,
+            params![
+                username,
+                role.as_str_name(),
+                email,
+                nickname,
+                avatar_url,
+                description,
+                password_hash
+            ],
+        )
+        .await?;
+        let Some(user) = de::<User>(rows).await?.pop() else {
+            return Ok(None);
+        };
+
+        let mut stmt = Self::tx_prepare(
+            &transaction,
+            "insert into user_identity (idp_id, identifier, user_id) values (?, ?, ?)",
+        )
+        .await?;
+        Self::statement_execute(&mut stmt, params![idp_id, identifier, user.id]).await?;
+        Self::commit(transaction).await?;
+        Ok(Some(user))
+    }
+}
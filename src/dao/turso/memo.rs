@@ -3,13 +3,15 @@ mod find;
 mod update;
 
 use async_trait::async_trait;
+use libsql::params;
 
+use crate::api::prefix::MEMO_NAME_PREFIX;
 use crate::dao::memo::{
     CreateMemoError, DeleteMemoError, ListMemoError, MemoRepository, UpdateMemoError,
 };
 use crate::model::memo::{CreateMemo, FindMemo, Memo, UpdateMemo};
 
-use super::Turso;
+use super::{ToCriteria, Turso};
 
 #[async_trait]
 impl MemoRepository for Turso {
@@ -22,14 +24,56 @@ impl MemoRepository for Turso {
         Ok(self.query_criteria(finder).await?)
     }
 
-    async fn delete_memo(&self, memo_id: i32) -> Result<(), DeleteMemoError> {
-        self.execute("delete from memo where id = ?", [memo_id])
-            .await?;
+    async fn update_memo(&self, updator: UpdateMemo) -> Result<(), UpdateMemoError> {
+        self.execute_criteria(updator).await?;
         Ok(())
     }
 
-    async fn update_memo(&self, updator: UpdateMemo) -> Result<(), UpdateMemoError> {
-        self.execute_criteria(updator).await?;
+    async fn update_memos(&self, updates: Vec<UpdateMemo>) -> Result<(), UpdateMemoError> {
+        let transaction = self.transaction().await?;
+        for update in updates {
+            let (sql, params) = update.to_criteria();
+            if sql.as_ref().is_empty() {
+                continue;
+            }
+            let mut stmt = Self::tx_prepare(&transaction, sql).await?;
+            Self::statement_execute(&mut stmt, params).await?;
+        }
+        Self::commit(transaction).await?;
+        Ok(())
+    }
+
+    async fn delete_memos(&self, memo_ids: Vec<i32>) -> Result<(), DeleteMemoError> {
+        if memo_ids.is_empty() {
+            return Ok(());
+        }
+
+        let transaction = self.transaction().await?;
+        let mut memo_stmt = Self::tx_prepare(&transaction, "delete from memo where id = ?").await?;
+        let mut relation_stmt = Self::tx_prepare(
+            &transaction,
+            "delete from memo_relation where memo_id = ? or related_memo_id = ?",
+        )
+        .await?;
+        let mut reaction_stmt =
+            Self::tx_prepare(&transaction, "delete from reaction where content_id = ?").await?;
+        let mut revision_stmt =
+            Self::tx_prepare(&transaction, "delete from memo_revision where memo_id = ?").await?;
+        for memo_id in memo_ids {
+            Self::statement_execute(&mut memo_stmt, [memo_id]).await?;
+            memo_stmt.reset();
+            Self::statement_execute(&mut relation_stmt, [memo_id, memo_id]).await?;
+            relation_stmt.reset();
+            Self::statement_execute(
+                &mut reaction_stmt,
+                params![format!("{MEMO_NAME_PREFIX}/{memo_id}")],
+            )
+            .await?;
+            reaction_stmt.reset();
+            Self::statement_execute(&mut revision_stmt, [memo_id]).await?;
+            revision_stmt.reset();
+        }
+        Self::commit(transaction).await?;
         Ok(())
     }
 }
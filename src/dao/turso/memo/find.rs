@@ -1,12 +1,11 @@
-use cel_parser::{parse, ArithmeticOp, Atom, Expression, RelationOp, UnaryOp};
 use libsql::Value;
 use sql_query_builder::Select;
-use time::{format_description::well_known::Rfc3339, OffsetDateTime};
 
 use crate::{
     api::v1::gen::Direction,
     dao::turso::ToCriteria,
     model::{
+        filter::{Field, Filter, TextOp},
         memo::{FindMemo, FindMemoPayload},
         pager::Paginator as _,
     },
@@ -16,6 +15,7 @@ impl ToCriteria for FindMemo {
     fn to_criteria(self) -> (impl AsRef<str>, impl libsql::params::IntoParams) {
         let FindMemo {
             id,
+            id_list,
             uid,
             state,
             creator_id,
@@ -27,6 +27,8 @@ impl ToCriteria for FindMemo {
             visibility_list,
             payload_find,
             exclude_content,
+            exclude_comments,
+            random,
             page_token,
             only_payload,
             order_by_pinned,
@@ -37,11 +39,27 @@ impl ToCriteria for FindMemo {
             ..
         } = self;
 
-        let mut sql = Select::new()
-            .from("memo")
-            .left_join("JSON_EACH(memo.payload, '$.tags')");
+        let mut sql = Select::new().from("memo").left_join(
+            "memo_relation ON memo.id = memo_relation.memo_id AND memo_relation.type = 'COMMENT'",
+        );
         let mut params = Vec::new();
 
+        // 全文检索时按相关度排序并返回高亮片段
+        let fts_query = filter
+            .as_ref()
+            .map(|f| f.content_matches())
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(to_fts_query)
+            .collect::<Vec<_>>()
+            .join(" OR ");
+        if !fts_query.is_empty() {
+            sql = sql.left_join(
+                "(SELECT rowid, rank, snippet(memo_fts, 0, '<mark>', '</mark>', '...', 16) AS snippet FROM memo_fts WHERE memo_fts MATCH ?) AS fts ON fts.rowid = memo.id",
+            );
+            params.push(Value::from(fts_query.clone()));
+        }
+
         if only_payload {
             sql = sql
                 .select("memo.id AS id")
@@ -54,12 +72,17 @@ impl ToCriteria for FindMemo {
                 .select("memo.id AS id")
                 .select("memo.uid AS uid")
                 .select("memo.creator_id AS creator_id")
+                .select("(SELECT role FROM user WHERE user.id = memo.creator_id) AS creator_role")
                 .select("memo.created_ts AS created_ts")
                 .select("memo.updated_ts AS updated_ts")
                 .select("memo.row_status AS state")
                 .select("memo.visibility AS visibility")
                 .select("memo.pinned AS pinned")
-                .select("memo.payload AS payload");
+                .select("memo.payload AS payload")
+                .select("memo_relation.related_memo_id AS parent_id");
+            if !fts_query.is_empty() {
+                sql = sql.select("fts.snippet AS snippet");
+            }
         };
 
         if !exclude_content && !only_payload {
@@ -70,6 +93,16 @@ impl ToCriteria for FindMemo {
             sql = sql.where_and("memo.id = ?");
             params.push(Value::from(id));
         }
+        let w_id;
+        if !id_list.is_empty() {
+            let mut l = Vec::new();
+            for id in id_list {
+                params.push(Value::from(id));
+                l.push("?");
+            }
+            w_id = format!("memo.id in ({})", l.join(", "));
+            sql = sql.where_and(w_id.as_str());
+        }
         if let Some(uid) = uid {
             sql = sql.where_and("memo.uid = ?");
             params.push(Value::from(uid))
@@ -98,9 +131,17 @@ impl ToCriteria for FindMemo {
             sql = sql.where_and("memo.updated_ts > ?");
             params.push(Value::from(updated_ts_after));
         }
+        if exclude_comments {
+            sql = sql.where_and("memo_relation.related_memo_id IS NULL");
+        }
+        let mut w_contents = Vec::new();
         for content_search in content_search.iter() {
-            sql = sql.where_and("memo.content LIKE ?");
-            params.push(Value::from(format!("%{content_search}%")));
+            let (content_sql, mut content_params) = convert_content_search_to_sql(content_search);
+            w_contents.push(content_sql);
+            params.append(&mut content_params);
+        }
+        for w_content in w_contents.iter() {
+            sql = sql.where_and(w_content.as_str());
         }
 
         let w;
@@ -114,6 +155,7 @@ impl ToCriteria for FindMemo {
             sql = sql.where_and(w.as_str());
         }
 
+        let mut w_tags = Vec::new();
         if let Some(FindMemoPayload {
             raw,
             tags,
@@ -127,11 +169,13 @@ impl ToCriteria for FindMemo {
                 sql = sql.where_and("memo.payload = ?");
                 params.push(Value::from(raw));
             }
-            if let Some(tags) = tags {
-                for tag in tags {
-                    sql = sql.where_and("JSON_EACH.value = ?");
-                    params.push(Value::from(tag));
-                }
+            for tag in tags.unwrap_or_default() {
+                let (tag_sql, mut tag_params) = convert_tags_to_sql(&[tag]);
+                w_tags.push(tag_sql);
+                params.append(&mut tag_params);
+            }
+            for w_tag in w_tags.iter() {
+                sql = sql.where_and(w_tag.as_str());
             }
             if has_link {
                 sql = sql.where_and("JSON_EXTRACT(memo.payload, '$.property.has_link') IS TRUE");
@@ -150,17 +194,31 @@ impl ToCriteria for FindMemo {
             }
         }
 
-        let (filter_sql, mut filter_params) = convert_ecl_to_sql(&filter);
-        if !filter_sql.is_empty() {
-            sql = sql.where_and(filter_sql.as_str());
+        let w_filter;
+        if let Some(filter) = filter {
+            let (filter_sql, mut filter_params) =
+                convert_filter_to_sql(&filter, order_by_updated_ts);
+            w_filter = filter_sql;
+            sql = sql.where_and(w_filter.as_str());
             params.append(&mut filter_params);
         }
 
+        if !fts_query.is_empty() {
+            sql = sql.order_by("fts.rank");
+        }
+
         if !only_payload && order_by_pinned {
             sql = sql.order_by("pinned DESC");
         }
 
-        if !only_payload || direction != Direction::Unspecified {
+        if random {
+            sql = sql.order_by("RANDOM()");
+        } else if !only_payload || direction != Direction::Unspecified {
+            let direction = if direction == Direction::Asc {
+                Direction::Asc
+            } else {
+                Direction::Desc
+            };
             if order_by_updated_ts {
                 sql = sql.order_by(&format!("updated_ts {}", direction.as_str_name()));
             } else {
@@ -178,164 +236,203 @@ impl ToCriteria for FindMemo {
     }
 }
 
-fn convert_ecl_to_sql(filter: &str) -> (String, Vec<Value>) {
-    if filter.is_empty() {
-        return (String::default(), Vec::default());
+/// 标签匹配自身及其子标签，如 work 匹配 work/projectA
+fn convert_tags_to_sql(tags: &[String]) -> (String, Vec<Value>) {
+    let mut wheres = Vec::new();
+    let mut params = Vec::new();
+    for tag in tags {
+        wheres.push("(tag.value = ? OR tag.value LIKE ? ESCAPE '\\')");
+        params.push(Value::from(tag.to_owned()));
+        params.push(Value::from(format!("{}/%", escape_like(tag))));
     }
+    (
+        format!(
+            "EXISTS (SELECT 1 FROM JSON_EACH(memo.payload, '$.tags') AS tag WHERE {})",
+            wheres.join(" OR ")
+        ),
+        params,
+    )
+}
 
-    let expr = parse(filter).unwrap();
-    if matches!(expr, Expression::Atom(_)) {
-        return (String::default(), Vec::default());
+/// 拆分为 FTS5 短语，trigram 分词下少于 3 个字符的词无法命中
+fn to_fts_query(text: &str) -> Option<String> {
+    let terms: Vec<String> = text
+        .split_whitespace()
+        .filter(|t| t.chars().count() >= 3)
+        .map(|t| format!("\"{}\"", t.replace('"', "\"\"")))
+        .collect();
+    if terms.is_empty() {
+        None
+    } else {
+        Some(terms.join(" "))
     }
+}
 
-    convert_expr_to_sql(expr)
+/// 长词走 memo_fts，短词退回 LIKE
+fn convert_content_search_to_sql(text: &str) -> (String, Vec<Value>) {
+    let mut wheres = Vec::new();
+    let mut params = Vec::new();
+    if let Some(query) = to_fts_query(text) {
+        wheres.push("memo.id IN (SELECT rowid FROM memo_fts WHERE memo_fts MATCH ?)");
+        params.push(Value::from(query));
+    }
+    for term in text.split_whitespace().filter(|t| t.chars().count() < 3) {
+        wheres.push("memo.content LIKE ? ESCAPE '\\'");
+        params.push(Value::from(format!("%{}%", escape_like(term))));
+    }
+    if wheres.is_empty() {
+        return (String::from("1 = 1"), params);
+    }
+    (format!("({})", wheres.join(" AND ")), params)
 }
 
-fn convert_expr_to_sql(expr: Expression) -> (String, Vec<Value>) {
-    match expr {
-        Expression::Or(expr1, expr2) => {
-            let (sql1, mut param1) = convert_expr_to_sql(*expr1);
-            let (sql2, mut param2) = convert_expr_to_sql(*expr2);
-            param1.append(&mut param2);
-            (format!("({sql1} OR {sql2})"), param1)
-        }
-        Expression::And(expr1, expr2) => {
-            let (sql1, mut param1) = convert_expr_to_sql(*expr1);
-            let (sql2, mut param2) = convert_expr_to_sql(*expr2);
+fn escape_like(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+/// display_time 随设置取更新时间或创建时间
+fn convert_filter_to_sql(filter: &Filter, display_with_updated_ts: bool) -> (String, Vec<Value>) {
+    match filter {
+        Filter::And(f1, f2) | Filter::Or(f1, f2) => {
+            let (sql1, mut param1) = convert_filter_to_sql(f1, display_with_updated_ts);
+            let (sql2, mut param2) = convert_filter_to_sql(f2, display_with_updated_ts);
             param1.append(&mut param2);
-            (format!("({sql1} AND {sql2})"), param1)
-        }
-        Expression::Relation(expr1, op, expr2) => {
-            let (sql1, mut param1) = convert_expr_to_sql(*expr1);
-            let (sql2, mut param2) = if sql1.contains("time") {
-                if let Expression::Atom(Atom::String(s)) = *expr2 {
-                    if let Ok(time) = OffsetDateTime::parse(&s, &Rfc3339) {
-                        ("?".to_string(), vec![Value::from(time.unix_timestamp())])
-                    } else {
-                        ("?".to_string(), vec![Value::from(s.to_string())])
-                    }
-                } else {
-                    convert_expr_to_sql(*expr2)
-                }
+            let op = if matches!(filter, Filter::And(..)) {
+                "AND"
             } else {
-                convert_expr_to_sql(*expr2)
-            };
-            param1.append(&mut param2);
-            let op = match op {
-                RelationOp::Equals => "=",
-                RelationOp::NotEquals => "<>",
-                RelationOp::LessThan => "<",
-                RelationOp::LessThanEq => "<=",
-                RelationOp::GreaterThan => ">",
-                RelationOp::GreaterThanEq => ">=",
-                RelationOp::In => "IN",
+                "OR"
             };
             (format!("({sql1} {op} {sql2})"), param1)
         }
-        Expression::Unary(op, expr) => {
-            let (sql, param) = convert_expr_to_sql(*expr);
-            let op = match op {
-                UnaryOp::Not => "NOT ",
-                UnaryOp::Minus => "-",
-                _ => "",
-            };
-            (format!("{op}({sql})"), param)
+        Filter::Not(f) => {
+            let (sql, param) = convert_filter_to_sql(f, display_with_updated_ts);
+            (format!("NOT ({sql})"), param)
         }
-        Expression::List(expr_list) => {
-            let mut sql = String::new();
-            let mut param = Vec::new();
-            for (i, expr) in expr_list.iter().enumerate() {
-                let (s, mut p) = convert_expr_to_sql(expr.clone());
-                if i == 0 {
-                    sql.push_str(&s);
-                } else {
-                    sql.push_str(&format!(", {s}"));
+        Filter::Flag(field) => {
+            let sql = match field {
+                Field::HasLink => "JSON_EXTRACT(memo.payload, '$.property.has_link') IS TRUE",
+                Field::HasTaskList => {
+                    "JSON_EXTRACT(memo.payload, '$.property.has_task_list') IS TRUE"
                 }
-                param.append(&mut p);
-            }
-            (format!("({sql})"), param)
-        }
-        Expression::Atom(atom) => match atom {
-            Atom::Int(int) => ("?".to_string(), vec![Value::from(int)]),
-            Atom::UInt(uint) => ("?".to_string(), vec![Value::from(uint as i64)]),
-            Atom::Float(float) => ("?".to_string(), vec![Value::from(float)]),
-            Atom::String(s) => ("?".to_string(), vec![Value::from(s.to_string())]),
-            Atom::Bytes(bytes) => ("?".to_string(), vec![Value::from(bytes.to_vec())]),
-            Atom::Bool(bl) => ("?".to_string(), vec![Value::from(if bl { 1 } else { 0 })]),
-            Atom::Null => ("NULL".to_string(), Vec::default()),
-        },
-        Expression::Ident(ident) => {
-            let ident = match ident.as_str() {
-                "tag" => "JSON_EACH.value",
-                "pinned" => "pinned IS TRUE",
-                "has_link" => "JSON_EXTRACT(payload, '$.property.has_link') IS TRUE",
-                "has_task_list" => "JSON_EXTRACT(payload, '$.property.has_task_list') IS TRUE",
-                "has_code" => "JSON_EXTRACT(payload, '$.property.has_code') IS TRUE",
-                "has_incomplete_tasks" => {
-                    "JSON_EXTRACT(payload, '$.property.has_incomplete_tasks') IS TRUE"
+                Field::HasCode => "JSON_EXTRACT(memo.payload, '$.property.has_code') IS TRUE",
+                Field::HasIncompleteTasks => {
+                    "JSON_EXTRACT(memo.payload, '$.property.has_incomplete_tasks') IS TRUE"
                 }
-                _ => ident.as_str(),
+                _ => "memo.pinned IS TRUE",
             };
-            (ident.to_string(), Vec::default())
+            (sql.to_string(), Vec::default())
         }
-        Expression::Arithmetic(expr1, op, expr2) => {
-            let (sql1, mut param1) = convert_expr_to_sql(*expr1);
-            let (sql2, mut param2) = convert_expr_to_sql(*expr2);
-            param1.append(&mut param2);
-            let op = match op {
-                ArithmeticOp::Add => " + ",
-                ArithmeticOp::Subtract => " - ",
-                ArithmeticOp::Multiply => " * ",
-                ArithmeticOp::Divide => " / ",
-                _ => return (String::default(), Vec::default()),
+        Filter::Tag(tags) => convert_tags_to_sql(tags),
+        Filter::Content(op, text) => match op {
+            TextOp::Equals => (
+                "memo.content = ?".to_string(),
+                vec![Value::from(text.to_owned())],
+            ),
+            TextOp::Contains => (
+                "memo.content LIKE ? ESCAPE '\\'".to_string(),
+                vec![Value::from(format!("%{}%", escape_like(text)))],
+            ),
+            TextOp::StartsWith => (
+                "memo.content LIKE ? ESCAPE '\\'".to_string(),
+                vec![Value::from(format!("{}%", escape_like(text)))],
+            ),
+        },
+        Filter::Matches(text) => convert_content_search_to_sql(text),
+        Filter::ContentSize(op, size) => (
+            format!("LENGTH(memo.content) {} ?", op.as_sql()),
+            vec![Value::from(*size)],
+        ),
+        Filter::TagSize(op, size) => (
+            format!(
+                "JSON_ARRAY_LENGTH(memo.payload, '$.tags') {} ?",
+                op.as_sql()
+            ),
+            vec![Value::from(*size)],
+        ),
+        Filter::Time(field, op, ts) => {
+            let column = match field {
+                Field::CreateTime => "memo.created_ts",
+                Field::DisplayTime if !display_with_updated_ts => "memo.created_ts",
+                _ => "memo.updated_ts",
             };
-            (format!("({sql1} {op} {sql2})"), param1)
+            (
+                format!("{column} {} ?", op.as_sql()),
+                vec![Value::from(*ts)],
+            )
         }
-        Expression::FunctionCall(expr, that, args) => {
-            let (sql, mut param1) = convert_expr_to_sql(*expr);
-            if "contains" == sql && args.len() == 1 {
-                if let (Some(that), Expression::Atom(Atom::String(arg))) = (that, args[0].clone()) {
-                    let (that, mut param) = convert_expr_to_sql(*that);
-                    param.append(&mut param1);
-                    param.push(Value::from(format!("%{arg}%")));
-                    (format!("{that} LIKE ?"), param)
-                } else {
-                    (String::default(), Vec::default())
-                }
-            } else {
-                let mut sql = String::new();
-                for (i, arg) in args.iter().enumerate() {
-                    let (s, mut p) = convert_expr_to_sql(arg.clone());
-                    if i == 0 {
-                        sql.push_str(&s);
-                    } else {
-                        sql.push_str(&format!(", {s}"));
-                    }
-                    param1.append(&mut p);
-                }
-                (format!("{sql}({})", sql), param1)
-            }
+        Filter::Visibility(visibilities) => {
+            let placeholder = vec!["?"; visibilities.len()].join(", ");
+            (
+                format!("memo.visibility IN ({placeholder})"),
+                visibilities
+                    .iter()
+                    .map(|v| Value::from(v.as_str_name().to_owned()))
+                    .collect(),
+            )
+        }
+        Filter::Creator(ids) => {
+            let placeholder = vec!["?"; ids.len()].join(", ");
+            (
+                format!("memo.creator_id IN ({placeholder})"),
+                ids.iter().map(|&id| Value::from(id)).collect(),
+            )
         }
-        _ => (String::default(), Vec::default()),
+        Filter::State(state) => (
+            "memo.row_status = ?".to_string(),
+            vec![Value::from(state.as_str_name().to_owned())],
+        ),
+        Filter::Uid(uid) => (
+            "memo.uid = ?".to_string(),
+            vec![Value::from(uid.to_owned())],
+        ),
     }
 }
 
 #[test]
 fn test() {
-    let (sql, params) = convert_ecl_to_sql(
+    let filter = Filter::from_cel(
         r#"!(tag in ["tag1", "tag2"]) && (content.contains('hello') || pinned) && create_time == "2006-01-02T15:04:05+07:00""#,
-    );
+    )
+    .unwrap()
+    .unwrap();
+    let (sql, params) = convert_filter_to_sql(&filter, true);
     assert_eq!(
-        r#"((NOT ((JSON_EACH.value IN (?, ?))) AND (content LIKE ? OR pinned IS TRUE)) AND (create_time = ?))"#,
+        r#"((NOT (EXISTS (SELECT 1 FROM JSON_EACH(memo.payload, '$.tags') AS tag WHERE (tag.value = ? OR tag.value LIKE ? ESCAPE '\') OR (tag.value = ? OR tag.value LIKE ? ESCAPE '\'))) AND (memo.content LIKE ? ESCAPE '\' OR memo.pinned IS TRUE)) AND memo.created_ts = ?)"#,
         sql
     );
     assert_eq!(
         vec![
             Value::from("tag1"),
+            Value::from("tag1/%"),
             Value::from("tag2"),
+            Value::from("tag2/%"),
             Value::from("%hello%"),
             Value::from(1136189045)
         ],
         params
     );
 }
+
+#[test]
+fn test_content_matches() {
+    let filter = Filter::from_cel(r#"content.matches("hello 世界 wo") && pinned"#)
+        .unwrap()
+        .unwrap();
+    assert_eq!(vec!["hello 世界 wo"], filter.content_matches());
+
+    let (sql, params) = convert_filter_to_sql(&filter, true);
+    assert_eq!(
+        r#"((memo.id IN (SELECT rowid FROM memo_fts WHERE memo_fts MATCH ?) AND memo.content LIKE ? ESCAPE '\' AND memo.content LIKE ? ESCAPE '\') AND memo.pinned IS TRUE)"#,
+        sql
+    );
+    assert_eq!(
+        vec![
+            Value::from("\"hello\""),
+            Value::from("%世界%"),
+            Value::from("%wo%")
+        ],
+        params
+    );
+}
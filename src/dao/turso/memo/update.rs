@@ -16,8 +16,7 @@ impl ToCriteria for UpdateMemo {
         } = self;
 
         let mut params = Vec::new();
-        let mut sql = Update::new().update("memo").where_and("id = ?");
-        params.push(Value::from(id));
+        let mut sql = Update::new().update("memo");
 
         if let Some(visibility) = visibility {
             sql = sql.set("visibility = ?");
@@ -44,9 +43,13 @@ impl ToCriteria for UpdateMemo {
             params.push(Value::from(if pinned { 1 } else { 0 }));
         }
 
-        if params.len() == 1 {
+        if params.is_empty() {
             (String::default(), Vec::default())
         } else {
+            // 仅允许更新属于 creator_id 的 memo，where 参数位于 set 之后
+            sql = sql.where_and("id = ?").where_and("creator_id = ?");
+            params.push(Value::from(id));
+            params.push(Value::from(creator_id));
             (sql.as_string(), params)
         }
     }
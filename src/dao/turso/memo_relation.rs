@@ -0,0 +1,120 @@
+use async_trait::async_trait;
+use libsql::{params, Value};
+
+use crate::{
+    api::v1::gen::memo_relation::Type as RelationType,
+    dao::memo_relation::{
+        ListMemoRelationError, MemoRelationRepository, SetMemoRelationError,
+        UpsertMemoRelationError,
+    },
+    model::memo::{FindMemoRelation, MemoRelation},
+};
+
+use super::Turso;
+
+#[async_trait]
+impl MemoRelationRepository for Turso {
+    async fn upsert_memo_relation(
+        &self,
+        memo_id: i32,
+        related_memo_id: i32,
+        r#type: RelationType,
+    ) -> Result<(), UpsertMemoRelationError> {
+        let sql = "insert into memo_relation (memo_id, related_memo_id, type) values (?, ?, ?) on conflict(memo_id, related_memo_id, type) do nothing";
+        self.execute(sql, params![memo_id, related_memo_id, r#type.as_str_name()])
+            .await?;
+        Ok(())
+    }
+
+    async fn set_memo_relations(
+        &self,
+        memo_id: i32,
+        r#type: RelationType,
+        related_memo_ids: Vec<i32>,
+    ) -> Result<(), SetMemoRelationError> {
+        let transaction = self.transaction().await?;
+        let mut stmt = Self::tx_prepare(
+            &transaction,
+            "delete from memo_relation where memo_id = ? and type = ?",
+        )
+        .await?;
+        Self::statement_execute(&mut stmt, params![memo_id, r#type.as_str_name()]).await?;
+
+        if !related_memo_ids.is_empty() {
+            let mut stmt = Self::tx_prepare(&transaction, "insert into memo_relation (memo_id, related_memo_id, type) values (?, ?, ?) on conflict(memo_id, related_memo_id, type) do nothing").await?;
+            for related_memo_id in related_memo_ids {
+                Self::statement_execute(
+                    &mut stmt,
+                    params![memo_id, related_memo_id, r#type.as_str_name()],
+                )
+                .await?;
+                stmt.reset();
+            }
+        }
+        Self::commit(transaction).await?;
+
+        Ok(())
+    }
+
+    async fn list_memo_relations(
+        &self,
+        FindMemoRelation {
+            memo_id,
+            related_memo_id,
+            memo_id_list,
+            r#type,
+        }: FindMemoRelation,
+    ) -> Result<Vec<MemoRelation>, ListMemoRelationError> {
+        let mut wheres = vec!["1 = 1".to_string()];
+        let mut args = Vec::new();
+
+        if let Some(memo_id) = memo_id {
+            wheres.push("memo_relation.memo_id = ?".to_string());
+            args.push(Value::from(memo_id));
+        }
+
+        if let Some(related_memo_id) = related_memo_id {
+            wheres.push("memo_relation.related_memo_id = ?".to_string());
+            args.push(Value::from(related_memo_id));
+        }
+
+        if !memo_id_list.is_empty() {
+            let placeholder = vec!["?"; memo_id_list.len()].join(", ");
+            wheres.push(format!(
+                "(memo_relation.memo_id in ({placeholder}) OR memo_relation.related_memo_id in ({placeholder}))"
+            ));
+            for _ in 0..2 {
+                args.extend(memo_id_list.iter().map(|&id| Value::from(id)));
+            }
+        }
+
+        if let Some(r#type) = r#type {
+            wheres.push("memo_relation.type = ?".to_string());
+            args.push(Value::from(r#type.as_str_name()));
+        }
+
+        let sql = format!(
+            r#"
+            select
+              memo_relation.memo_id as memo_id,
+              memo.uid as memo_uid,
+              substr(memo.content, 1, 100) as memo_snippet,
+              memo_relation.related_memo_id as related_memo_id,
+              related_memo.uid as related_memo_uid,
+              substr(related_memo.content, 1, 100) as related_memo_snippet,
+              memo_relation.type as type,
+              memo.creator_id as memo_creator_id,
+              memo.visibility as memo_visibility,
+              related_memo.creator_id as related_memo_creator_id,
+              related_memo.visibility as related_memo_visibility
+            from memo_relation
+            join memo on memo.id = memo_relation.memo_id
+            join memo as related_memo on related_memo.id = memo_relation.related_memo_id
+            where {}
+            "#,
+            wheres.join(" AND ")
+        );
+
+        Ok(self.query(&sql, args).await?)
+    }
+}
@@ -0,0 +1,36 @@
+use async_trait::async_trait;
+use libsql::Value;
+
+use crate::{
+    dao::memo_revision::{ListMemoRevisionError, MemoRevisionRepository},
+    model::memo::{FindMemoRevision, MemoRevision},
+};
+
+use super::Turso;
+
+#[async_trait]
+impl MemoRevisionRepository for Turso {
+    async fn list_memo_revisions(
+        &self,
+        FindMemoRevision { id, memo_id }: FindMemoRevision,
+    ) -> Result<Vec<MemoRevision>, ListMemoRevisionError> {
+        let mut wheres = vec!["1 = 1".to_string()];
+        let mut args = Vec::new();
+
+        if let Some(id) = id {
+            wheres.push("id = ?".to_string());
+            args.push(Value::from(id));
+        }
+
+        if let Some(memo_id) = memo_id {
+            wheres.push("memo_id = ?".to_string());
+            args.push(Value::from(memo_id));
+        }
+
+        let sql = format!(
+            "select id, memo_id, created_ts, content from memo_revision where {} order by id desc",
+            wheres.join(" AND ")
+        );
+        Ok(self.query(&sql, args).await?)
+    }
+}
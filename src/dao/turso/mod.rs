@@ -1,4 +1,9 @@
+pub mod idp;
 pub mod memo;
+pub mod memo_relation;
+pub mod memo_revision;
+pub mod quota;
+pub mod reaction;
 pub mod resource;
 pub mod session;
 pub mod user;
@@ -81,7 +86,6 @@ impl Turso {
         Ok(conn.transaction().await?)
     }
 
-    #[allow(dead_code)]
     pub async fn transaction_with_behavior(
         &self,
         tx_behavior: TransactionBehavior,
@@ -0,0 +1,38 @@
+use async_trait::async_trait;
+use libsql::params;
+
+use crate::{
+    dao::quota::{FindUserQuotaError, GetStorageUsageError, QuotaRepository, UpsertUserQuotaError},
+    model::user::{StorageUsage, UserQuota},
+};
+
+use super::Turso;
+
+#[async_trait]
+impl QuotaRepository for Turso {
+    async fn find_user_quota(&self, user_id: i32) -> Result<Option<UserQuota>, FindUserQuotaError> {
+        let sql = "select user_id, bytes_limit, files_limit from user_quota where user_id = ?";
+        let mut rs = self.query(sql, [user_id]).await?;
+        Ok(rs.pop())
+    }
+
+    async fn upsert_user_quota(
+        &self,
+        UserQuota {
+            user_id,
+            bytes_limit,
+            files_limit,
+        }: UserQuota,
+    ) -> Result<(), UpsertUserQuotaError> {
+        let sql = "insert into user_quota (user_id, bytes_limit, files_limit) values (?, ?, ?) on conflict(user_id) do update set bytes_limit = excluded.bytes_limit, files_limit = excluded.files_limit";
+        self.execute(sql, params![user_id, bytes_limit, files_limit])
+            .await?;
+        Ok(())
+    }
+
+    async fn get_storage_usage(&self, user_id: i32) -> Result<StorageUsage, GetStorageUsageError> {
+        let sql = "select coalesce(sum(size), 0) as bytes, count(*) as files from resource where creator_id = ?";
+        let mut rs: Vec<StorageUsage> = self.query(sql, [user_id]).await?;
+        Ok(rs.pop().unwrap_or_default())
+    }
+}
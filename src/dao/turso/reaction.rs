@@ -0,0 +1,70 @@
+use async_trait::async_trait;
+use libsql::{params, Value};
+
+use crate::{
+    dao::reaction::{
+        DeleteReactionError, ListReactionError, ReactionRepository, UpsertReactionError,
+    },
+    model::reaction::{FindReaction, Reaction},
+};
+
+use super::Turso;
+
+#[async_trait]
+impl ReactionRepository for Turso {
+    async fn upsert_reaction(
+        &self,
+        Reaction {
+            creator_id,
+            content_id,
+            reaction_type,
+            ..
+        }: Reaction,
+    ) -> Result<Option<Reaction>, UpsertReactionError> {
+        let sql = "insert into reaction (creator_id, content_id, reaction_type) values (?, ?, ?) on conflict(creator_id, content_id, reaction_type) do update set reaction_type = excluded.reaction_type returning id, created_ts, creator_id, content_id, reaction_type";
+        let mut rs = self
+            .query(sql, params![creator_id, content_id, reaction_type])
+            .await?;
+        Ok(rs.pop())
+    }
+
+    async fn list_reactions(
+        &self,
+        FindReaction {
+            id,
+            creator_id,
+            content_id_list,
+        }: FindReaction,
+    ) -> Result<Vec<Reaction>, ListReactionError> {
+        let mut wheres = vec!["1 = 1".to_string()];
+        let mut args = Vec::new();
+
+        if let Some(id) = id {
+            wheres.push("id = ?".to_string());
+            args.push(Value::from(id));
+        }
+
+        if let Some(creator_id) = creator_id {
+            wheres.push("creator_id = ?".to_string());
+            args.push(Value::from(creator_id));
+        }
+
+        if !content_id_list.is_empty() {
+            let placeholder = vec!["?"; content_id_list.len()].join(", ");
+            wheres.push(format!("content_id in ({placeholder})"));
+            args.extend(content_id_list.into_iter().map(Value::from));
+        }
+
+        let sql = format!(
+            "select id, created_ts, creator_id, content_id, reaction_type from reaction where {} order by id asc",
+            wheres.join(" AND ")
+        );
+        Ok(self.query(&sql, args).await?)
+    }
+
+    async fn delete_reaction(&self, id: i32, creator_id: i32) -> Result<(), DeleteReactionError> {
+        let sql = "delete from reaction where id = ? and creator_id = ?";
+        self.execute(sql, [id, creator_id]).await?;
+        Ok(())
+    }
+}
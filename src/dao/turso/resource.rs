@@ -1,16 +1,17 @@
 use async_trait::async_trait;
 use std::collections::HashMap;
 
-use libsql::Value;
+use libsql::{TransactionBehavior, Value};
 
 use crate::{
     dao::resource::{
-        CreateResourceError, DeleteResourceError, GetResourceError, ListResourceError,
-        RelateResourceError, ResourceRepository, SetResourceError,
+        CreateResourceError, DeleteResourceError, FindResourceBlobError, GetResourceError,
+        ListResourceError, RelateResourceError, ReleaseResourceBlobError, ResourceRepository,
+        SetResourceError, UpdateResourceError,
     },
     model::{
         gen::ResourceStorageType,
-        resource::{FindResource, Resource},
+        resource::{FindResource, Resource, ResourceBlob, UpdateResource},
     },
 };
 
@@ -20,92 +21,46 @@ use super::{de, Turso};
 impl ResourceRepository for Turso {
     async fn create_resource(
         &self,
-        Resource {
-            filename,
-            r#type,
-            size,
-            creator_id,
-            blob,
-            id,
-            created_ts,
-            updated_ts,
-            memo_id,
-            uid,
-            reference,
-            storage_type,
-            payload,
-        }: Resource,
+        resource: Resource,
     ) -> Result<Option<Resource>, CreateResourceError> {
-        let mut fields = vec![
-            "uid",
-            "filename",
-            "type",
-            "size",
-            "creator_id",
-            "storage_type",
-        ];
-        let mut placeholder = vec!["?", "?", "?", "?", "?", "?"];
-        let mut storage_type_str = "";
-        if storage_type != ResourceStorageType::Unspecified {
-            storage_type_str = storage_type.as_str_name();
-        }
-        let mut args = vec![
-            Value::from(uid),
-            Value::from(filename),
-            Value::from(r#type),
-            Value::from(size as u32),
-            Value::from(creator_id),
-            Value::from(storage_type_str),
-        ];
-
-        if !blob.is_empty() {
-            fields.push("blob");
-            placeholder.push("?");
-            args.push(Value::from(blob));
-        }
-
-        if !reference.is_empty() {
-            fields.push("reference");
-            placeholder.push("?");
-            args.push(Value::from(reference));
-        }
-
-        fields.push("payload");
-        placeholder.push("?");
-        args.push(payload.into());
-
-        if id > 0 {
-            fields.push("id");
-            placeholder.push("?");
-            args.push(Value::from(id));
-        }
-
-        if created_ts > 0 {
-            fields.push("created_ts");
-            placeholder.push("?");
-            args.push(Value::from(created_ts));
-        }
-
-        if updated_ts > 0 {
-            fields.push("updated_ts");
-            placeholder.push("?");
-            args.push(Value::from(updated_ts));
-        }
+        let (insert_sql, args) = insert_resource(resource);
+        let mut rs = self.query(&insert_sql, args).await?;
+        Ok(rs.pop())
+    }
 
-        if let Some(memo_id) = memo_id {
-            fields.push("memo_id");
-            placeholder.push("?");
-            args.push(Value::from(memo_id));
+    async fn create_resource_with_blob(
+        &self,
+        mut resource: Resource,
+        blob: Option<ResourceBlob>,
+    ) -> Result<Option<(Resource, bool)>, CreateResourceError> {
+        // 立即获取写锁，期间内容不会被并发的删除释放
+        let transaction = self
+            .transaction_with_behavior(TransactionBehavior::Immediate)
+            .await?;
+        let mut created = false;
+        if let Some(blob) = blob {
+            let (sql, args) = insert_resource_blob(blob);
+            let mut stmt = Self::tx_prepare(&transaction, sql).await?;
+            created = Self::statement_execute(&mut stmt, args).await? > 0;
         }
 
-        let insert_sql = format!(
-            "insert into resource ({}) values ({}) returning id, memo_id, uid, filename, type, size, created_ts, reference",
-            fields.join(", "),
-            placeholder.join(", ")
-        );
-
-        let mut rs = self.query(&insert_sql, args).await?;
-        Ok(rs.pop())
+        let mut stmt = Self::tx_prepare(
+            &transaction,
+            "select hash, size, storage_type, reference, payload, ref_count from resource_blob where hash = ?",
+        )
+        .await?;
+        let rows = Self::statement_query(&mut stmt, [resource.hash.as_str()]).await?;
+        let Some(stored) = de::<ResourceBlob>(rows).await?.pop() else {
+            return Ok(None);
+        };
+        resource.link_blob(&stored);
+
+        let (insert_sql, args) = insert_resource(resource);
+        let mut stmt = Self::tx_prepare(&transaction, insert_sql).await?;
+        let rows = Self::statement_query(&mut stmt, args).await?;
+        let resource = de::<Resource>(rows).await?.pop();
+        Self::commit(transaction).await?;
+        Ok(resource.map(|r| (r, created)))
     }
 
     async fn set_resources_memo(
@@ -120,9 +75,11 @@ impl ResourceRepository for Turso {
 
         let transaction = self.transaction().await?;
         if !add_res_ids.is_empty() {
-            let mut stmt =
-                Self::tx_prepare(&transaction, "update resource set memo_id = ? where id = ?")
-                    .await?;
+            let mut stmt = Self::tx_prepare(
+                &transaction,
+                "update resource set memo_id = ?, trashed_ts = null where id = ?",
+            )
+            .await?;
             for add_id in add_res_ids {
                 Self::statement_execute(&mut stmt, [memo_id, add_id]).await?;
                 stmt.reset();
@@ -130,9 +87,10 @@ impl ResourceRepository for Turso {
         }
 
         if !del_res_ids.is_empty() {
+            // 移入回收站，由定时任务清理
             let mut stmt = Self::tx_prepare(
                 &transaction,
-                "delete from resource where memo_id = ? and id = ?",
+                "update resource set memo_id = null, trashed_ts = strftime('%s', 'now'), updated_ts = strftime('%s', 'now') where memo_id = ? and id = ?",
             )
             .await?;
             for del_id in del_res_ids {
@@ -146,7 +104,17 @@ impl ResourceRepository for Turso {
     }
 
     async fn get_resource(&self, id: i32) -> Result<Option<Resource>, GetResourceError> {
-        let sql = "select * from resource where id = ?";
+        // 去重后的内容保存在 resource_blob 中
+        let sql = r#"
+            select
+              resource.id, resource.uid, resource.creator_id, resource.created_ts,
+              resource.updated_ts, resource.filename, resource.type, resource.size,
+              resource.memo_id, resource.storage_type, resource.reference, resource.payload,
+              resource.hash, coalesce(resource_blob.blob, resource.blob, x'') as blob
+            from resource
+            left join resource_blob on resource.hash <> '' and resource_blob.hash = resource.hash
+            where resource.id = ?
+            "#;
         let mut rs = self.query(sql, [id]).await?;
         Ok(rs.pop())
     }
@@ -164,6 +132,7 @@ impl ResourceRepository for Turso {
             uid,
             storage_type,
             get_blob,
+            trashed,
         }: FindResource,
     ) -> Result<Vec<Resource>, ListResourceError> {
         let mut wheres = vec!["1 = 1"];
@@ -202,8 +171,13 @@ impl ResourceRepository for Turso {
             wheres.push("memo_id IS NOT NULL");
         }
 
-        let mut fields =
-            "id, uid, filename, reference, type, size, created_ts, memo_id".to_string();
+        if trashed {
+            wheres.push("trashed_ts IS NOT NULL");
+        } else {
+            wheres.push("trashed_ts IS NULL");
+        }
+
+        let mut fields = "id, uid, creator_id, filename, reference, type, size, created_ts, updated_ts, memo_id, storage_type, payload, hash, trashed_ts, location".to_string();
         if get_blob {
             fields = format!("{fields}, blob as content");
         }
@@ -224,12 +198,53 @@ impl ResourceRepository for Turso {
         Ok(self.query(&sql, args).await?)
     }
 
+    async fn list_orphan_resources(&self, before: i64) -> Result<Vec<Resource>, ListResourceError> {
+        let sql = "select id, uid, creator_id, filename, reference, type, size, created_ts, updated_ts, memo_id, storage_type, payload, hash, trashed_ts from resource where (memo_id is null or not exists (select 1 from memo where memo.id = resource.memo_id)) and coalesce(trashed_ts, updated_ts) < ?";
+        Ok(self.query(sql, [before]).await?)
+    }
+
     async fn delete_resource(&self, id: i32, creator_id: i32) -> Result<(), DeleteResourceError> {
         let sql = "delete from resource where id = ? and creator_id = ?";
         self.execute(sql, [id, creator_id]).await?;
         Ok(())
     }
 
+    async fn update_resource(
+        &self,
+        UpdateResource {
+            id,
+            filename,
+            memo_id,
+            trashed,
+        }: UpdateResource,
+    ) -> Result<(), UpdateResourceError> {
+        let mut sets = vec!["updated_ts = strftime('%s', 'now')"];
+        let mut args = Vec::new();
+
+        if let Some(filename) = filename {
+            sets.push("filename = ?");
+            args.push(Value::from(filename));
+        }
+
+        if let Some(memo_id) = memo_id {
+            sets.push("memo_id = ?");
+            args.push(memo_id.map(Value::from).unwrap_or(Value::Null));
+        }
+
+        if let Some(trashed) = trashed {
+            sets.push(if trashed {
+                "trashed_ts = strftime('%s', 'now')"
+            } else {
+                "trashed_ts = null"
+            });
+        }
+
+        args.push(Value::from(id));
+        let sql = format!("update resource set {} where id = ?", sets.join(", "));
+        self.execute(&sql, args).await?;
+        Ok(())
+    }
+
     async fn relate_resources(
         &self,
         memo_ids: Vec<i32>,
@@ -240,7 +255,7 @@ impl ResourceRepository for Turso {
 
         let mut rtn = HashMap::new();
         let mut stmt = self
-            .prepare("select id, uid, filename, reference, type, size, created_ts, memo_id from resource where memo_id = ?").await?;
+            .prepare("select id, uid, creator_id, filename, reference, type, size, created_ts, updated_ts, memo_id, storage_type, payload, hash, location from resource where memo_id = ?").await?;
         for memo_id in memo_ids {
             let rows = Self::statement_query(&mut stmt, [memo_id]).await?;
             let res = de(rows).await?;
@@ -250,4 +265,160 @@ impl ResourceRepository for Turso {
 
         Ok(rtn)
     }
+
+    async fn find_resource_blob(
+        &self,
+        hash: &str,
+    ) -> Result<Option<ResourceBlob>, FindResourceBlobError> {
+        let sql = "select hash, size, storage_type, reference, payload, ref_count from resource_blob where hash = ?";
+        let mut rs = self.query(sql, [hash]).await?;
+        Ok(rs.pop())
+    }
+
+    async fn release_resource_blob(
+        &self,
+        hash: &str,
+    ) -> Result<Option<ResourceBlob>, ReleaseResourceBlobError> {
+        // 以实际引用判断，不依赖触发器维护的 ref_count
+        let sql = "delete from resource_blob where hash = ?1 and not exists (select 1 from resource where hash = ?1) returning hash, size, storage_type, reference, payload, ref_count";
+        let mut rs = self.query(sql, [hash]).await?;
+        Ok(rs.pop())
+    }
+}
+
+fn insert_resource(
+    Resource {
+        filename,
+        r#type,
+        size,
+        creator_id,
+        blob,
+        id,
+        created_ts,
+        updated_ts,
+        memo_id,
+        uid,
+        reference,
+        storage_type,
+        payload,
+        hash,
+        location,
+        ..
+    }: Resource,
+) -> (String, Vec<Value>) {
+    let mut fields = vec![
+        "uid",
+        "filename",
+        "type",
+        "size",
+        "creator_id",
+        "storage_type",
+    ];
+    let mut placeholder = vec!["?", "?", "?", "?", "?", "?"];
+    let mut storage_type_str = "";
+    if storage_type != ResourceStorageType::Unspecified {
+        storage_type_str = storage_type.as_str_name();
+    }
+    let mut args = vec![
+        Value::from(uid),
+        Value::from(filename),
+        Value::from(r#type),
+        Value::from(size as u32),
+        Value::from(creator_id),
+        Value::from(storage_type_str),
+    ];
+
+    if !blob.is_empty() {
+        fields.push("blob");
+        placeholder.push("?");
+        args.push(Value::from(blob));
+    }
+
+    if !reference.is_empty() {
+        fields.push("reference");
+        placeholder.push("?");
+        args.push(Value::from(reference));
+    }
+
+    if !hash.is_empty() {
+        fields.push("hash");
+        placeholder.push("?");
+        args.push(Value::from(hash));
+    }
+
+    fields.push("payload");
+    placeholder.push("?");
+    args.push(payload.into());
+
+    if let Some(location) = location {
+        fields.push("location");
+        placeholder.push("?");
+        args.push(Value::from(
+            serde_json::to_string(&location).unwrap_or_default(),
+        ));
+    }
+
+    if id > 0 {
+        fields.push("id");
+        placeholder.push("?");
+        args.push(Value::from(id));
+    }
+
+    if created_ts > 0 {
+        fields.push("created_ts");
+        placeholder.push("?");
+        args.push(Value::from(created_ts));
+    }
+
+    if updated_ts > 0 {
+        fields.push("updated_ts");
+        placeholder.push("?");
+        args.push(Value::from(updated_ts));
+    }
+
+    if let Some(memo_id) = memo_id {
+        fields.push("memo_id");
+        placeholder.push("?");
+        args.push(Value::from(memo_id));
+    }
+
+    let insert_sql = format!(
+            "insert into resource ({}) values ({}) returning id, memo_id, uid, creator_id, filename, type, size, created_ts, updated_ts, storage_type, reference, payload, hash, location",
+            fields.join(", "),
+            placeholder.join(", ")
+        );
+    (insert_sql, args)
+}
+
+fn insert_resource_blob(
+    ResourceBlob {
+        hash,
+        size,
+        blob,
+        storage_type,
+        reference,
+        payload,
+        ..
+    }: ResourceBlob,
+) -> (&'static str, Vec<Value>) {
+    let mut storage_type_str = "";
+    if storage_type != ResourceStorageType::Unspecified {
+        storage_type_str = storage_type.as_str_name();
+    }
+    let blob = if blob.is_empty() {
+        Value::Null
+    } else {
+        Value::from(blob)
+    };
+    (
+        "insert into resource_blob (hash, size, blob, storage_type, reference, payload) values (?, ?, ?, ?, ?, ?) on conflict(hash) do nothing",
+        vec![
+            Value::from(hash),
+            Value::from(size as u32),
+            blob,
+            Value::from(storage_type_str),
+            Value::from(reference),
+            payload.into(),
+        ],
+    )
 }
@@ -1,47 +1,157 @@
 use async_trait::async_trait;
-use libsql::params;
+use libsql::{params, Value};
 
 use crate::{
     api::v1::gen::user::Role,
     dao::user::{
-        FindUserError, FindUserSettingError, GetHostUserError, PetchUserError,
-        UpsertUserSettingError, UserRepository,
+        CreateUserError, FindUserError, FindUserSettingError, GetHostUserError, ListUserError,
+        PetchUserError, UpdateUserError, UpsertUserSettingError, UserRepository,
     },
-    model::user::{User, UserSetting},
+    model::user::{CreateUser, UpdateUser, User, UserSetting},
 };
 
 use super::Turso;
 
+pub(super) const USER_FIELDS: &str = "id, created_ts, updated_ts, row_status as state, username, role, email, nickname, password_hash, avatar_url, description";
+
 #[async_trait]
 impl UserRepository for Turso {
-    async fn find_user(
-        &self,
-        name: &str,
-        password_hash: Option<&str>,
-    ) -> Result<Option<User>, FindUserError> {
-        let sql = "select id, created_ts, updated_ts, row_status, username, role, email, nickname, password_hash, avatar_url, description from user where username = ?";
-        let mut users = if let Some(password_hash) = password_hash {
-            let sql = format!("{sql} and password_hash = ?");
-            self.query(sql, [name, password_hash]).await?
-        } else {
-            self.query(sql, [name]).await?
-        };
-
+    async fn find_user(&self, name: &str) -> Result<Option<User>, FindUserError> {
+        let sql = format!("select {USER_FIELDS} from user where username = ?");
+        let mut users = self.query(sql, [name]).await?;
         Ok(users.pop())
     }
 
     async fn petch_user(&self, id: i32) -> Result<Option<User>, PetchUserError> {
-        let sql = "select id, created_ts, updated_ts, row_status, username, role, email, nickname, password_hash, avatar_url, description from user where id = ?";
+        let sql = format!("select {USER_FIELDS} from user where id = ?");
         let mut users = self.query(sql, [id]).await?;
         Ok(users.pop())
     }
 
     async fn host_user(&self) -> Result<Option<User>, GetHostUserError> {
-        let sql = "select id, created_ts, updated_ts, row_status, username, role, email, nickname, password_hash, avatar_url, description from user where role = ?";
+        let sql = format!("select {USER_FIELDS} from user where role = ?");
         let mut users = self.query(sql, [Role::Host.as_str_name()]).await?;
         Ok(users.pop())
     }
 
+    async fn list_users(&self) -> Result<Vec<User>, ListUserError> {
+        let sql = format!("select {USER_FIELDS} from user order by id");
+        Ok(self.query(sql, ()).await?)
+    }
+
+    async fn create_user(
+        &self,
+        CreateUser {
+            username,
+            role,
+            email,
+            nickname,
+            avatar_url,
+            description,
+            password_hash,
+        }: CreateUser,
+    ) -> Result<Option<User>, CreateUserError> {
+        let sql = format!("insert into user (username, role, email, nickname, avatar_url, description, password_hash) values (?, ?, ?, ?, ?, ?, ?) returning {USER_FIELDS}");
+        let mut users = self
+            .query(
+                sql,
+                params![
+                    username,
+                    role.as_str_name(),
+                    email,
+                    nickname,
+                    avatar_url,
+                    description,
+                    password_hash
+                ],
+            )
+            .await?;
+        Ok(users.pop())
+    }
+
+    async fn sign_up_user(
+        &self,
+        CreateUser {
+            username,
+            email,
+            nickname,
+            avatar_url,
+            description,
+            password_hash,
+            ..
+        }: CreateUser,
+        allow_user: bool,
+    ) -> Result<Option<User>, CreateUserError> {
+        let sql = format!(
+            r#"
+            insert into user (username, role, email, nickname, avatar_url, description, password_hash)
+            select ?1, case when exists (select 1 from user where role = ?8) then ?9 else ?8 end, ?2, ?3, ?4, ?5, ?6
+            where ?7 or not exists (select 1 from user where role = ?8)
+            returning {USER_FIELDS}
+            "#
+        );
+        let mut users = self
+            .query(
+                sql,
+                params![
+                    username,
+                    email,
+                    nickname,
+                    avatar_url,
+                    description,
+                    password_hash,
+                    allow_user,
+                    Role::Host.as_str_name(),
+                    Role::User.as_str_name()
+                ],
+            )
+            .await?;
+        Ok(users.pop())
+    }
+
+    async fn update_user(
+        &self,
+        UpdateUser {
+            id,
+            username,
+            role,
+            email,
+            nickname,
+            avatar_url,
+            description,
+            password_hash,
+            state,
+        }: UpdateUser,
+    ) -> Result<(), UpdateUserError> {
+        let mut sets = vec!["updated_ts = strftime('%s', 'now')"];
+        let mut args = Vec::new();
+
+        let fields = [
+            ("username = ?", username),
+            ("role = ?", role.map(|r| r.as_str_name().to_owned())),
+            ("email = ?", email),
+            ("nickname = ?", nickname),
+            ("avatar_url = ?", avatar_url),
+            ("description = ?", description),
+            ("password_hash = ?", password_hash),
+            ("row_status = ?", state.map(|s| s.as_str_name().to_owned())),
+        ];
+        for (set, value) in fields {
+            if let Some(value) = value {
+                sets.push(set);
+                args.push(Value::from(value));
+            }
+        }
+        if args.is_empty() {
+            return Ok(());
+        }
+
+        args.push(Value::from(id));
+        let sql = format!("update user set {} where id = ?", sets.join(", "));
+        self.execute(sql, args).await?;
+        Ok(())
+    }
+
     async fn find_user_setting(
         &self,
         user_id: i32,
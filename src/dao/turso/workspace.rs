@@ -1,15 +1,20 @@
 use async_trait::async_trait;
+use libsql::params;
 use tracing::error;
 
-use crate::dao::workspace::{FindWorkspaceSettingError, WorkspaceRepository};
+use crate::dao::workspace::{
+    FindWorkspaceSettingError, UpsertWorkspaceSettingError, WorkspaceRepository,
+};
 use crate::model::gen::{
     workspace_setting::Value as WorkspaceSettingValue, WorkspaceGeneralSetting,
     WorkspaceMemoRelatedSetting, WorkspaceSettingKey, WorkspaceStorageSetting,
 };
-use crate::model::system::SystemSetting;
+use crate::model::system::{StoragePrivacySetting, SystemSetting};
 
 use super::Turso;
 
+const STORAGE_PRIVACY: &str = "STORAGE_PRIVACY";
+
 #[async_trait]
 impl WorkspaceRepository for Turso {
     async fn find_workspace_setting(
@@ -43,4 +48,31 @@ impl WorkspaceRepository for Turso {
         });
         Ok(value)
     }
+
+    async fn find_storage_privacy_setting(
+        &self,
+    ) -> Result<StoragePrivacySetting, FindWorkspaceSettingError> {
+        let sql = "select value from system_setting where name = ?";
+        let mut settings: Vec<SystemSetting> = self.query(sql, [STORAGE_PRIVACY]).await?;
+
+        let setting = settings
+            .pop()
+            .and_then(|s| {
+                serde_json::from_str(&s.value)
+                    .inspect_err(|e| error!("{e}"))
+                    .ok()
+            })
+            .unwrap_or_default();
+        Ok(setting)
+    }
+
+    async fn upsert_storage_privacy_setting(
+        &self,
+        setting: &StoragePrivacySetting,
+    ) -> Result<(), UpsertWorkspaceSettingError> {
+        let sql = "insert into system_setting (name, value) values (?, ?) on conflict(name) do update set value = excluded.value";
+        let value = serde_json::to_string(setting).map_err(anyhow::Error::from)?;
+        self.execute(sql, params![STORAGE_PRIVACY, value]).await?;
+        Ok(())
+    }
 }
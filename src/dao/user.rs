@@ -1,17 +1,23 @@
 use async_trait::async_trait;
 use snafu::Snafu;
 
-use crate::model::user::{User, UserSetting};
+use crate::model::user::{CreateUser, UpdateUser, User, UserSetting};
 
 #[async_trait]
 pub trait UserRepository: Clone + Send + Sync + 'static {
-    async fn find_user(
-        &self,
-        name: &str,
-        password_hash: Option<&str>,
-    ) -> Result<Option<User>, FindUserError>;
+    async fn find_user(&self, name: &str) -> Result<Option<User>, FindUserError>;
     async fn petch_user(&self, id: i32) -> Result<Option<User>, PetchUserError>;
     async fn host_user(&self) -> Result<Option<User>, GetHostUserError>;
+    async fn list_users(&self) -> Result<Vec<User>, ListUserError>;
+    async fn create_user(&self, create: CreateUser) -> Result<Option<User>, CreateUserError>;
+    /// 尚无 HOST 时以 HOST 创建，否则仅在 allow_user 时以 USER 创建；
+    /// 角色在同一条插入语句中判定，create.role 被忽略
+    async fn sign_up_user(
+        &self,
+        create: CreateUser,
+        allow_user: bool,
+    ) -> Result<Option<User>, CreateUserError>;
+    async fn update_user(&self, update: UpdateUser) -> Result<(), UpdateUserError>;
     async fn find_user_setting(
         &self,
         user_id: i32,
@@ -40,6 +46,24 @@ pub struct GetHostUserError {
     source: anyhow::Error,
 }
 
+#[derive(Debug, Snafu)]
+#[snafu(context(false), display("Failed to list user: {source}"))]
+pub struct ListUserError {
+    source: anyhow::Error,
+}
+
+#[derive(Debug, Snafu)]
+#[snafu(context(false), display("Failed to create user: {source}"))]
+pub struct CreateUserError {
+    source: anyhow::Error,
+}
+
+#[derive(Debug, Snafu)]
+#[snafu(context(false), display("Failed to update user: {source}"))]
+pub struct UpdateUserError {
+    source: anyhow::Error,
+}
+
 #[derive(Debug, Snafu)]
 #[snafu(context(false), display("Failed to find user setting: {source}"))]
 pub struct FindUserSettingError {
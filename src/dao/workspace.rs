@@ -2,6 +2,7 @@ use async_trait::async_trait;
 use snafu::Snafu;
 
 use crate::model::gen::{workspace_setting::Value as WorkspaceSettingValue, WorkspaceSettingKey};
+use crate::model::system::StoragePrivacySetting;
 
 #[async_trait]
 pub trait WorkspaceRepository: Clone + Send + Sync + 'static {
@@ -9,6 +10,13 @@ pub trait WorkspaceRepository: Clone + Send + Sync + 'static {
         &self,
         key: WorkspaceSettingKey,
     ) -> Result<Option<WorkspaceSettingValue>, FindWorkspaceSettingError>;
+    async fn find_storage_privacy_setting(
+        &self,
+    ) -> Result<StoragePrivacySetting, FindWorkspaceSettingError>;
+    async fn upsert_storage_privacy_setting(
+        &self,
+        setting: &StoragePrivacySetting,
+    ) -> Result<(), UpsertWorkspaceSettingError>;
 }
 
 #[derive(Debug, Snafu)]
@@ -16,3 +24,12 @@ pub trait WorkspaceRepository: Clone + Send + Sync + 'static {
 pub struct FindWorkspaceSettingError {
     source: anyhow::Error,
 }
+
+#[derive(Debug, Snafu)]
+#[snafu(
+    context(false),
+    display("Failed to upsert workspace setting: {source}")
+)]
+pub struct UpsertWorkspaceSettingError {
+    source: anyhow::Error,
+}
@@ -1,3 +1,4 @@
+mod memo;
 mod resource;
 
 use std::net::SocketAddr;
@@ -37,7 +38,7 @@ use tower_http::{
     services::{ServeDir, ServeFile},
     trace::TraceLayer,
 };
-use tracing::error;
+use tracing::{error, info};
 
 use crate::{
     ctrl::auth::AuthLayer,
@@ -46,7 +47,7 @@ use crate::{
         idp::IDPService,
         inbox::InboxService,
         memo::MemoService,
-        resource::ResourceService,
+        resource::{cache::ResourceCache, ResourceService},
         webhook::WebhookService,
         workspace::{WorkspaceService, WorkspaceSettingService},
     },
@@ -61,7 +62,10 @@ type GrpcService = tower_http::trace::Trace<
     GrpcWebService<
         CookieManager<
             SessionManager<
-                AuthManager<crate::ctrl::auth::AuthService<Routes>, Backend<RepoService>>,
+                AuthManager<
+                    crate::ctrl::auth::AuthService<Routes, RepoService>,
+                    Backend<RepoService>,
+                >,
                 SessionStore,
             >,
         >,
@@ -71,8 +75,9 @@ type GrpcService = tower_http::trace::Trace<
 
 #[derive(Debug, Clone)]
 /// The global application state shared between all request handlers.
-struct AppState<RS: ResourceService> {
+struct AppState<RS: ResourceService, MS: MemoService> {
     res_service: Arc<RS>,
+    memo_service: Arc<MS>,
 }
 
 pub struct GrpcRestService {
@@ -81,20 +86,48 @@ pub struct GrpcRestService {
 }
 
 impl GrpcRestService {
-    pub fn new(repo: Repo) -> Self {
+    pub fn new(repo: Repo, cache: ResourceCache, gc_grace: Duration) -> Self {
         let session_store = SessionStore::new(repo.clone());
         let session_layer = SessionManagerLayer::new(session_store)
             .with_secure(false)
             .with_expiry(Expiry::OnInactivity(Duration::days(30)));
 
-        let svc = Arc::new(RepoService::new(repo));
+        let svc = Arc::new(
+            RepoService::new(repo)
+                .with_cache(cache)
+                .with_gc_grace(gc_grace),
+        );
+        let sweeper = svc.clone();
+        tokio::spawn(async move {
+            if let Err(e) = sweeper.sweep_resource_cache().await {
+                error!("{e}");
+            }
+        });
+        let collector = svc.clone();
+        tokio::spawn(async move {
+            // 孤立资源回收的间隔
+            const GC_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60 * 60);
+            let mut interval = tokio::time::interval(GC_INTERVAL);
+            loop {
+                interval.tick().await;
+                match collector.collect_orphan_resources(false).await {
+                    Ok(report) if report.resources > 0 => info!(
+                        "Collected {} orphan resources, {} bytes",
+                        report.resources, report.bytes
+                    ),
+                    Ok(_) => (),
+                    Err(e) => error!("{e}"),
+                }
+            }
+        });
         let backend = Backend::new(svc.clone());
         let auth_manager_layer = AuthManagerLayerBuilder::new(backend, session_layer).build();
 
         let index_file = ServeFile::new("web/dist/index.html").precompressed_br();
         let axum_router = Router::new()
             .merge(resource::router())
-            .layer(auth_manager_layer.clone())
+            .merge(memo::router())
+            .layer(AuthLayer::optional(auth_manager_layer.clone(), svc.clone()))
             .route_service("/home", index_file.clone())
             .route_service("/auth", index_file.clone())
             .route_service("/explore", index_file.clone())
@@ -109,28 +142,38 @@ impl GrpcRestService {
 
         let public_path = vec![
             "/memos.api.v1.AuthService/SignIn".to_string(),
+            "/memos.api.v1.AuthService/SignUp".to_string(),
+            "/memos.api.v1.AuthService/SignInWithSSO".to_string(),
             "/memos.api.v1.AuthService/GetAuthStatus".to_string(),
             "/memos.api.v1.MemoService/ListMemos".to_string(),
             "/memos.api.v1.MemoService/ListMemoRelations".to_string(),
+            "/memos.api.v1.MemoService/ListMemoComments".to_string(),
+            "/memos.api.v1.MemoService/ListMemoReactions".to_string(),
             "/memos.api.v1.MemoService/ListMemoResources".to_string(),
             "/memos.api.v1.WorkspaceSettingService/GetWorkspaceSetting".to_string(),
             "/memos.api.v1.WorkspaceService/GetWorkspaceProfile".to_string(),
             "/memos.api.v1.UserService/ListAllUserStats".to_string(),
+            "/memos.api.v1.IdentityProviderService/ListIdentityProviders".to_string(),
+            "/memos.api.v1.IdentityProviderService/GetIdentityProvider".to_string(),
         ];
 
+        let auth_svc = svc.clone();
         let user = svc.clone().user_server();
+        let auth = svc.clone().auth_server();
+        let idp = svc.clone().idp_server();
         let memo = svc.clone().memo_server();
         let resource = svc.clone().resource_server();
         let setting = svc.clone().workspace_setting_server();
         let workspace = svc.clone().workspace_server();
-        let state = AppState { res_service: svc };
+        let state = AppState {
+            res_service: svc.clone(),
+            memo_service: svc,
+        };
 
         let empty_svc = Arc::new(EmptyService);
-        let idp = empty_svc.clone().idp_server();
         let inbox = empty_svc.clone().inbox_server();
         let webhook = empty_svc.clone().webhook_server();
-        let markdown = empty_svc.clone().markdown_server();
-        let auth = empty_svc.auth_server();
+        let markdown = empty_svc.markdown_server();
 
         let axum_router = axum_router.with_state(state);
 
@@ -138,7 +181,7 @@ impl GrpcRestService {
             .accept_http1(true)
             .layer(TraceLayer::new_for_http())
             .layer(GrpcWebLayer::new())
-            .layer(AuthLayer::new(auth_manager_layer, public_path))
+            .layer(AuthLayer::new(auth_manager_layer, auth_svc, public_path))
             .add_service(user)
             .add_service(auth)
             .add_service(markdown)
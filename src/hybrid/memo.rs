@@ -0,0 +1,106 @@
+use axum::{
+    extract::{Path, Query, State},
+    response::{IntoResponse, Response, Result},
+    routing::{get, post},
+    Json, Router,
+};
+use hyper::StatusCode;
+use serde::Deserialize;
+
+use crate::{
+    ctrl::{error_response, CurrentUser},
+    model::memo::MemoRevision,
+    svc::{memo::MemoService, resource::ResourceService},
+    util::diff::DiffLine,
+};
+
+use super::AppState;
+
+pub fn router<RS: ResourceService, MS: MemoService>() -> Router<AppState<RS, MS>> {
+    Router::new()
+        .route("/api/v1/memos/{id}/revisions", get(list_memo_revisions))
+        .route(
+            "/api/v1/memos/{id}/revisions/diff",
+            get(diff_memo_revisions),
+        )
+        .route(
+            "/api/v1/memos/{id}/revisions/{revision_id}",
+            get(get_memo_revision),
+        )
+        .route(
+            "/api/v1/memos/{id}/revisions/{revision_id}/restore",
+            post(restore_memo_revision),
+        )
+}
+
+#[derive(Debug, Deserialize)]
+struct DiffQry {
+    from: i32,
+    /// 为空时与当前内容对比
+    to: Option<i32>,
+}
+
+/// GET /api/v1/memos/{id}/revisions
+async fn list_memo_revisions<RS: ResourceService, MS: MemoService>(
+    State(state): State<AppState<RS, MS>>,
+    CurrentUser(user): CurrentUser,
+    Path(id): Path<i32>,
+) -> Result<Json<Vec<MemoRevision>>> {
+    let revisions = state
+        .memo_service
+        .list_memo_revisions(user.as_ref(), id)
+        .await?;
+    Ok(Json(revisions))
+}
+
+/// GET /api/v1/memos/{id}/revisions/{revision_id}
+async fn get_memo_revision<RS: ResourceService, MS: MemoService>(
+    State(state): State<AppState<RS, MS>>,
+    CurrentUser(user): CurrentUser,
+    Path((id, revision_id)): Path<(i32, i32)>,
+) -> Result<Json<MemoRevision>> {
+    let revision = state
+        .memo_service
+        .get_memo_revision(user.as_ref(), id, revision_id)
+        .await?;
+    Ok(Json(revision))
+}
+
+/// GET /api/v1/memos/{id}/revisions/diff?from={revision_id}&to={revision_id}
+async fn diff_memo_revisions<RS: ResourceService, MS: MemoService>(
+    State(state): State<AppState<RS, MS>>,
+    CurrentUser(user): CurrentUser,
+    Path(id): Path<i32>,
+    Query(DiffQry { from, to }): Query<DiffQry>,
+) -> Result<Json<Vec<DiffLine>>> {
+    let diff = state
+        .memo_service
+        .diff_memo_revisions(user.as_ref(), id, from, to)
+        .await?;
+    Ok(Json(diff))
+}
+
+/// POST /api/v1/memos/{id}/revisions/{revision_id}/restore
+async fn restore_memo_revision<RS: ResourceService, MS: MemoService>(
+    State(state): State<AppState<RS, MS>>,
+    CurrentUser(user): CurrentUser,
+    Path((id, revision_id)): Path<(i32, i32)>,
+) -> Result<StatusCode> {
+    state
+        .memo_service
+        .restore_memo_revision(user.as_ref(), id, revision_id)
+        .await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+impl IntoResponse for crate::svc::memo::Error {
+    fn into_response(self) -> Response {
+        let status_code = match self {
+            crate::svc::memo::Error::MemoNotFound
+            | crate::svc::memo::Error::MemoRevisionNotFound => StatusCode::NOT_FOUND,
+            crate::svc::memo::Error::Permission { .. } => StatusCode::FORBIDDEN,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        error_response(status_code, self)
+    }
+}
@@ -1,71 +1,461 @@
 use axum::{
     body::Body,
-    extract::{Path, Query, State},
+    extract::{DefaultBodyLimit, Multipart, Path, Query, State},
     response::{IntoResponse, Response, Result},
-    routing::get,
-    Router,
+    routing::{get, post, put},
+    Json, Router,
 };
-use hyper::{header::CONTENT_TYPE, StatusCode};
-use tracing::error;
+use futures::TryStreamExt;
+use hyper::{
+    header::{
+        HeaderValue, ACCEPT, ACCEPT_RANGES, CACHE_CONTROL, CONTENT_DISPOSITION, CONTENT_LENGTH,
+        CONTENT_RANGE, CONTENT_TYPE, ETAG, IF_NONE_MATCH, IF_RANGE, RANGE, VARY,
+    },
+    HeaderMap, StatusCode,
+};
+use serde::{Deserialize, Serialize};
+use std::io::SeekFrom;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+use tokio_util::io::ReaderStream;
 
-use crate::{model::resource::ResourceQry, svc::resource::ResourceService};
+use crate::{
+    api::prefix::FormatName,
+    ctrl::{error_response, CurrentUser},
+    model::{
+        resource::{Resource as ResourceModel, ResourceGcReport, ResourceQry},
+        system::StoragePrivacySetting,
+        user::{UserQuota, UserStorage},
+    },
+    svc::{
+        memo::MemoService,
+        permission::{self, Action},
+        resource::{
+            thumbnail::{self, Thumbnail},
+            ResourceService,
+        },
+    },
+};
 
 use super::AppState;
 
-pub fn router<RS: ResourceService>() -> Router<AppState<RS>> {
-    Router::new().route("/file/resources/{id}/{filename}", get(stream_resource))
+pub fn router<RS: ResourceService, MS: MemoService>() -> Router<AppState<RS, MS>> {
+    Router::new()
+        .route("/file/resources/{id}/{filename}", get(stream_resource))
+        // 上传大小由 get_upload_size_limit 在接收过程中限制
+        .route(
+            "/api/v1/resources:upload",
+            post(upload_resource).layer(DefaultBodyLimit::disable()),
+        )
+        .route("/api/v1/resources/trash", get(list_trashed_resources))
+        .route("/api/v1/resources/{id}/restore", post(restore_resource))
+        .route("/api/v1/resources/gc", post(collect_orphan_resources))
+        .route("/api/v1/users/{id}/storage", get(get_user_storage))
+        .route("/api/v1/users/{id}/quota", put(set_user_quota))
+        .route(
+            "/api/v1/workspace/storage/privacy",
+            get(get_storage_privacy).put(set_storage_privacy),
+        )
+}
+
+/// POST /api/v1/resources:upload，multipart 的 file 字段为文件内容
+async fn upload_resource<RS: ResourceService, MS: MemoService>(
+    State(state): State<AppState<RS, MS>>,
+    CurrentUser(user): CurrentUser,
+    mut multipart: Multipart,
+) -> Result<Json<UploadedResource>> {
+    let user = user.ok_or(StatusCode::UNAUTHORIZED)?;
+    while let Some(field) = multipart.next_field().await? {
+        if field.name() != Some("file") {
+            continue;
+        }
+        let filename = field.file_name().unwrap_or_default().to_owned();
+        let content_type = field.content_type().map(str::to_owned);
+        let stream = field.map_err(std::io::Error::other);
+        let res = state
+            .res_service
+            .create_resource_stream(&user, filename, content_type, Box::pin(stream))
+            .await?;
+        return Ok(Json(res.into()));
+    }
+    Err((StatusCode::BAD_REQUEST, "missing file field").into())
 }
 
-/// /file/resources/{id}/{filename}
-async fn stream_resource<RS: ResourceService>(
-    State(state): State<AppState<RS>>,
-    Path((id, filename)): Path<(i32, String)>,
-    Query(ResourceQry { thumbnail }): Query<ResourceQry>,
-) -> Result<Resource> {
-    let res = state.res_service.get_resource_by_id(id).await?;
-    let r#type = res.r#type.clone();
-    let thumbnail = Some("1".to_owned()) == thumbnail && res.r#type.starts_with("image");
+/// GET /api/v1/resources/trash，当前用户回收站中的资源
+async fn list_trashed_resources<RS: ResourceService, MS: MemoService>(
+    State(state): State<AppState<RS, MS>>,
+    CurrentUser(user): CurrentUser,
+) -> Result<Json<Vec<UploadedResource>>> {
+    let user = user.ok_or(StatusCode::UNAUTHORIZED)?;
+    let resources = state.res_service.list_trashed_resources(&user).await?;
+    Ok(Json(resources.into_iter().map(Into::into).collect()))
+}
+
+/// POST /api/v1/resources/{id}/restore
+async fn restore_resource<RS: ResourceService, MS: MemoService>(
+    State(state): State<AppState<RS, MS>>,
+    CurrentUser(user): CurrentUser,
+    Path(id): Path<i32>,
+) -> Result<Json<UploadedResource>> {
+    let user = user.ok_or(StatusCode::UNAUTHORIZED)?;
+    let res = state.res_service.restore_resource(&user, id).await?;
+    Ok(Json(res.into()))
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GcQry {
+    #[serde(default)]
+    dry_run: bool,
+}
+
+/// POST /api/v1/resources/gc?dryRun=true，仅管理员可用
+async fn collect_orphan_resources<RS: ResourceService, MS: MemoService>(
+    State(state): State<AppState<RS, MS>>,
+    CurrentUser(user): CurrentUser,
+    Query(GcQry { dry_run }): Query<GcQry>,
+) -> Result<Json<ResourceGcReport>> {
+    let user = user.ok_or(StatusCode::UNAUTHORIZED)?;
+    if !permission::is_superuser(&user) {
+        return Err(StatusCode::FORBIDDEN.into());
+    }
+    let report = state.res_service.collect_orphan_resources(dry_run).await?;
+    Ok(Json(report))
+}
+
+/// GET /api/v1/users/{id}/storage，本人或管理员可查看
+async fn get_user_storage<RS: ResourceService, MS: MemoService>(
+    State(state): State<AppState<RS, MS>>,
+    CurrentUser(user): CurrentUser,
+    Path(id): Path<i32>,
+) -> Result<Json<UserStorage>> {
+    let user = user.ok_or(StatusCode::UNAUTHORIZED)?;
+    let storage = state.res_service.get_user_storage(&user, id).await?;
+    Ok(Json(storage))
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct QuotaBody {
+    #[serde(default)]
+    bytes_limit: i64,
+    #[serde(default)]
+    files_limit: i32,
+}
+
+/// PUT /api/v1/users/{id}/quota，仅管理员可用，0 表示不限制
+async fn set_user_quota<RS: ResourceService, MS: MemoService>(
+    State(state): State<AppState<RS, MS>>,
+    CurrentUser(user): CurrentUser,
+    Path(id): Path<i32>,
+    Json(body): Json<QuotaBody>,
+) -> Result<Json<UserStorage>> {
+    let user = user.ok_or(StatusCode::UNAUTHORIZED)?;
+    let quota = UserQuota {
+        user_id: id,
+        bytes_limit: body.bytes_limit,
+        files_limit: body.files_limit,
+    };
+    let storage = state.res_service.set_user_quota(&user, quota).await?;
+    Ok(Json(storage))
+}
+
+/// GET /api/v1/workspace/storage/privacy，仅管理员可用
+async fn get_storage_privacy<RS: ResourceService, MS: MemoService>(
+    State(state): State<AppState<RS, MS>>,
+    CurrentUser(user): CurrentUser,
+) -> Result<Json<StoragePrivacySetting>> {
+    let user = user.ok_or(StatusCode::UNAUTHORIZED)?;
+    let setting = state.res_service.get_storage_privacy(&user).await?;
+    Ok(Json(setting))
+}
+
+/// PUT /api/v1/workspace/storage/privacy，仅管理员可用
+async fn set_storage_privacy<RS: ResourceService, MS: MemoService>(
+    State(state): State<AppState<RS, MS>>,
+    CurrentUser(user): CurrentUser,
+    Json(setting): Json<StoragePrivacySetting>,
+) -> Result<Json<StoragePrivacySetting>> {
+    let user = user.ok_or(StatusCode::UNAUTHORIZED)?;
+    let setting = state
+        .res_service
+        .set_storage_privacy(&user, setting)
+        .await?;
+    Ok(Json(setting))
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct UploadedResource {
+    name: String,
+    uid: String,
+    filename: String,
+    r#type: String,
+    size: usize,
+    create_time: i64,
+}
+
+impl From<ResourceModel> for UploadedResource {
+    fn from(value: ResourceModel) -> Self {
+        Self {
+            name: value.get_name(),
+            uid: value.uid,
+            filename: value.filename,
+            r#type: value.r#type,
+            size: value.size,
+            create_time: value.created_ts,
+        }
+    }
+}
 
-    let stream = state
+/// /file/resources/{id}/{filename}?thumbnail=1&size=256
+async fn stream_resource<RS: ResourceService, MS: MemoService>(
+    State(state): State<AppState<RS, MS>>,
+    CurrentUser(user): CurrentUser,
+    Path((id, _filename)): Path<(i32, String)>,
+    Query(ResourceQry { thumbnail, size }): Query<ResourceQry>,
+    headers: HeaderMap,
+) -> Result<Resource> {
+    let res = state
         .res_service
-        .get_resource_stream(id, res.filename, thumbnail)
+        .get_resource_with_permission(user.as_ref(), id, Action::Read)
         .await?;
+    let thumbnail =
+        (Some("1".to_owned()) == thumbnail && thumbnail::supported(&res.r#type)).then(|| {
+            let accept = headers.get(ACCEPT).and_then(|v| v.to_str().ok());
+            Thumbnail::new(size, accept.is_some_and(accepts_webp), &res.r#type)
+        });
+    // 缩略图与原图内容不同，ETag 也需区分尺寸与格式
+    let etag = match thumbnail {
+        Some(t) => format!(
+            r#""{}-{}""#,
+            res.uid,
+            t.cache_key(&res.updated_ts.to_string())
+        ),
+        None => format!(r#""{}-{}""#, res.uid, res.updated_ts),
+    };
+    let mut resource = Resource {
+        status: StatusCode::OK,
+        filename: res.filename.clone(),
+        r#type: thumbnail.map_or(res.r#type.clone(), |t| t.mime().to_owned()),
+        etag,
+        vary_accept: thumbnail.is_some(),
+        length: 0,
+        content_range: None,
+        body: Body::empty(),
+    };
+
+    let if_none_match = headers.get(IF_NONE_MATCH).and_then(|v| v.to_str().ok());
+    if if_none_match.is_some_and(|v| etag_matches(v, &resource.etag)) {
+        resource.status = StatusCode::NOT_MODIFIED;
+        return Ok(resource);
+    }
+
+    let mut file = state.res_service.get_resource_file(&res, thumbnail).await?;
+    let len = file.metadata().await.map_err(internal_error)?.len();
+    resource.length = len;
 
-    let body = Body::from_stream(stream);
-    Ok(Resource {
-        _filename: filename,
-        r#type,
-        body,
+    // If-Range 不匹配时返回完整内容
+    let if_range = headers.get(IF_RANGE).and_then(|v| v.to_str().ok());
+    let range = headers
+        .get(RANGE)
+        .and_then(|v| v.to_str().ok())
+        .filter(|_| if_range.is_none_or(|v| v == resource.etag))
+        .and_then(|v| parse_range(v, len));
+    match range {
+        Some(Ok((start, end))) => {
+            file.seek(SeekFrom::Start(start))
+                .await
+                .map_err(internal_error)?;
+            resource.status = StatusCode::PARTIAL_CONTENT;
+            resource.length = end - start + 1;
+            resource.content_range = Some(format!("bytes {start}-{end}/{len}"));
+            resource.body = Body::from_stream(ReaderStream::new(file.take(resource.length)));
+        }
+        Some(Err(())) => {
+            resource.status = StatusCode::RANGE_NOT_SATISFIABLE;
+            resource.length = 0;
+            resource.content_range = Some(format!("bytes */{len}"));
+        }
+        None => resource.body = Body::from_stream(ReaderStream::new(file)),
+    }
+    Ok(resource)
+}
+
+/// 仅支持单段范围，多段或无法解析时返回 None 以响应完整内容
+fn parse_range(header: &str, len: u64) -> Option<Result<(u64, u64), ()>> {
+    let spec = header.trim().strip_prefix("bytes=")?;
+    if spec.contains(',') {
+        return None;
+    }
+    let (start, end) = spec.trim().split_once('-')?;
+    let range = match (start.trim(), end.trim()) {
+        ("", "") => return None,
+        // 最后 n 个字节
+        ("", suffix) => {
+            let suffix: u64 = suffix.parse().ok()?;
+            if suffix == 0 || len == 0 {
+                return Some(Err(()));
+            }
+            (len.saturating_sub(suffix), len - 1)
+        }
+        (start, end) => {
+            let start: u64 = start.parse().ok()?;
+            let end = if end.is_empty() {
+                len.saturating_sub(1)
+            } else {
+                let end: u64 = end.parse().ok()?;
+                if end < start {
+                    return None;
+                }
+                end.min(len.saturating_sub(1))
+            };
+            if start >= len {
+                return Some(Err(()));
+            }
+            (start, end)
+        }
+    };
+    Some(Ok(range))
+}
+
+/// Accept 中显式列出 image/webp 且 q 不为 0
+fn accepts_webp(accept: &str) -> bool {
+    accept.split(',').any(|range| {
+        let mut params = range.split(';');
+        let media = params.next().unwrap_or_default().trim();
+        let rejected = params.any(|p| {
+            p.trim()
+                .strip_prefix("q=")
+                .and_then(|q| q.trim().parse::<f32>().ok())
+                .is_some_and(|q| q == 0.0)
+        });
+        media.eq_ignore_ascii_case("image/webp") && !rejected
     })
 }
 
+fn etag_matches(if_none_match: &str, etag: &str) -> bool {
+    if_none_match
+        .split(',')
+        .map(|t| t.trim().trim_start_matches("W/"))
+        .any(|t| t == "*" || t == etag)
+}
+
+/// inline 展示，非 ASCII 文件名按 RFC 5987 编码
+fn content_disposition(filename: &str) -> String {
+    let fallback: String = filename
+        .chars()
+        .map(|c| {
+            if (c.is_ascii_graphic() && c != '"' && c != '\\') || c == ' ' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let encoded: String = filename
+        .bytes()
+        .map(|b| {
+            if b.is_ascii_alphanumeric() || b"!#$&+-.^_`|~".contains(&b) {
+                (b as char).to_string()
+            } else {
+                format!("%{b:02X}")
+            }
+        })
+        .collect();
+    format!(r#"inline; filename="{fallback}"; filename*=UTF-8''{encoded}"#)
+}
+
+fn internal_error(e: std::io::Error) -> Response {
+    error_response(StatusCode::INTERNAL_SERVER_ERROR, e)
+}
+
 struct Resource {
-    pub _filename: String,
+    pub status: StatusCode,
+    pub filename: String,
     pub r#type: String,
+    pub etag: String,
+    pub vary_accept: bool,
+    pub length: u64,
+    pub content_range: Option<String>,
     pub body: Body,
 }
 
 impl IntoResponse for Resource {
     fn into_response(self) -> Response {
-        let headers = [(CONTENT_TYPE, self.r#type)];
+        let mut headers = HeaderMap::new();
+        let mut insert = |name, value: &str| {
+            if let Ok(value) = HeaderValue::from_str(value) {
+                headers.insert(name, value);
+            }
+        };
+        insert(ETAG, &self.etag);
+        // 资源可能仅对当前用户可见，不允许共享缓存
+        insert(CACHE_CONTROL, "private, max-age=3600");
+        insert(ACCEPT_RANGES, "bytes");
+        // 缩略图格式取决于 Accept
+        if self.vary_accept {
+            insert(VARY, "Accept");
+        }
+        if self.status != StatusCode::NOT_MODIFIED {
+            insert(CONTENT_TYPE, &self.r#type);
+            insert(CONTENT_DISPOSITION, &content_disposition(&self.filename));
+            insert(CONTENT_LENGTH, &self.length.to_string());
+        }
+        if let Some(content_range) = &self.content_range {
+            insert(CONTENT_RANGE, content_range);
+        }
 
-        (headers, self.body).into_response()
+        (self.status, headers, self.body).into_response()
     }
 }
 
 impl IntoResponse for crate::svc::resource::Error {
     fn into_response(self) -> Response {
-        error!("{self}");
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            [(CONTENT_TYPE, "text/json; charset=utf-8")],
-            format!(
-                r#"{{"error": "code={}, message={}", "message": "{}"}}"#,
-                StatusCode::INTERNAL_SERVER_ERROR,
-                self,
-                self
-            ),
-        )
-            .into_response()
+        let status_code = match self {
+            crate::svc::resource::Error::ResourceNotFound => StatusCode::NOT_FOUND,
+            crate::svc::resource::Error::Permission { .. } => StatusCode::FORBIDDEN,
+            crate::svc::resource::Error::FileSizeLimit { .. }
+            | crate::svc::resource::Error::QuotaExceeded => StatusCode::PAYLOAD_TOO_LARGE,
+            crate::svc::resource::Error::InvalidQuota => StatusCode::BAD_REQUEST,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        error_response(status_code, self)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{accepts_webp, content_disposition, etag_matches, parse_range};
+
+    #[test]
+    fn range() {
+        assert_eq!(parse_range("bytes=0-99", 1000), Some(Ok((0, 99))));
+        assert_eq!(parse_range("bytes=900-", 1000), Some(Ok((900, 999))));
+        assert_eq!(parse_range("bytes=-100", 1000), Some(Ok((900, 999))));
+        assert_eq!(parse_range("bytes=500-5000", 1000), Some(Ok((500, 999))));
+        assert_eq!(parse_range("bytes=1000-", 1000), Some(Err(())));
+        assert_eq!(parse_range("bytes=0-1,5-6", 1000), None);
+        assert_eq!(parse_range("items=0-1", 1000), None);
+    }
+
+    #[test]
+    fn cache_headers() {
+        assert!(etag_matches(r#""a-1", "b-2""#, r#""b-2""#));
+        assert!(etag_matches(r#"W/"b-2""#, r#""b-2""#));
+        assert!(etag_matches("*", r#""b-2""#));
+        assert!(!etag_matches(r#""a-1""#, r#""b-2""#));
+
+        assert!(accepts_webp("image/avif,image/webp,image/apng,*/*;q=0.8"));
+        assert!(!accepts_webp("image/png,image/*;q=0.8"));
+        assert!(!accepts_webp("image/webp;q=0"));
+
+        assert_eq!(
+            content_disposition("a b.png"),
+            r#"inline; filename="a b.png"; filename*=UTF-8''a%20b.png"#
+        );
+        assert_eq!(
+            content_disposition("图.png"),
+            r#"inline; filename="_.png"; filename*=UTF-8''%E5%9B%BE.png"#
+        );
     }
 }
@@ -4,8 +4,13 @@
 use hybrid::{GrpcRestService, ShuttleGrpcWeb};
 use libsql::Database;
 use shuttle_runtime::SecretStore;
+use time::Duration;
 
 use crate::dao::turso::Turso;
+use crate::svc::resource::{
+    cache::{ResourceCache, DEFAULT_RESOURCE_CACHE_MIB, DEFAULT_THUMBNAIL_CACHE_MIB},
+    DEFAULT_GC_GRACE_DAYS,
+};
 
 mod google {
     #[allow(clippy::doc_lazy_continuation)]
@@ -29,6 +34,23 @@ async fn grpc_web(
     #[shuttle_runtime::Secrets] secrets: SecretStore,
 ) -> ShuttleGrpcWeb {
     let repo = Turso::new(repo);
+    // 缓存上限，单位 MiB
+    let cache_limit = |key: &str, default: u64| {
+        secrets
+            .get(key)
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(default)
+    };
+    let cache = ResourceCache::new(
+        cache_limit("RESOURCE_CACHE_MIB", DEFAULT_RESOURCE_CACHE_MIB),
+        cache_limit("THUMBNAIL_CACHE_MIB", DEFAULT_THUMBNAIL_CACHE_MIB),
+    );
 
-    Ok(GrpcRestService::new(repo))
+    // 未关联资源与回收站资源的保留天数
+    let gc_grace = secrets
+        .get("RESOURCE_GC_GRACE_DAYS")
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_GC_GRACE_DAYS);
+
+    Ok(GrpcRestService::new(repo, cache, Duration::days(gc_grace)))
 }
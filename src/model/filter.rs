@@ -0,0 +1,518 @@
+pub mod legacy;
+
+use std::fmt::Display;
+
+use cel_parser::{parse, Atom, Expression, RelationOp, UnaryOp};
+use snafu::{ensure, Snafu};
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+use crate::api::{
+    prefix,
+    v1::gen::{State, Visibility},
+};
+
+/// 可过滤的字段
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+    Tag,
+    Content,
+    CreateTime,
+    UpdateTime,
+    DisplayTime,
+    Visibility,
+    Creator,
+    Pinned,
+    HasLink,
+    HasTaskList,
+    HasCode,
+    HasIncompleteTasks,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompareOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextOp {
+    Equals,
+    Contains,
+    StartsWith,
+}
+
+/// 过滤条件，由 filter 与旧版 old_filter 共同生成，所有字段均已校验
+#[derive(Debug, Clone, PartialEq)]
+pub enum Filter {
+    And(Box<Filter>, Box<Filter>),
+    Or(Box<Filter>, Box<Filter>),
+    Not(Box<Filter>),
+    /// 布尔字段为真
+    Flag(Field),
+    /// 命中其一，包含子标签
+    Tag(Vec<String>),
+    Content(TextOp, String),
+    /// 全文检索
+    Matches(String),
+    ContentSize(CompareOp, i64),
+    TagSize(CompareOp, i64),
+    /// unix 时间戳
+    Time(Field, CompareOp, i64),
+    Visibility(Vec<Visibility>),
+    Creator(Vec<i32>),
+    /// 仅旧版 filter 使用
+    State(State),
+    Uid(String),
+}
+
+impl Filter {
+    /// 编译 CEL 过滤表达式，空字符串返回 None
+    pub fn from_cel(filter: &str) -> Result<Option<Filter>, Error> {
+        if filter.trim().is_empty() {
+            return Ok(None);
+        }
+        let expr = parse(filter).map_err(|e| Error::Parse {
+            message: e.to_string(),
+        })?;
+        compile(&expr).map(Some)
+    }
+
+    pub fn and(self, other: Filter) -> Filter {
+        Filter::And(Box::new(self), Box::new(other))
+    }
+
+    pub fn or(self, other: Filter) -> Filter {
+        Filter::Or(Box::new(self), Box::new(other))
+    }
+
+    #[allow(clippy::should_implement_trait)]
+    pub fn not(self) -> Filter {
+        Filter::Not(Box::new(self))
+    }
+
+    /// 全文检索的关键词
+    pub fn content_matches(&self) -> Vec<&str> {
+        match self {
+            Filter::And(f1, f2) | Filter::Or(f1, f2) => {
+                let mut rtn = f1.content_matches();
+                rtn.extend(f2.content_matches());
+                rtn
+            }
+            Filter::Not(f) => f.content_matches(),
+            Filter::Matches(text) => vec![text.as_str()],
+            _ => Vec::new(),
+        }
+    }
+}
+
+impl Field {
+    pub fn from_ident(ident: &str) -> Option<Field> {
+        let field = match ident {
+            "tag" => Field::Tag,
+            "content" => Field::Content,
+            "create_time" => Field::CreateTime,
+            "update_time" => Field::UpdateTime,
+            "display_time" => Field::DisplayTime,
+            "visibility" => Field::Visibility,
+            "creator" => Field::Creator,
+            "pinned" => Field::Pinned,
+            "has_link" => Field::HasLink,
+            "has_task_list" => Field::HasTaskList,
+            "has_code" => Field::HasCode,
+            "has_incomplete_tasks" => Field::HasIncompleteTasks,
+            _ => return None,
+        };
+        Some(field)
+    }
+
+    pub fn is_bool(&self) -> bool {
+        matches!(
+            self,
+            Field::Pinned
+                | Field::HasLink
+                | Field::HasTaskList
+                | Field::HasCode
+                | Field::HasIncompleteTasks
+        )
+    }
+
+    pub fn is_time(&self) -> bool {
+        matches!(
+            self,
+            Field::CreateTime | Field::UpdateTime | Field::DisplayTime
+        )
+    }
+}
+
+impl Display for Field {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Field::Tag => "tag",
+            Field::Content => "content",
+            Field::CreateTime => "create_time",
+            Field::UpdateTime => "update_time",
+            Field::DisplayTime => "display_time",
+            Field::Visibility => "visibility",
+            Field::Creator => "creator",
+            Field::Pinned => "pinned",
+            Field::HasLink => "has_link",
+            Field::HasTaskList => "has_task_list",
+            Field::HasCode => "has_code",
+            Field::HasIncompleteTasks => "has_incomplete_tasks",
+        };
+        write!(f, "{name}")
+    }
+}
+
+impl CompareOp {
+    pub fn as_sql(&self) -> &'static str {
+        match self {
+            CompareOp::Eq => "=",
+            CompareOp::Ne => "<>",
+            CompareOp::Lt => "<",
+            CompareOp::Le => "<=",
+            CompareOp::Gt => ">",
+            CompareOp::Ge => ">=",
+        }
+    }
+
+    fn from_relation(op: &RelationOp) -> Option<CompareOp> {
+        let op = match op {
+            RelationOp::Equals => CompareOp::Eq,
+            RelationOp::NotEquals => CompareOp::Ne,
+            RelationOp::LessThan => CompareOp::Lt,
+            RelationOp::LessThanEq => CompareOp::Le,
+            RelationOp::GreaterThan => CompareOp::Gt,
+            RelationOp::GreaterThanEq => CompareOp::Ge,
+            RelationOp::In => return None,
+        };
+        Some(op)
+    }
+}
+
+fn compile(expr: &Expression) -> Result<Filter, Error> {
+    match expr {
+        Expression::And(expr1, expr2) => Ok(compile(expr1)?.and(compile(expr2)?)),
+        Expression::Or(expr1, expr2) => Ok(compile(expr1)?.or(compile(expr2)?)),
+        Expression::Unary(UnaryOp::Not, expr) => Ok(compile(expr)?.not()),
+        Expression::Ident(ident) => {
+            let field = get_field(ident)?;
+            ensure!(
+                field.is_bool(),
+                TypeMismatch {
+                    field: field.to_string(),
+                    expected: "bool"
+                }
+            );
+            Ok(Filter::Flag(field))
+        }
+        Expression::Relation(expr1, op, expr2) => compile_relation(expr1, op, expr2),
+        Expression::FunctionCall(func, Some(that), args) => {
+            let func = get_function(func)?;
+            let field = match that.as_ref() {
+                Expression::Ident(ident) => get_field(ident)?,
+                _ => return UnsupportedExpression.fail(),
+            };
+            ensure!(
+                field == Field::Content,
+                TypeMismatch {
+                    field: field.to_string(),
+                    expected: "string"
+                }
+            );
+            let [arg] = args.as_slice() else {
+                return InvalidArguments { function: func }.fail();
+            };
+            let text = get_string(field, arg)?;
+            match func {
+                "contains" => Ok(Filter::Content(TextOp::Contains, text)),
+                "startsWith" => Ok(Filter::Content(TextOp::StartsWith, text)),
+                "matches" => Ok(Filter::Matches(text)),
+                _ => TypeMismatch {
+                    field: func,
+                    expected: "bool",
+                }
+                .fail(),
+            }
+        }
+        _ => UnsupportedExpression.fail(),
+    }
+}
+
+fn compile_relation(
+    expr1: &Expression,
+    op: &RelationOp,
+    expr2: &Expression,
+) -> Result<Filter, Error> {
+    let field = match expr1 {
+        Expression::Ident(ident) => get_field(ident)?,
+        Expression::FunctionCall(func, that, args) => {
+            // size(content) 或 content.size()
+            let func = get_function(func)?;
+            ensure!(
+                func == "size",
+                TypeMismatch {
+                    field: func,
+                    expected: "int"
+                }
+            );
+            let field = match (that.as_deref(), args.as_slice()) {
+                (Some(Expression::Ident(ident)), []) | (None, [Expression::Ident(ident)]) => {
+                    get_field(ident)?
+                }
+                _ => return InvalidArguments { function: func }.fail(),
+            };
+            let op = get_compare_op(field, op)?;
+            let size = get_int(field, expr2)?;
+            return match field {
+                Field::Content => Ok(Filter::ContentSize(op, size)),
+                Field::Tag => Ok(Filter::TagSize(op, size)),
+                _ => TypeMismatch {
+                    field: field.to_string(),
+                    expected: "string or list",
+                }
+                .fail(),
+            };
+        }
+        _ => return UnsupportedExpression.fail(),
+    };
+
+    // in 只用于可枚举的字段
+    if matches!(op, RelationOp::In) {
+        let Expression::List(list) = expr2 else {
+            return TypeMismatch {
+                field: field.to_string(),
+                expected: "list",
+            }
+            .fail();
+        };
+        let values = list
+            .iter()
+            .map(|expr| get_string(field, expr))
+            .collect::<Result<Vec<_>, _>>()?;
+        return match field {
+            Field::Tag => Ok(Filter::Tag(values)),
+            Field::Visibility => Ok(Filter::Visibility(get_visibilities(field, values)?)),
+            Field::Creator => Ok(Filter::Creator(get_creator_ids(field, values)?)),
+            _ => UnsupportedOperator {
+                field: field.to_string(),
+                op: "in",
+            }
+            .fail(),
+        };
+    }
+
+    let op = get_compare_op(field, op)?;
+    if field.is_time() {
+        return Ok(Filter::Time(field, op, get_time(field, expr2)?));
+    }
+
+    ensure!(
+        matches!(op, CompareOp::Eq | CompareOp::Ne),
+        UnsupportedOperator {
+            field: field.to_string(),
+            op: op.as_sql(),
+        }
+    );
+    let filter = if field.is_bool() {
+        if get_bool(field, expr2)? {
+            Filter::Flag(field)
+        } else {
+            Filter::Flag(field).not()
+        }
+    } else {
+        let value = get_string(field, expr2)?;
+        match field {
+            Field::Tag => Filter::Tag(vec![value]),
+            Field::Content => Filter::Content(TextOp::Equals, value),
+            Field::Visibility => Filter::Visibility(get_visibilities(field, vec![value])?),
+            Field::Creator => Filter::Creator(get_creator_ids(field, vec![value])?),
+            _ => return UnsupportedExpression.fail(),
+        }
+    };
+
+    if op == CompareOp::Ne {
+        Ok(filter.not())
+    } else {
+        Ok(filter)
+    }
+}
+
+fn get_field(ident: &str) -> Result<Field, Error> {
+    Field::from_ident(ident).ok_or_else(|| Error::UnknownIdentifier {
+        name: ident.to_owned(),
+    })
+}
+
+fn get_function(func: &Expression) -> Result<&'static str, Error> {
+    let Expression::Ident(name) = func else {
+        return UnsupportedExpression.fail();
+    };
+    match name.as_str() {
+        "contains" => Ok("contains"),
+        "startsWith" => Ok("startsWith"),
+        "matches" => Ok("matches"),
+        "size" => Ok("size"),
+        _ => UnsupportedFunction {
+            name: name.as_str(),
+        }
+        .fail(),
+    }
+}
+
+fn get_compare_op(field: Field, op: &RelationOp) -> Result<CompareOp, Error> {
+    CompareOp::from_relation(op).ok_or_else(|| Error::UnsupportedOperator {
+        field: field.to_string(),
+        op: "in".to_owned(),
+    })
+}
+
+fn get_string(field: Field, expr: &Expression) -> Result<String, Error> {
+    match expr {
+        Expression::Atom(Atom::String(s)) => Ok(s.to_string()),
+        _ => TypeMismatch {
+            field: field.to_string(),
+            expected: "string",
+        }
+        .fail(),
+    }
+}
+
+fn get_int(field: Field, expr: &Expression) -> Result<i64, Error> {
+    match expr {
+        Expression::Atom(Atom::Int(i)) => Ok(*i),
+        Expression::Atom(Atom::UInt(i)) => Ok(*i as i64),
+        _ => TypeMismatch {
+            field: field.to_string(),
+            expected: "int",
+        }
+        .fail(),
+    }
+}
+
+fn get_bool(field: Field, expr: &Expression) -> Result<bool, Error> {
+    match expr {
+        Expression::Atom(Atom::Bool(b)) => Ok(*b),
+        _ => TypeMismatch {
+            field: field.to_string(),
+            expected: "bool",
+        }
+        .fail(),
+    }
+}
+
+/// RFC 3339 字符串或 unix 时间戳
+fn get_time(field: Field, expr: &Expression) -> Result<i64, Error> {
+    match expr {
+        Expression::Atom(Atom::String(s)) => OffsetDateTime::parse(s, &Rfc3339)
+            .map(|t| t.unix_timestamp())
+            .map_err(|_| Error::TypeMismatch {
+                field: field.to_string(),
+                expected: "RFC 3339 timestamp".to_owned(),
+            }),
+        _ => get_int(field, expr),
+    }
+}
+
+fn get_visibilities(field: Field, values: Vec<String>) -> Result<Vec<Visibility>, Error> {
+    values
+        .iter()
+        .map(|v| {
+            Visibility::from_str_name(v).ok_or_else(|| Error::TypeMismatch {
+                field: field.to_string(),
+                expected: "PUBLIC, PROTECTED or PRIVATE".to_owned(),
+            })
+        })
+        .collect()
+}
+
+fn get_creator_ids(field: Field, values: Vec<String>) -> Result<Vec<i32>, Error> {
+    values
+        .iter()
+        .map(|v| {
+            prefix::get_id_parent_token(v, prefix::USER_NAME_PREFIX).map_err(|_| {
+                Error::TypeMismatch {
+                    field: field.to_string(),
+                    expected: "users/{id}".to_owned(),
+                }
+            })
+        })
+        .collect()
+}
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("Failed to parse filter: {message}"), context(suffix(false)))]
+    Parse { message: String },
+    #[snafu(display("Unknown identifier: {name}"), context(suffix(false)))]
+    UnknownIdentifier { name: String },
+    #[snafu(display("Unsupported function: {name}"), context(suffix(false)))]
+    UnsupportedFunction { name: String },
+    #[snafu(display("Invalid arguments for {function}"), context(suffix(false)))]
+    InvalidArguments { function: String },
+    #[snafu(
+        display("Unsupported operator {op} for {field}"),
+        context(suffix(false))
+    )]
+    UnsupportedOperator { field: String, op: String },
+    #[snafu(
+        display("Type mismatch for {field}: expected {expected}"),
+        context(suffix(false))
+    )]
+    TypeMismatch { field: String, expected: String },
+    #[snafu(display("Unsupported filter expression"), context(suffix(false)))]
+    UnsupportedExpression,
+    #[snafu(
+        display("Invalid filter at {position}: {message}"),
+        context(suffix(false))
+    )]
+    InvalidLegacyFilter { position: usize, message: String },
+}
+
+mod test {
+    #[test]
+    fn compile_cel() {
+        use super::{CompareOp, Field, Filter, TextOp};
+
+        let filter = Filter::from_cel(
+            r#"tag in ["work"] && content.contains("hi") && create_time > 1700000000 && !pinned"#,
+        )
+        .unwrap();
+        assert_eq!(
+            Some(
+                Filter::Tag(vec!["work".to_string()])
+                    .and(Filter::Content(TextOp::Contains, "hi".to_string()))
+                    .and(Filter::Time(Field::CreateTime, CompareOp::Gt, 1700000000))
+                    .and(Filter::Flag(Field::Pinned).not())
+            ),
+            filter
+        );
+        assert_eq!(None, Filter::from_cel("").unwrap());
+    }
+
+    #[test]
+    fn reject_cel() {
+        use super::{Error, Filter};
+
+        assert!(matches!(
+            Filter::from_cel("id == 1 || 1 == 1"),
+            Err(Error::UnknownIdentifier { .. })
+        ));
+        assert!(matches!(
+            Filter::from_cel(r#"content.lower("x")"#),
+            Err(Error::UnsupportedFunction { .. })
+        ));
+        assert!(matches!(
+            Filter::from_cel(r#"pinned == "yes""#),
+            Err(Error::TypeMismatch { .. })
+        ));
+        assert!(matches!(
+            Filter::from_cel("tag == "),
+            Err(Error::Parse { .. })
+        ));
+    }
+}
@@ -0,0 +1,529 @@
+//! 旧版 old_filter，如 `creator == "users/1" && tag_search == ['TODO']`
+
+use snafu::ensure;
+
+use crate::api::v1::gen::{State, Visibility};
+
+use super::{CompareOp, Error, Field, Filter, InvalidLegacyFilter};
+
+/// 旧版 filter 中不属于过滤条件的查询选项
+#[derive(Debug, Default, PartialEq)]
+pub struct FilterOptions {
+    pub order_by_pinned: Option<bool>,
+    pub random: Option<bool>,
+    pub limit: Option<i32>,
+    pub include_comments: Option<bool>,
+    /// 最外层的 creator 决定查询谁的 memo，与 parent 等价
+    pub creator_id: Option<i32>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Str(String),
+    Int(i64),
+    Bool(bool),
+    LBracket,
+    RBracket,
+    LParen,
+    RParen,
+    Comma,
+    And,
+    Or,
+    Not,
+    Op(CompareOp),
+}
+
+#[derive(Debug)]
+enum Value {
+    Str(String),
+    Int(i64),
+    Bool(bool),
+    List(Vec<(usize, Value)>),
+}
+
+#[derive(Debug)]
+enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Compare {
+        position: usize,
+        key: String,
+        op: CompareOp,
+        value: (usize, Value),
+    },
+}
+
+pub fn parse(input: &str) -> Result<(Option<Filter>, FilterOptions), Error> {
+    let mut options = FilterOptions::default();
+    if input.trim().is_empty() {
+        return Ok((None, options));
+    }
+
+    let tokens = tokenize(input)?;
+    let mut parser = Parser {
+        tokens,
+        index: 0,
+        end: input.len(),
+    };
+    let expr = parser.parse_or()?;
+    if let Some((position, token)) = parser.tokens.get(parser.index) {
+        return fail(*position, format!("unexpected {token:?}"));
+    }
+
+    // 查询选项只能出现在最外层的 && 中
+    let mut filter = None;
+    for expr in split_and(expr) {
+        if let Expr::Compare {
+            position,
+            key,
+            op,
+            value,
+        } = &expr
+        {
+            if set_option(&mut options, *position, key, *op, value)? {
+                continue;
+            }
+        }
+        let f = lower(expr)?;
+        filter = Some(match filter {
+            Some(filter) => Filter::and(filter, f),
+            None => f,
+        });
+    }
+    Ok((filter, options))
+}
+
+fn fail<T>(position: usize, message: impl Into<String>) -> Result<T, Error> {
+    InvalidLegacyFilter {
+        position,
+        message: message.into(),
+    }
+    .fail()
+}
+
+fn tokenize(input: &str) -> Result<Vec<(usize, Token)>, Error> {
+    let mut tokens = Vec::new();
+    let mut chars = input.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        let token = match c {
+            c if c.is_whitespace() => continue,
+            '[' => Token::LBracket,
+            ']' => Token::RBracket,
+            '(' => Token::LParen,
+            ')' => Token::RParen,
+            ',' => Token::Comma,
+            '&' | '|' => {
+                if chars.next_if(|&(_, n)| n == c).is_none() {
+                    return fail(i, format!("expected '{c}{c}'"));
+                }
+                if c == '&' {
+                    Token::And
+                } else {
+                    Token::Or
+                }
+            }
+            '=' => {
+                if chars.next_if(|&(_, n)| n == '=').is_none() {
+                    return fail(i, "expected '=='");
+                }
+                Token::Op(CompareOp::Eq)
+            }
+            '!' => {
+                if chars.next_if(|&(_, n)| n == '=').is_some() {
+                    Token::Op(CompareOp::Ne)
+                } else {
+                    Token::Not
+                }
+            }
+            '<' | '>' => {
+                let eq = chars.next_if(|&(_, n)| n == '=').is_some();
+                Token::Op(match (c, eq) {
+                    ('<', false) => CompareOp::Lt,
+                    ('<', true) => CompareOp::Le,
+                    ('>', false) => CompareOp::Gt,
+                    _ => CompareOp::Ge,
+                })
+            }
+            '"' | '\'' => {
+                let mut s = String::new();
+                loop {
+                    match chars.next() {
+                        Some((_, '\\')) => match chars.next() {
+                            Some((_, e)) => s.push(e),
+                            None => return fail(i, "unterminated string"),
+                        },
+                        Some((_, e)) if e == c => break,
+                        Some((_, e)) => s.push(e),
+                        None => return fail(i, "unterminated string"),
+                    }
+                }
+                Token::Str(s)
+            }
+            c if c.is_ascii_digit() || c == '-' => {
+                let mut s = String::from(c);
+                while let Some((_, d)) = chars.next_if(|&(_, d)| d.is_ascii_digit()) {
+                    s.push(d);
+                }
+                match s.parse() {
+                    Ok(n) => Token::Int(n),
+                    Err(_) => return fail(i, format!("invalid number '{s}'")),
+                }
+            }
+            c if c.is_ascii_alphabetic() || c == '_' => {
+                let mut s = String::from(c);
+                while let Some((_, d)) =
+                    chars.next_if(|&(_, d)| d.is_ascii_alphanumeric() || d == '_')
+                {
+                    s.push(d);
+                }
+                match s.as_str() {
+                    "true" => Token::Bool(true),
+                    "false" => Token::Bool(false),
+                    _ => Token::Ident(s),
+                }
+            }
+            _ => return fail(i, format!("unexpected character '{c}'")),
+        };
+        tokens.push((i, token));
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    index: usize,
+    /// 输入长度，用于报告意外结束的位置
+    end: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.index).map(|(_, t)| t)
+    }
+
+    fn advance(&mut self) -> Result<(usize, Token), Error> {
+        match self.tokens.get(self.index) {
+            Some(token) => {
+                self.index += 1;
+                Ok(token.clone())
+            }
+            None => fail(self.end, "unexpected end of filter"),
+        }
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<(), Error> {
+        let (position, token) = self.advance()?;
+        ensure!(
+            token == expected,
+            InvalidLegacyFilter {
+                position,
+                message: format!("expected {expected:?}, found {token:?}"),
+            }
+        );
+        Ok(())
+    }
+
+    fn parse_or(&mut self) -> Result<Expr, Error> {
+        let mut expr = self.parse_and()?;
+        while self.peek() == Some(&Token::Or) {
+            self.index += 1;
+            expr = Expr::Or(Box::new(expr), Box::new(self.parse_and()?));
+        }
+        Ok(expr)
+    }
+
+    fn parse_and(&mut self) -> Result<Expr, Error> {
+        let mut expr = self.parse_unary()?;
+        while self.peek() == Some(&Token::And) {
+            self.index += 1;
+            expr = Expr::And(Box::new(expr), Box::new(self.parse_unary()?));
+        }
+        Ok(expr)
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr, Error> {
+        let (position, token) = self.advance()?;
+        match token {
+            Token::Not => Ok(Expr::Not(Box::new(self.parse_unary()?))),
+            Token::LParen => {
+                let expr = self.parse_or()?;
+                self.expect(Token::RParen)?;
+                Ok(expr)
+            }
+            Token::Ident(key) => {
+                let op = match self.advance()? {
+                    (_, Token::Op(op)) => op,
+                    (position, token) => {
+                        return fail(position, format!("expected operator, found {token:?}"))
+                    }
+                };
+                let value = self.parse_value()?;
+                Ok(Expr::Compare {
+                    position,
+                    key,
+                    op,
+                    value,
+                })
+            }
+            token => fail(position, format!("expected key, found {token:?}")),
+        }
+    }
+
+    fn parse_value(&mut self) -> Result<(usize, Value), Error> {
+        let (position, token) = self.advance()?;
+        let value = match token {
+            Token::Str(s) => Value::Str(s),
+            Token::Int(n) => Value::Int(n),
+            Token::Bool(b) => Value::Bool(b),
+            Token::LBracket => {
+                let mut list = Vec::new();
+                if self.peek() == Some(&Token::RBracket) {
+                    self.index += 1;
+                } else {
+                    loop {
+                        list.push(self.parse_value()?);
+                        match self.advance()? {
+                            (_, Token::Comma) => continue,
+                            (_, Token::RBracket) => break,
+                            (position, token) => {
+                                return fail(
+                                    position,
+                                    format!("expected ',' or ']', found {token:?}"),
+                                )
+                            }
+                        }
+                    }
+                }
+                Value::List(list)
+            }
+            token => return fail(position, format!("expected value, found {token:?}")),
+        };
+        Ok((position, value))
+    }
+}
+
+fn split_and(expr: Expr) -> Vec<Expr> {
+    match expr {
+        Expr::And(expr1, expr2) => {
+            let mut rtn = split_and(*expr1);
+            rtn.extend(split_and(*expr2));
+            rtn
+        }
+        expr => vec![expr],
+    }
+}
+
+fn set_option(
+    options: &mut FilterOptions,
+    position: usize,
+    key: &str,
+    op: CompareOp,
+    (value_position, value): &(usize, Value),
+) -> Result<bool, Error> {
+    if !matches!(
+        key,
+        "order_by_pinned" | "random" | "limit" | "include_comments" | "creator"
+    ) {
+        return Ok(false);
+    }
+    // 其余形式的 creator 作为普通过滤条件
+    if key == "creator" && (op != CompareOp::Eq || options.creator_id.is_some()) {
+        return Ok(false);
+    }
+    if op != CompareOp::Eq {
+        return fail(position, format!("{key} only supports '=='"));
+    }
+    match (key, value) {
+        ("order_by_pinned", Value::Bool(b)) => options.order_by_pinned = Some(*b),
+        ("random", Value::Bool(b)) => options.random = Some(*b),
+        ("include_comments", Value::Bool(b)) => options.include_comments = Some(*b),
+        ("limit", Value::Int(n)) => match i32::try_from(*n) {
+            Ok(n) if n > 0 => options.limit = Some(n),
+            _ => return fail(*value_position, "limit must be a positive integer"),
+        },
+        ("limit", _) => return fail(*value_position, "expected integer"),
+        ("creator", Value::Str(creator)) => {
+            let id = super::get_creator_ids(Field::Creator, vec![creator.clone()])
+                .or_else(|e| fail(*value_position, e.to_string()))?;
+            options.creator_id = id.first().copied();
+        }
+        ("creator", _) => return fail(*value_position, "expected string"),
+        _ => return fail(*value_position, "expected bool"),
+    }
+    Ok(true)
+}
+
+fn lower(expr: Expr) -> Result<Filter, Error> {
+    let (position, key, op, (value_position, value)) = match expr {
+        Expr::And(expr1, expr2) => return Ok(lower(*expr1)?.and(lower(*expr2)?)),
+        Expr::Or(expr1, expr2) => return Ok(lower(*expr1)?.or(lower(*expr2)?)),
+        Expr::Not(expr) => return Ok(lower(*expr)?.not()),
+        Expr::Compare {
+            position,
+            key,
+            op,
+            value,
+        } => (position, key, op, value),
+    };
+
+    let filter = match key.as_str() {
+        "display_time_before" | "display_time_after" => {
+            ensure_eq(position, &key, op)?;
+            let Value::Int(ts) = value else {
+                return fail(value_position, "expected integer");
+            };
+            let op = if key == "display_time_before" {
+                CompareOp::Lt
+            } else {
+                CompareOp::Gt
+            };
+            return Ok(Filter::Time(Field::DisplayTime, op, ts));
+        }
+        "order_by_pinned" | "random" | "limit" | "include_comments" => {
+            return fail(position, format!("{key} must be at the top level"));
+        }
+        "creator" => {
+            let creator = get_string(value_position, value)?;
+            let id = super::get_creator_ids(Field::Creator, vec![creator])
+                .or_else(|e| fail(value_position, e.to_string()))?;
+            Filter::Creator(id)
+        }
+        "state" => {
+            let state = get_string(value_position, value)?;
+            match State::from_str_name(&state) {
+                Some(state) => Filter::State(state),
+                None => return fail(value_position, format!("invalid state '{state}'")),
+            }
+        }
+        "uid" => Filter::Uid(get_string(value_position, value)?),
+        "visibilities" => {
+            let mut visibilities = Vec::new();
+            for (position, value) in get_list(value_position, value)? {
+                let visibility = get_string(position, value)?;
+                match Visibility::from_str_name(&visibility) {
+                    Some(v) => visibilities.push(v),
+                    None => return fail(position, format!("invalid visibility '{visibility}'")),
+                }
+            }
+            Filter::Visibility(visibilities)
+        }
+        // 每一项都需命中
+        "tag_search" | "content_search" => {
+            let mut filter: Option<Filter> = None;
+            for (position, value) in get_list(value_position, value)? {
+                let text = get_string(position, value)?;
+                let f = if key == "tag_search" {
+                    Filter::Tag(vec![text])
+                } else {
+                    Filter::Matches(text)
+                };
+                filter = Some(match filter {
+                    Some(filter) => filter.and(f),
+                    None => f,
+                });
+            }
+            match filter {
+                Some(filter) => filter,
+                None => return fail(value_position, "expected non-empty list"),
+            }
+        }
+        "has_link" | "has_task_list" | "has_code" | "has_incomplete_tasks" => {
+            let Value::Bool(b) = value else {
+                return fail(value_position, "expected bool");
+            };
+            let field = Field::from_ident(&key).unwrap_or(Field::HasLink);
+            if b {
+                Filter::Flag(field)
+            } else {
+                Filter::Flag(field).not()
+            }
+        }
+        _ => return fail(position, format!("unknown key '{key}'")),
+    };
+
+    match op {
+        CompareOp::Eq => Ok(filter),
+        CompareOp::Ne => Ok(filter.not()),
+        _ => fail(position, format!("{key} only supports '==' and '!='")),
+    }
+}
+
+fn ensure_eq(position: usize, key: &str, op: CompareOp) -> Result<(), Error> {
+    if op == CompareOp::Eq {
+        Ok(())
+    } else {
+        fail(position, format!("{key} only supports '=='"))
+    }
+}
+
+fn get_string(position: usize, value: Value) -> Result<String, Error> {
+    match value {
+        Value::Str(s) => Ok(s),
+        _ => fail(position, "expected string"),
+    }
+}
+
+fn get_list(position: usize, value: Value) -> Result<Vec<(usize, Value)>, Error> {
+    match value {
+        Value::List(list) => Ok(list),
+        _ => fail(position, "expected list"),
+    }
+}
+
+mod test {
+    #[test]
+    fn parse_legacy() {
+        use super::FilterOptions;
+        use crate::api::v1::gen::{State, Visibility};
+        use crate::model::filter::{CompareOp, Field, Filter};
+
+        let (filter, options) = super::parse(
+            r#"visibilities == ['PUBLIC'] && state == "NORMAL" && creator == "users/1" && order_by_pinned == true && (display_time_before == 123 || tag_search == ["TODO"])"#,
+        )
+        .unwrap();
+        assert_eq!(
+            Some(
+                Filter::Visibility(vec![Visibility::Public])
+                    .and(Filter::State(State::Normal))
+                    .and(
+                        Filter::Time(Field::DisplayTime, CompareOp::Lt, 123)
+                            .or(Filter::Tag(vec!["TODO".to_string()]))
+                    )
+            ),
+            filter
+        );
+        assert_eq!(
+            FilterOptions {
+                order_by_pinned: Some(true),
+                creator_id: Some(1),
+                ..Default::default()
+            },
+            options
+        );
+    }
+
+    #[test]
+    fn legacy_error_position() {
+        use crate::model::filter::Error;
+
+        let err = super::parse(r#"state == "NORMAL" && colour == "red""#).unwrap_err();
+        assert!(matches!(
+            err,
+            Error::InvalidLegacyFilter { position: 21, .. }
+        ));
+
+        let err = super::parse(r#"tag_search == ["a" "b"]"#).unwrap_err();
+        assert!(matches!(
+            err,
+            Error::InvalidLegacyFilter { position: 19, .. }
+        ));
+
+        let err = super::parse("limit == 10 || pinned == true").unwrap_err();
+        assert!(matches!(
+            err,
+            Error::InvalidLegacyFilter { position: 0, .. }
+        ));
+    }
+}
@@ -0,0 +1,83 @@
+use crate::api::v1::gen::identity_provider::Type;
+
+#[derive(Debug, Default, Clone, serde::Deserialize)]
+#[serde(default)]
+pub struct IdentityProvider {
+    pub id: i32,
+    pub name: String,
+    pub r#type: Type,
+    pub identifier_filter: String,
+    #[serde(deserialize_with = "crate::model::idp::config_serde::deserialize")]
+    pub config: IdentityProviderConfig,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct CreateIdentityProvider {
+    pub name: String,
+    pub r#type: Type,
+    pub identifier_filter: String,
+    pub config: IdentityProviderConfig,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct UpdateIdentityProvider {
+    pub id: i32,
+    pub name: Option<String>,
+    pub identifier_filter: Option<String>,
+    pub config: Option<IdentityProviderConfig>,
+}
+
+/// 与 Go 版 protojson 存储格式一致
+#[derive(Debug, Default, Clone, serde::Deserialize, serde::Serialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct IdentityProviderConfig {
+    pub oauth2_config: Option<OAuth2Config>,
+}
+
+#[derive(Debug, Default, Clone, serde::Deserialize, serde::Serialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct OAuth2Config {
+    pub client_id: String,
+    pub client_secret: String,
+    pub auth_url: String,
+    pub token_url: String,
+    pub user_info_url: String,
+    pub scopes: Vec<String>,
+    pub field_mapping: FieldMapping,
+}
+
+/// userinfo 响应中各字段的路径，支持以 `.` 分隔的嵌套字段
+#[derive(Debug, Default, Clone, serde::Deserialize, serde::Serialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct FieldMapping {
+    pub identifier: String,
+    pub display_name: String,
+    pub email: String,
+}
+
+/// 按 FieldMapping 从 userinfo 中取出的用户信息
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct IdentityProviderUserInfo {
+    pub identifier: String,
+    pub display_name: String,
+    pub email: String,
+}
+
+impl From<IdentityProviderConfig> for libsql::Value {
+    fn from(val: IdentityProviderConfig) -> Self {
+        libsql::Value::Text(serde_json::to_string(&val).unwrap_or("{}".to_string()))
+    }
+}
+
+pub mod config_serde {
+    use super::IdentityProviderConfig;
+    use serde::{self, Deserialize, Deserializer};
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<IdentityProviderConfig, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let config = String::deserialize(deserializer)?;
+        Ok(serde_json::from_str(&config).unwrap_or_default())
+    }
+}
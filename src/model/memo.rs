@@ -1,11 +1,6 @@
-use crate::util::ast;
+use super::filter::Filter;
 
-use crate::api::v1::gen::{Direction, PageToken, State, Visibility};
-
-use syn::{
-    parse::{Parse, ParseStream},
-    parse_quote, BinOp, Expr,
-};
+use crate::api::v1::gen::{memo_relation, user::Role, Direction, PageToken, State, Visibility};
 
 use super::gen::memo_payload::Property;
 use super::gen::MemoPayload;
@@ -17,6 +12,8 @@ pub struct Memo {
     pub uid: String,
     pub state: State,
     pub creator_id: i32,
+    /// 创建者的角色，管理员只能修改角色更低的用户的 memo
+    pub creator_role: Role,
     pub created_ts: i64,
     pub updated_ts: i64,
     pub content: String,
@@ -25,11 +22,18 @@ pub struct Memo {
     pub pinned: bool,
     #[serde(deserialize_with = "crate::model::memo::payload_serde::deserialize")]
     pub payload: MemoPayload,
+    /// 评论所属的 memo
+    #[serde(deserialize_with = "crate::model::option_serde::deserialize")]
+    pub parent_id: Option<i32>,
+    /// 全文检索命中的高亮片段
+    #[serde(deserialize_with = "crate::model::option_serde::deserialize")]
+    pub snippet: Option<String>,
 }
 
 #[derive(Debug, Default)]
 pub struct FindMemo {
     pub id: Option<i32>,
+    pub id_list: Vec<i32>,
     pub uid: Option<String>,
 
     // Standard fields
@@ -58,7 +62,7 @@ pub struct FindMemo {
 
     pub sort: String,
     pub direction: Direction,
-    pub filter: String,
+    pub filter: Option<Filter>,
 }
 
 #[allow(dead_code)]
@@ -72,6 +76,48 @@ pub struct FindMemoPayload {
     pub has_incomplete_tasks: bool,
 }
 
+#[derive(Debug, Default, Clone, serde::Deserialize)]
+#[serde(default)]
+pub struct MemoRelation {
+    pub memo_id: i32,
+    pub memo_uid: String,
+    pub memo_snippet: String,
+    pub related_memo_id: i32,
+    pub related_memo_uid: String,
+    pub related_memo_snippet: String,
+    pub r#type: memo_relation::Type,
+    /// 两端 memo 的可见性，用于按当前用户过滤关联
+    pub memo_creator_id: i32,
+    pub memo_visibility: Visibility,
+    pub related_memo_creator_id: i32,
+    pub related_memo_visibility: Visibility,
+}
+
+#[derive(Debug, Default)]
+pub struct FindMemoRelation {
+    pub memo_id: Option<i32>,
+    pub related_memo_id: Option<i32>,
+    /// memo_id 或 related_memo_id 在其中
+    pub memo_id_list: Vec<i32>,
+    pub r#type: Option<memo_relation::Type>,
+}
+
+/// memo 内容变更前的快照
+#[derive(Debug, Default, Clone, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct MemoRevision {
+    pub id: i32,
+    pub memo_id: i32,
+    pub created_ts: i64,
+    pub content: String,
+}
+
+#[derive(Debug, Default)]
+pub struct FindMemoRevision {
+    pub id: Option<i32>,
+    pub memo_id: Option<i32>,
+}
+
 pub struct CreateMemo {
     pub creator_id: i32,
     pub uid: String,
@@ -91,88 +137,6 @@ pub struct UpdateMemo {
     pub payload: Option<MemoPayload>,
 }
 
-#[derive(Debug, Default)]
-pub struct SearchMemosFilter {
-    pub content_search: Option<Vec<String>>,
-    pub visibilities: Option<Vec<Visibility>>,
-    pub tag_search: Option<Vec<String>>,
-    pub order_by_pinned: Option<bool>,
-    pub display_time_before: Option<i64>,
-    pub display_time_after: Option<i64>,
-    pub creator: Option<String>,
-    pub uid: Option<String>,
-    pub state: Option<State>,
-    pub random: Option<bool>,
-    pub limit: Option<i32>,
-    pub include_comments: Option<bool>,
-    pub has_link: Option<bool>,
-    pub has_task_list: Option<bool>,
-    pub has_code: Option<bool>,
-    pub has_incomplete_tasks: Option<bool>,
-}
-
-impl Parse for SearchMemosFilter {
-    fn parse(input: ParseStream) -> syn::Result<Self> {
-        let mut binary = Expr::parse(input)?;
-
-        let mut filter = SearchMemosFilter::default();
-        let mut get_value = |ident: Expr, lit: Expr| {
-            if ident == parse_quote!(creator) {
-                filter.creator = ast::get_string(lit);
-            } else if ident == parse_quote!(state) {
-                filter.state = ast::get_state(lit);
-            } else if ident == parse_quote!(visibilities) {
-                filter.visibilities = ast::get_visibilities(lit);
-            } else if ident == parse_quote!(order_by_pinned) {
-                filter.order_by_pinned = ast::get_bool(lit);
-            } else if ident == parse_quote!(content_search) {
-                filter.content_search = ast::get_string_list(lit);
-            } else if ident == parse_quote!(display_time_before) {
-                filter.display_time_before = ast::get_int(lit);
-            } else if ident == parse_quote!(display_time_after) {
-                filter.display_time_after = ast::get_int(lit);
-            } else if ident == parse_quote!(tag_search) {
-                filter.tag_search = ast::get_string_list(lit);
-            } else if ident == parse_quote!(uid) {
-                filter.uid = ast::get_string(lit);
-            } else if ident == parse_quote!(random) {
-                filter.random = ast::get_bool(lit);
-            } else if ident == parse_quote!(limit) {
-                filter.limit = ast::get_int(lit);
-            } else if ident == parse_quote!(include_comments) {
-                filter.include_comments = ast::get_bool(lit);
-            } else if ident == parse_quote!(has_link) {
-                filter.has_link = ast::get_bool(lit);
-            } else if ident == parse_quote!(has_task_list) {
-                filter.has_task_list = ast::get_bool(lit);
-            } else if ident == parse_quote!(has_code) {
-                filter.has_code = ast::get_bool(lit);
-            } else if ident == parse_quote!(has_incomplete_tasks) {
-                filter.has_incomplete_tasks = ast::get_bool(lit);
-            }
-        };
-
-        while let Expr::Binary(ref bin) = binary {
-            let left = *bin.left.clone();
-            let right = *bin.right.clone();
-
-            if matches!(bin.op, BinOp::And(_)) {
-                if let Expr::Binary(bin) = right {
-                    let ident = *bin.left;
-                    let lit = *bin.right;
-                    get_value(ident, lit);
-                }
-                binary = left;
-            } else {
-                get_value(left, right);
-                break;
-            }
-        }
-
-        Ok(filter)
-    }
-}
-
 impl FindMemo {
     pub fn completed(&mut self, user_id: Option<i32>, is_display_with_update_time: bool) {
         if let Some(user_id) = user_id {
@@ -213,9 +177,18 @@ impl MemoPayload {
             property1.has_task_list = property1.has_task_list || property2.has_task_list;
             property1.has_incomplete_tasks =
                 property1.has_incomplete_tasks || property2.has_incomplete_tasks;
+            property1.references.extend(property2.references);
         }
     }
 
+    /// 内容中引用的 memo id
+    pub fn reference_ids(&self) -> Vec<i32> {
+        self.property
+            .as_ref()
+            .map(|p| p.references.iter().filter_map(|r| r.parse().ok()).collect())
+            .unwrap_or_default()
+    }
+
     #[allow(dead_code)]
     pub fn tag(tag: String) -> Self {
         Self {
@@ -235,6 +208,16 @@ impl MemoPayload {
         }
     }
 
+    pub fn references(references: Vec<String>) -> Self {
+        Self {
+            property: Some(Property {
+                references,
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
     pub fn link() -> Self {
         Self {
             property: Some(Property {
@@ -304,22 +287,3 @@ pub mod payload_serde {
         Ok(serde_json::from_str(&payload).unwrap_or_default())
     }
 }
-
-mod test {
-    #[test]
-    fn parse_filter() {
-        use crate::api::v1::gen::{State, Visibility};
-        use crate::model::memo::SearchMemosFilter;
-
-        let filter =
-        r#"visibilities == ['PUBLIC'] && state == "NORMAL" && creator == "users/THELOSTSOUL" && order_by_pinned == true && display_time_before == 123 && tag_search == ["TODO"]"#
-            .replace('\'', "\"");
-        let filter = syn::parse_str::<SearchMemosFilter>(&filter).unwrap();
-        assert_eq!(filter.state, Some(State::Normal));
-        assert_eq!(filter.visibilities, Some(vec![Visibility::Public]));
-        assert_eq!(filter.creator, Some("users/THELOSTSOUL".to_string()));
-        assert_eq!(filter.order_by_pinned, Some(true));
-        assert_eq!(filter.display_time_before, Some(123_i64));
-        assert_eq!(filter.tag_search, Some(vec!["TODO".to_string()]));
-    }
-}
@@ -1,6 +1,9 @@
+pub mod filter;
 pub mod gen;
+pub mod idp;
 pub mod memo;
 pub mod pager;
+pub mod reaction;
 pub mod resource;
 pub mod session;
 pub mod system;
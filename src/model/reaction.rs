@@ -0,0 +1,19 @@
+use serde::Deserialize;
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct Reaction {
+    pub id: i32,
+    pub created_ts: i64,
+    pub creator_id: i32,
+    /// memos/{id}
+    pub content_id: String,
+    pub reaction_type: String,
+}
+
+#[derive(Debug, Default)]
+pub struct FindReaction {
+    pub id: Option<i32>,
+    pub creator_id: Option<i32>,
+    pub content_id_list: Vec<String>,
+}
@@ -2,7 +2,7 @@ use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
 use crate::util;
 
-use super::gen::{ResourcePayload, ResourceStorageType};
+use super::gen::{memo_payload::Location, ResourcePayload, ResourceStorageType};
 
 #[derive(Debug, Default)]
 pub struct FindResource {
@@ -16,9 +16,11 @@ pub struct FindResource {
     pub offset: Option<isize>,
     pub has_relate_memo: bool,
     pub storage_type: Option<ResourceStorageType>,
+    /// 为 true 时只查回收站中的资源，否则排除回收站
+    pub trashed: bool,
 }
 
-#[derive(Debug, Default, Deserialize)]
+#[derive(Debug, Default, Clone, Deserialize)]
 #[serde(default)]
 pub struct Resource {
     pub id: i32,
@@ -40,11 +42,110 @@ pub struct Resource {
     pub payload: ResourcePayload,
     #[serde(deserialize_with = "crate::model::option_serde::deserialize")]
     pub memo_id: Option<i32>,
+    /// 内容的 SM3 摘要，为空时内容保存在本记录中
+    pub hash: String,
+    /// 移入回收站的时间
+    #[serde(deserialize_with = "crate::model::option_serde::deserialize")]
+    pub trashed_ts: Option<i64>,
+    /// 移除元数据前提取的拍摄位置，关联 memo 时写入 memo
+    #[serde(deserialize_with = "crate::model::resource::location_serde::deserialize")]
+    pub location: Option<Location>,
+}
+
+/// 按内容摘要去重后的资源内容，ref_count 为引用它的 resource 数
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct ResourceBlob {
+    pub hash: String,
+    pub size: usize,
+    pub blob: Vec<u8>,
+    pub storage_type: ResourceStorageType,
+    pub reference: String,
+    #[serde(deserialize_with = "crate::model::resource::payload_serde::deserialize")]
+    pub payload: ResourcePayload,
+    pub ref_count: i32,
+}
+
+impl Resource {
+    /// 引用已保存的内容
+    pub fn link_blob(&mut self, blob: &ResourceBlob) {
+        self.blob = Vec::new();
+        self.hash = blob.hash.clone();
+        self.storage_type = blob.storage_type;
+        self.reference = blob.reference.clone();
+        self.payload = blob.payload.clone();
+    }
+}
+
+impl From<ResourceBlob> for Resource {
+    fn from(value: ResourceBlob) -> Self {
+        Self {
+            blob: value.blob,
+            size: value.size,
+            storage_type: value.storage_type,
+            reference: value.reference,
+            payload: value.payload,
+            hash: value.hash,
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct UpdateResource {
+    pub id: i32,
+    pub filename: Option<String>,
+    /// Some(None) 表示解除与 memo 的关联
+    pub memo_id: Option<Option<i32>>,
+    /// Some(false) 表示从回收站恢复
+    pub trashed: Option<bool>,
+}
+
+/// 孤立资源回收的结果，内容仍被其他资源引用时实际释放的空间会更少
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResourceGcReport {
+    pub dry_run: bool,
+    pub resources: usize,
+    pub bytes: usize,
+    pub users: Vec<UserReclaim>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UserReclaim {
+    pub creator_id: i32,
+    pub resources: usize,
+    pub bytes: usize,
+}
+
+impl ResourceGcReport {
+    pub fn add(&mut self, resource: &Resource) {
+        self.resources += 1;
+        self.bytes += resource.size;
+        match self
+            .users
+            .iter_mut()
+            .find(|u| u.creator_id == resource.creator_id)
+        {
+            Some(user) => {
+                user.resources += 1;
+                user.bytes += resource.size;
+            }
+            None => self.users.push(UserReclaim {
+                creator_id: resource.creator_id,
+                resources: 1,
+                bytes: resource.size,
+            }),
+        }
+    }
 }
 
 #[derive(Deserialize)]
 pub struct ResourceQry {
     pub thumbnail: Option<String>,
+    /// 缩略图边长，取最接近的预设尺寸
+    pub size: Option<u32>,
 }
 
 impl From<crate::api::v1::gen::Resource> for Resource {
@@ -112,3 +213,18 @@ pub mod payload_serde {
         Ok(serde_json::from_str(&payload).unwrap_or_default())
     }
 }
+
+pub mod location_serde {
+    use crate::model::gen::memo_payload::Location;
+    use serde::{self, Deserialize, Deserializer};
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<Location>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let Ok(location) = String::deserialize(deserializer) else {
+            return Ok(None);
+        };
+        Ok(serde_json::from_str(&location).ok())
+    }
+}
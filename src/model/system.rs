@@ -1,4 +1,4 @@
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 #[derive(Default, Deserialize)]
 #[serde(default)]
@@ -7,3 +7,13 @@ pub struct SystemSetting {
     pub value: String,
     pub description: String,
 }
+
+/// 存储设置中 proto 未定义的隐私选项，单独保存在 STORAGE_PRIVACY 记录
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct StoragePrivacySetting {
+    /// 上传图片时移除 EXIF/XMP 元数据
+    pub strip_image_metadata: bool,
+    /// 移除前将 GPS 坐标写入关联 memo 的位置
+    pub keep_image_location: bool,
+}
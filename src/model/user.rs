@@ -27,6 +27,90 @@ pub struct User {
     pub updated_ts: i64,
 }
 
+#[derive(Debug, Default)]
+pub struct CreateUser {
+    pub username: String,
+    pub role: Role,
+    pub email: String,
+    pub nickname: String,
+    pub avatar_url: String,
+    pub description: String,
+    pub password_hash: String,
+}
+
+#[derive(Debug, Default)]
+pub struct UpdateUser {
+    pub id: i32,
+    pub username: Option<String>,
+    pub role: Option<Role>,
+    pub email: Option<String>,
+    pub nickname: Option<String>,
+    pub avatar_url: Option<String>,
+    pub description: Option<String>,
+    pub password_hash: Option<String>,
+    pub state: Option<State>,
+}
+
+/// 个人访问令牌记录，保存在 ACCESS_TOKENS 用户设置中
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccessToken {
+    pub id: String,
+    /// secret 的 SM3 摘要
+    pub digest: String,
+    pub description: String,
+    pub issued_ts: i64,
+    pub expires_ts: Option<i64>,
+}
+
+/// 用户存储配额，0 表示不限制
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct UserQuota {
+    pub user_id: i32,
+    pub bytes_limit: i64,
+    pub files_limit: i32,
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct StorageUsage {
+    pub bytes: i64,
+    pub files: i32,
+}
+
+/// 存储用量与配额，limit 为 0 表示不限制
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UserStorage {
+    pub user_id: i32,
+    pub bytes: i64,
+    pub files: i32,
+    pub bytes_limit: i64,
+    pub files_limit: i32,
+}
+
+impl UserStorage {
+    pub fn new(user_id: i32, usage: StorageUsage, quota: Option<UserQuota>) -> Self {
+        let quota = quota.unwrap_or_default();
+        Self {
+            user_id,
+            bytes: usage.bytes,
+            files: usage.files,
+            bytes_limit: quota.bytes_limit,
+            files_limit: quota.files_limit,
+        }
+    }
+
+    pub fn files_exhausted(&self) -> bool {
+        self.files_limit > 0 && self.files >= self.files_limit
+    }
+
+    /// 剩余可用字节数，None 表示不限制
+    pub fn remaining_bytes(&self) -> Option<usize> {
+        (self.bytes_limit > 0).then(|| (self.bytes_limit - self.bytes).max(0) as usize)
+    }
+}
+
 impl Serialize for UserSettingKey {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
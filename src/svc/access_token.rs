@@ -0,0 +1,107 @@
+//! 个人访问令牌：格式为 memos.{user_id}.{id}.{secret}，只保存 secret 的摘要，
+//! 明文仅在创建时返回一次
+
+use sm3::{Digest, Sm3};
+
+use crate::{
+    api::{to_timestamp, v1::gen::UserAccessToken},
+    model::user::AccessToken,
+    util,
+};
+
+const PREFIX: &str = "memos";
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct TokenRef<'a> {
+    pub user_id: i32,
+    pub id: &'a str,
+    secret: Option<&'a str>,
+}
+
+/// 解析令牌，不含 secret 的标识也能解析，用于删除
+pub fn parse(token: &str) -> Option<TokenRef<'_>> {
+    let mut parts = token.splitn(4, '.');
+    if parts.next()? != PREFIX {
+        return None;
+    }
+    let user_id = parts.next()?.parse().ok()?;
+    let id = parts.next().filter(|id| !id.is_empty())?;
+    let secret = parts.next();
+    Some(TokenRef {
+        user_id,
+        id,
+        secret,
+    })
+}
+
+/// 生成新令牌，返回明文与需要保存的记录
+pub fn issue(
+    user_id: i32,
+    description: String,
+    expires_ts: Option<i64>,
+    now: i64,
+) -> (String, AccessToken) {
+    let id = util::uuid();
+    let secret = util::secret();
+    let token = format!("{PREFIX}.{user_id}.{id}.{secret}");
+    let entry = AccessToken {
+        id,
+        digest: digest(&secret),
+        description,
+        issued_ts: now,
+        expires_ts,
+    };
+    (token, entry)
+}
+
+/// 列表中展示的标识，不含 secret
+pub fn identifier(user_id: i32, entry: &AccessToken) -> String {
+    format!("{PREFIX}.{user_id}.{}", entry.id)
+}
+
+pub fn verify(token: &TokenRef, entry: &AccessToken, now: i64) -> bool {
+    entry.id == token.id
+        && token.secret.is_some_and(|s| digest(s) == entry.digest)
+        && entry.expires_ts.is_none_or(|ts| ts > now)
+}
+
+pub fn to_api(entry: AccessToken, access_token: String) -> UserAccessToken {
+    UserAccessToken {
+        access_token,
+        description: entry.description,
+        issued_at: to_timestamp(entry.issued_ts),
+        expires_at: entry.expires_ts.and_then(to_timestamp),
+    }
+}
+
+fn digest(secret: &str) -> String {
+    let mut hasher = Sm3::new();
+    hasher.update(secret);
+    hex::encode(hasher.finalize())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{identifier, issue, parse, verify};
+
+    #[test]
+    fn issue_and_verify() {
+        let (token, entry) = issue(3, "cli".to_owned(), Some(200), 100);
+        let parsed = parse(&token).unwrap();
+        assert_eq!(parsed.user_id, 3);
+        assert!(verify(&parsed, &entry, 150));
+        assert!(!verify(&parsed, &entry, 200));
+        assert!(!token.contains(&entry.digest));
+
+        let name = identifier(3, &entry);
+        let named = parse(&name).unwrap();
+        assert_eq!(named.id, entry.id);
+        assert!(!verify(&named, &entry, 150));
+
+        let forged = format!("{name}.secret");
+        assert!(!verify(&parse(&forged).unwrap(), &entry, 150));
+        assert_eq!(parse("memos.x.id.secret"), None);
+        assert_eq!(parse("token.1.id.secret"), None);
+        assert_eq!(parse("memos.1."), None);
+    }
+}
@@ -13,7 +13,9 @@ use crate::{
     ctrl::AuthSession,
 };
 
-use super::{EmptyService, RequestExt};
+use super::idp::IDPService;
+use super::user::UserService;
+use super::{RequestExt, Service};
 
 #[async_trait]
 pub trait AuthService: auth_service_server::AuthService + Clone + Send + Sync + 'static {
@@ -23,10 +25,13 @@ pub trait AuthService: auth_service_server::AuthService + Clone + Send + Sync +
 }
 
 #[async_trait]
-impl AuthService for EmptyService {}
+impl<R> AuthService for Service<R> where Service<R>: UserService + IDPService {}
 
 #[tonic::async_trait]
-impl auth_service_server::AuthService for EmptyService {
+impl<R> auth_service_server::AuthService for Service<R>
+where
+    Service<R>: UserService + IDPService,
+{
     async fn get_auth_status(
         &self,
         request: Request<GetAuthStatusRequest>,
@@ -59,13 +64,31 @@ impl auth_service_server::AuthService for EmptyService {
     /// SignInWithSSO signs in the user with the given SSO code.
     async fn sign_in_with_sso(
         &self,
-        request: Request<SignInWithSsoRequest>,
+        mut request: Request<SignInWithSsoRequest>,
     ) -> Result<Response<User>, Status> {
-        Err(Status::unimplemented("unimplemented"))
+        let SignInWithSsoRequest {
+            idp_id,
+            code,
+            redirect_uri,
+        } = request.get_ref().clone();
+        let Some(session) = request.extensions_mut().get_mut::<AuthSession>() else {
+            return Err(Status::internal("Auth layer uninitialized"));
+        };
+
+        let user = IDPService::sign_in_with_sso(self, idp_id, &code, &redirect_uri).await?;
+        session.login(&user).await.context(Login)?;
+        Ok(Response::new(user.into()))
     }
     /// SignUp signs up the user with the given username and password.
-    async fn sign_up(&self, request: Request<SignUpRequest>) -> Result<Response<User>, Status> {
-        Err(Status::unimplemented("unimplemented"))
+    async fn sign_up(&self, mut request: Request<SignUpRequest>) -> Result<Response<User>, Status> {
+        let SignUpRequest { username, password } = request.get_ref().clone();
+        let Some(session) = request.extensions_mut().get_mut::<AuthSession>() else {
+            return Err(Status::internal("Auth layer uninitialized"));
+        };
+
+        let user = UserService::sign_up(self, &username, &password).await?;
+        session.login(&user).await.context(Login)?;
+        Ok(Response::new(user.into()))
     }
     /// SignOut signs out the user.
     async fn sign_out(&self, mut request: Request<SignOutRequest>) -> Result<Response<()>, Status> {
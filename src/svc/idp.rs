@@ -1,16 +1,33 @@
+pub mod oauth2;
+
 use std::sync::Arc;
 
 use async_trait::async_trait;
+use regex::Regex;
+use snafu::{ensure, OptionExt, ResultExt, Snafu};
 use tonic::{Request, Response, Status};
 
+use crate::api::prefix::ExtractName;
 use crate::api::v1::gen::{
+    identity_provider::Type,
     identity_provider_service_server::{self, IdentityProviderServiceServer},
+    user::Role,
     CreateIdentityProviderRequest, DeleteIdentityProviderRequest, GetIdentityProviderRequest,
-    IdentityProvider, ListIdentityProvidersRequest, ListIdentityProvidersResponse,
+    IdentityProvider, ListIdentityProvidersRequest, ListIdentityProvidersResponse, State,
     UpdateIdentityProviderRequest,
 };
+use crate::dao::idp::IdpRepository;
+use crate::dao::user::UserRepository;
+use crate::dao::workspace::WorkspaceRepository;
+use crate::model::idp::{
+    CreateIdentityProvider, IdentityProvider as IdentityProviderModel, UpdateIdentityProvider,
+};
+use crate::model::user::{CreateUser, User as UserModel};
+use crate::util;
 
-use super::EmptyService;
+use super::permission::{self, PermissionDeniedSnafu};
+use super::workspace::WorkspaceSettingService;
+use super::{password, user, RequestExt, Service};
 
 #[async_trait]
 pub trait IDPService:
@@ -19,19 +36,117 @@ pub trait IDPService:
     fn idp_server(self: Arc<Self>) -> IdentityProviderServiceServer<Self> {
         IdentityProviderServiceServer::from_arc(self)
     }
+
+    /// 授权码换取用户信息，仅登录与该身份关联的用户；
+    /// 首次登录时按工作区设置创建用户并关联，同名的本地用户不会被接管
+    async fn sign_in_with_sso(
+        &self,
+        idp_id: i32,
+        code: &str,
+        redirect_uri: &str,
+    ) -> Result<UserModel, Error>;
 }
 
 #[async_trait]
-impl IDPService for EmptyService {}
+impl<R: IdpRepository + UserRepository + WorkspaceRepository> IDPService for Service<R> {
+    async fn sign_in_with_sso(
+        &self,
+        idp_id: i32,
+        code: &str,
+        redirect_uri: &str,
+    ) -> Result<UserModel, Error> {
+        let idp = self.find_idp(idp_id).await?;
+        let config = idp
+            .config
+            .oauth2_config
+            .as_ref()
+            .filter(|_| idp.r#type == Type::Oauth2)
+            .context(InvalidIdpField { field: "config" })?;
+        let token = oauth2::exchange(config, code, redirect_uri)
+            .await
+            .context(OAuth2)?;
+        let info = oauth2::user_info(config, &token).await.context(OAuth2)?;
+
+        if !idp.identifier_filter.is_empty() {
+            let filter = Regex::new(&idp.identifier_filter).context(InvalidIdentifierFilter)?;
+            ensure!(
+                filter.is_match(&info.identifier),
+                IdentifierNotAllowed {
+                    identifier: info.identifier
+                }
+            );
+        }
+
+        let user = match self
+            .repo
+            .find_identity_user(idp_id, &info.identifier)
+            .await?
+        {
+            Some(user) => user,
+            None => {
+                let exists = self.repo.find_user(&info.identifier).await?.is_some();
+                ensure!(
+                    !exists,
+                    UserExists {
+                        username: info.identifier
+                    }
+                );
+                let setting = self.get_general_setting().await;
+                ensure!(!setting.disallow_user_registration, RegistrationDisabled);
+                ensure!(
+                    user::valid_username(&info.identifier),
+                    InvalidUsername {
+                        username: info.identifier
+                    }
+                );
+                // 仅能通过 SSO 登录，密码随机生成
+                let password_hash = password::hash(&util::secret()).context(HashPassword)?;
+                let create = CreateUser {
+                    username: info.identifier.clone(),
+                    role: Role::User,
+                    email: info.email,
+                    nickname: info.display_name,
+                    password_hash,
+                    ..Default::default()
+                };
+                self.repo
+                    .create_identity_user(idp_id, &info.identifier, create)
+                    .await?
+                    .context(MaybeCreateUser)?
+            }
+        };
+        ensure!(user.state != State::Archived, UserArchived);
+        Ok(user)
+    }
+}
+
+impl<R: IdpRepository> Service<R> {
+    async fn find_idp(&self, id: i32) -> Result<IdentityProviderModel, Error> {
+        self.repo.find_idp(id).await?.context(IdpNotFound)
+    }
+}
 
 #[tonic::async_trait]
-impl identity_provider_service_server::IdentityProviderService for EmptyService {
+impl<R: IdpRepository + UserRepository + WorkspaceRepository>
+    identity_provider_service_server::IdentityProviderService for Service<R>
+{
     async fn list_identity_providers(
         &self,
         request: Request<ListIdentityProvidersRequest>,
     ) -> Result<Response<ListIdentityProvidersResponse>, Status> {
+        // 登录页匿名访问
+        let superuser = request
+            .get_current_user()
+            .is_ok_and(permission::is_superuser);
+        let identity_providers = self
+            .repo
+            .list_idps()
+            .await?
+            .into_iter()
+            .map(|idp| to_api(idp, superuser))
+            .collect();
         Ok(Response::new(ListIdentityProvidersResponse {
-            identity_providers: vec![],
+            identity_providers,
         }))
     }
 
@@ -39,27 +154,240 @@ impl identity_provider_service_server::IdentityProviderService for EmptyService
         &self,
         request: Request<GetIdentityProviderRequest>,
     ) -> Result<Response<IdentityProvider>, Status> {
-        todo!()
+        let superuser = request
+            .get_current_user()
+            .is_ok_and(permission::is_superuser);
+        let idp = self.find_idp(request.get_ref().get_id()?).await?;
+        Ok(Response::new(to_api(idp, superuser)))
     }
 
     async fn create_identity_provider(
         &self,
         request: Request<CreateIdentityProviderRequest>,
     ) -> Result<Response<IdentityProvider>, Status> {
-        todo!()
+        let user = request.get_current_user()?;
+        ensure!(permission::is_superuser(user), PermissionDeniedSnafu);
+        let Some(create) = request.get_ref().identity_provider.clone() else {
+            return Err(Status::invalid_argument("identity_provider is required"));
+        };
+
+        let r#type = Type::try_from(create.r#type)
+            .ok()
+            .filter(|t| *t == Type::Oauth2)
+            .context(InvalidIdpField { field: "type" })?;
+        valid_identifier_filter(&create.identifier_filter)?;
+        let idp = self
+            .repo
+            .create_idp(CreateIdentityProvider {
+                name: create.title,
+                r#type,
+                identifier_filter: create.identifier_filter,
+                config: create.config.unwrap_or_default().into(),
+            })
+            .await?
+            .context(MaybeCreateIdp)?;
+        Ok(Response::new(to_api(idp, true)))
     }
 
     async fn update_identity_provider(
         &self,
         request: Request<UpdateIdentityProviderRequest>,
     ) -> Result<Response<IdentityProvider>, Status> {
-        todo!()
+        let user = request.get_current_user()?;
+        ensure!(permission::is_superuser(user), PermissionDeniedSnafu);
+        let UpdateIdentityProviderRequest {
+            identity_provider: Some(patch),
+            update_mask: Some(field_mask),
+        } = request.get_ref()
+        else {
+            return Err(Status::invalid_argument(
+                "identity_provider and update_mask are required",
+            ));
+        };
+        let id = patch.get_id()?;
+        self.find_idp(id).await?;
+
+        let mut update = UpdateIdentityProvider {
+            id,
+            ..Default::default()
+        };
+        for path in &field_mask.paths {
+            match path.as_str() {
+                "title" => update.name = Some(patch.title.clone()),
+                "identifier_filter" => {
+                    valid_identifier_filter(&patch.identifier_filter)?;
+                    update.identifier_filter = Some(patch.identifier_filter.clone());
+                }
+                "config" => update.config = Some(patch.config.clone().unwrap_or_default().into()),
+                _ => (),
+            }
+        }
+
+        self.repo.update_idp(update).await?;
+        let idp = self.find_idp(id).await?;
+        Ok(Response::new(to_api(idp, true)))
     }
 
     async fn delete_identity_provider(
         &self,
         request: Request<DeleteIdentityProviderRequest>,
     ) -> Result<Response<()>, Status> {
-        todo!()
+        let user = request.get_current_user()?;
+        ensure!(permission::is_superuser(user), PermissionDeniedSnafu);
+        let id = request.get_ref().get_id()?;
+        self.find_idp(id).await?;
+        self.repo.delete_idp(id).await?;
+        Ok(Response::new(()))
+    }
+}
+
+/// client_secret 仅对管理员可见
+fn to_api(mut idp: IdentityProviderModel, superuser: bool) -> IdentityProvider {
+    if !superuser {
+        if let Some(config) = idp.config.oauth2_config.as_mut() {
+            config.client_secret.clear();
+        }
+    }
+    idp.into()
+}
+
+fn valid_identifier_filter(filter: &str) -> Result<(), Error> {
+    if !filter.is_empty() {
+        Regex::new(filter).context(InvalidIdentifierFilter)?;
+    }
+    Ok(())
+}
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("Identity provider not found"), context(suffix(false)))]
+    IdpNotFound,
+
+    #[snafu(
+        display("Invalid identity provider field: {field}"),
+        context(suffix(false))
+    )]
+    InvalidIdpField { field: String },
+
+    #[snafu(display("Invalid identifier filter: {source}"), context(suffix(false)))]
+    InvalidIdentifierFilter { source: regex::Error },
+
+    #[snafu(
+        display("Identifier is not allowed: {identifier}"),
+        context(suffix(false))
+    )]
+    IdentifierNotAllowed { identifier: String },
+
+    #[snafu(
+        display("Failed to sign in with SSO: {source}"),
+        context(suffix(false))
+    )]
+    OAuth2 { source: oauth2::Error },
+
+    #[snafu(display("User registration is disabled"), context(suffix(false)))]
+    RegistrationDisabled,
+
+    #[snafu(display("Invalid username: {username}"), context(suffix(false)))]
+    InvalidUsername { username: String },
+
+    #[snafu(display("User is archived"), context(suffix(false)))]
+    UserArchived,
+
+    #[snafu(
+        display("User {username} exists but is not linked to this identity provider"),
+        context(suffix(false))
+    )]
+    UserExists { username: String },
+
+    #[snafu(display("Failed to hash password: {source}"), context(suffix(false)))]
+    HashPassword {
+        source: argon2::password_hash::Error,
+    },
+
+    #[snafu(
+        display("Maybe create identity provider failed, because return none"),
+        context(suffix(false))
+    )]
+    MaybeCreateIdp,
+
+    #[snafu(
+        display("Maybe create user failed, because return none"),
+        context(suffix(false))
+    )]
+    MaybeCreateUser,
+
+    #[snafu(context(false))]
+    FindIdp {
+        source: crate::dao::idp::FindIdpError,
+    },
+
+    #[snafu(context(false))]
+    QueryUser {
+        source: crate::dao::user::FindUserError,
+    },
+
+    #[snafu(context(false))]
+    FindIdentityUser {
+        source: crate::dao::idp::FindIdentityUserError,
+    },
+
+    #[snafu(context(false))]
+    CreateIdentityUser {
+        source: crate::dao::idp::CreateIdentityUserError,
+    },
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        api::v1::gen::{identity_provider::Type, user::Role},
+        dao::idp::IdpRepository,
+        model::idp::{CreateIdentityProvider, IdentityProviderConfig},
+        svc::test::{self, TestService},
+    };
+
+    use super::{oauth2, Error, IDPService};
+
+    const REDIRECT_URI: &str = "http://memos/auth/callback";
+
+    async fn create_idp(svc: &TestService) -> i32 {
+        let base = oauth2::test::mock_server().await;
+        let create = CreateIdentityProvider {
+            name: "mock".to_owned(),
+            r#type: Type::Oauth2,
+            identifier_filter: String::new(),
+            config: IdentityProviderConfig {
+                oauth2_config: Some(oauth2::test::config(&base)),
+            },
+        };
+        svc.repo.create_idp(create).await.unwrap().unwrap().id
+    }
+
+    #[tokio::test]
+    async fn provision_and_link() {
+        let svc = test::service().await;
+        let idp_id = create_idp(&svc).await;
+
+        let user = svc
+            .sign_in_with_sso(idp_id, "good", REDIRECT_URI)
+            .await
+            .unwrap();
+        assert_eq!(user.username, "alice");
+        assert_eq!(user.role, Role::User);
+        let again = svc
+            .sign_in_with_sso(idp_id, "good", REDIRECT_URI)
+            .await
+            .unwrap();
+        assert_eq!(again.id, user.id);
+    }
+
+    #[tokio::test]
+    async fn unlinked_local_user() {
+        let svc = test::service().await;
+        let idp_id = create_idp(&svc).await;
+        test::create_user(&svc, "alice", Role::Host).await;
+
+        let result = svc.sign_in_with_sso(idp_id, "good", REDIRECT_URI).await;
+        assert!(matches!(result, Err(Error::UserExists { .. })));
     }
 }
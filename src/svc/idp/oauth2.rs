@@ -0,0 +1,220 @@
+//! OAuth2 授权码模式：以 code 换取 access_token，再按 FieldMapping 映射 userinfo
+
+use serde_json::Value;
+use snafu::{OptionExt, ResultExt, Snafu};
+
+use crate::model::idp::{FieldMapping, IdentityProviderUserInfo, OAuth2Config};
+
+#[derive(Debug, serde::Deserialize)]
+struct Token {
+    access_token: Option<String>,
+}
+
+pub async fn exchange(
+    config: &OAuth2Config,
+    code: &str,
+    redirect_uri: &str,
+) -> Result<String, Error> {
+    let form = [
+        ("grant_type", "authorization_code"),
+        ("code", code),
+        ("redirect_uri", redirect_uri),
+        ("client_id", config.client_id.as_str()),
+        ("client_secret", config.client_secret.as_str()),
+    ];
+    // GitHub 等默认返回 form 编码，需显式要求 JSON
+    let token: Token = reqwest::Client::new()
+        .post(&config.token_url)
+        .header(reqwest::header::ACCEPT, "application/json")
+        .form(&form)
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .context(Request)?
+        .json()
+        .await
+        .context(Request)?;
+    token
+        .access_token
+        .filter(|t| !t.is_empty())
+        .context(MissingAccessToken)
+}
+
+pub async fn user_info(
+    config: &OAuth2Config,
+    access_token: &str,
+) -> Result<IdentityProviderUserInfo, Error> {
+    let claims: Value = reqwest::Client::new()
+        .get(&config.user_info_url)
+        .bearer_auth(access_token)
+        .header(reqwest::header::ACCEPT, "application/json")
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .context(Request)?
+        .json()
+        .await
+        .context(Request)?;
+    map_claims(&config.field_mapping, &claims)
+}
+
+/// identifier 必须存在，display_name 缺省时取 identifier
+pub fn map_claims(
+    mapping: &FieldMapping,
+    claims: &Value,
+) -> Result<IdentityProviderUserInfo, Error> {
+    let identifier = claim(claims, &mapping.identifier).context(MissingIdentifier {
+        field: &mapping.identifier,
+    })?;
+    let display_name = claim(claims, &mapping.display_name).unwrap_or_else(|| identifier.clone());
+    let email = claim(claims, &mapping.email).unwrap_or_default();
+    Ok(IdentityProviderUserInfo {
+        identifier,
+        display_name,
+        email,
+    })
+}
+
+/// 按 `a.b.c` 取嵌套字段，数字 id 转为字符串
+fn claim(claims: &Value, path: &str) -> Option<String> {
+    if path.is_empty() {
+        return None;
+    }
+    let value = path
+        .split('.')
+        .try_fold(claims, |value, key| value.get(key))?;
+    match value {
+        Value::String(s) if !s.is_empty() => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    }
+}
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("OAuth2 request failed: {source}"), context(suffix(false)))]
+    Request { source: reqwest::Error },
+
+    #[snafu(
+        display("OAuth2 token response has no access_token"),
+        context(suffix(false))
+    )]
+    MissingAccessToken,
+
+    #[snafu(
+        display("Userinfo has no identifier field: {field}"),
+        context(suffix(false))
+    )]
+    MissingIdentifier { field: String },
+}
+
+#[cfg(test)]
+pub mod test {
+    use axum::{
+        http::{HeaderMap, StatusCode},
+        routing::{get, post},
+        Form, Json, Router,
+    };
+    use serde_json::{json, Value};
+
+    use crate::model::idp::{FieldMapping, IdentityProviderUserInfo, OAuth2Config};
+
+    use super::{exchange, map_claims, user_info};
+
+    #[test]
+    fn claims() {
+        let mapping = FieldMapping {
+            identifier: "login".to_owned(),
+            display_name: "profile.name".to_owned(),
+            email: "email".to_owned(),
+        };
+        let claims = json!({"login": "alice", "profile": {"name": "Alice"}, "email": null});
+        assert_eq!(
+            map_claims(&mapping, &claims).unwrap(),
+            IdentityProviderUserInfo {
+                identifier: "alice".to_owned(),
+                display_name: "Alice".to_owned(),
+                email: String::new(),
+            }
+        );
+
+        let mapping = FieldMapping {
+            identifier: "id".to_owned(),
+            ..Default::default()
+        };
+        let info = map_claims(&mapping, &json!({"id": 42})).unwrap();
+        assert_eq!(info.identifier, "42");
+        assert_eq!(info.display_name, "42");
+        assert!(map_claims(&mapping, &json!({"id": ""})).is_err());
+    }
+
+    /// 本地模拟的 OAuth2 服务
+    pub async fn mock_server() -> String {
+        async fn token(Form(form): Form<Vec<(String, String)>>) -> (StatusCode, Json<Value>) {
+            let has = |k: &str, v: &str| form.iter().any(|(fk, fv)| fk == k && fv == v);
+            if has("grant_type", "authorization_code")
+                && has("code", "good")
+                && has("client_id", "id")
+                && has("client_secret", "secret")
+                && has("redirect_uri", "http://memos/auth/callback")
+            {
+                (StatusCode::OK, Json(json!({"access_token": "token"})))
+            } else {
+                (
+                    StatusCode::BAD_REQUEST,
+                    Json(json!({"error": "invalid_grant"})),
+                )
+            }
+        }
+
+        async fn userinfo(headers: HeaderMap) -> (StatusCode, Json<Value>) {
+            match headers.get("authorization").and_then(|h| h.to_str().ok()) {
+                Some("Bearer token") => (
+                    StatusCode::OK,
+                    Json(json!({"sub": "alice", "name": "Alice", "email": "alice@example.com"})),
+                ),
+                _ => (StatusCode::UNAUTHORIZED, Json(json!({}))),
+            }
+        }
+
+        let app = Router::new()
+            .route("/token", post(token))
+            .route("/userinfo", get(userinfo));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        format!("http://{addr}")
+    }
+
+    pub fn config(base: &str) -> OAuth2Config {
+        OAuth2Config {
+            client_id: "id".to_owned(),
+            client_secret: "secret".to_owned(),
+            token_url: format!("{base}/token"),
+            user_info_url: format!("{base}/userinfo"),
+            field_mapping: FieldMapping {
+                identifier: "sub".to_owned(),
+                display_name: "name".to_owned(),
+                email: "email".to_owned(),
+            },
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn authorization_code() {
+        let base = mock_server().await;
+        let config = config(&base);
+        let redirect_uri = "http://memos/auth/callback";
+
+        assert!(exchange(&config, "bad", redirect_uri).await.is_err());
+        let token = exchange(&config, "good", redirect_uri).await.unwrap();
+        assert_eq!(token, "token");
+
+        assert!(user_info(&config, "bad").await.is_err());
+        let info = user_info(&config, &token).await.unwrap();
+        assert_eq!(info.identifier, "alice");
+        assert_eq!(info.display_name, "Alice");
+        assert_eq!(info.email, "alice@example.com");
+    }
+}
@@ -1,8 +1,16 @@
-use crate::api::prefix::{self, ExtractName};
+use crate::api::prefix::{self, ExtractName, FormatName};
 use crate::api::v1::gen::UserStats;
 use crate::api::v1::r#gen::user_stats::MemoTypeStats;
-use crate::api::v1::r#gen::State;
-use crate::model::memo::CreateMemo;
+use crate::api::v1::r#gen::{memo_relation::Type as RelationType, Direction, State};
+use crate::dao::memo_relation::MemoRelationRepository;
+use crate::dao::memo_revision::MemoRevisionRepository;
+use crate::dao::reaction::ReactionRepository;
+use crate::model::gen::MemoPayload;
+use crate::model::memo::{
+    CreateMemo, FindMemoRelation, FindMemoRevision, Memo as MemoModel,
+    MemoRelation as MemoRelationModel, MemoRevision,
+};
+use crate::model::reaction::{FindReaction, Reaction as ReactionModel};
 use crate::model::user::User;
 use crate::{
     api::v1::gen::{
@@ -16,20 +24,25 @@ use crate::{
         UpsertMemoReactionRequest,
     },
     dao::{
-        memo::MemoRepository, resource::ResourceRepository, user::UserRepository,
-        workspace::WorkspaceRepository,
+        memo::MemoRepository, quota::QuotaRepository, resource::ResourceRepository,
+        user::UserRepository, workspace::WorkspaceRepository,
     },
     model::{
-        memo::{FindMemo, UpdateMemo},
+        memo::{FindMemo, FindMemoPayload, UpdateMemo},
         pager::Paginator,
     },
+    util::{
+        diff::{self, DiffLine},
+        md,
+    },
 };
 use async_trait::async_trait;
-use snafu::{OptionExt, ResultExt, Snafu};
+use snafu::{ensure, OptionExt, ResultExt, Snafu};
 use std::collections::HashMap;
 use std::sync::Arc;
 use tonic::{Request, Response, Status};
 
+use super::permission::{self, Action, PermissionDenied};
 use super::resource::ResourceService;
 use super::workspace::WorkspaceSettingService;
 use super::{RequestExt, Service};
@@ -41,11 +54,78 @@ pub trait MemoService: memo_service_server::MemoService + Clone + Send + Sync +
     }
 
     async fn get_user_memo_stats(&self, user: Option<&User>) -> Result<UserStats, Error>;
+    /// 仅保留当前用户两端均可读的关联
+    async fn relate_memo_relations(
+        &self,
+        user: Option<&User>,
+        memo_ids: Vec<i32>,
+    ) -> Result<HashMap<i32, Vec<MemoRelationModel>>, Error>;
+    async fn relate_reactions(
+        &self,
+        memo_names: Vec<String>,
+    ) -> Result<HashMap<String, Vec<ReactionModel>>, Error>;
+    /// 补全 memo 的资源、关联与表态
+    async fn complete_memos(
+        &self,
+        user: Option<&User>,
+        memos: Vec<MemoModel>,
+    ) -> Result<Vec<Memo>, Error>;
+    /// 用户带有该标签的 memo，memo_id 为空时不限定
+    async fn list_tag_memos(
+        &self,
+        creator_id: i32,
+        memo_id: Option<i32>,
+        tag: &str,
+    ) -> Result<Vec<MemoModel>, Error>;
+    /// 删除 memo 及其评论
+    async fn delete_memos(&self, memo_ids: Vec<i32>) -> Result<(), Error>;
+    /// 读取 memo 并校验当前用户的权限
+    async fn get_memo_with_permission(
+        &self,
+        user: Option<&User>,
+        memo_id: i32,
+        action: Action,
+    ) -> Result<MemoModel, Error>;
+    /// memo 的历史修订，最近的在前
+    async fn list_memo_revisions(
+        &self,
+        user: Option<&User>,
+        memo_id: i32,
+    ) -> Result<Vec<MemoRevision>, Error>;
+    async fn get_memo_revision(
+        &self,
+        user: Option<&User>,
+        memo_id: i32,
+        revision_id: i32,
+    ) -> Result<MemoRevision, Error>;
+    /// 对比两个修订，to 为空时与当前内容对比
+    async fn diff_memo_revisions(
+        &self,
+        user: Option<&User>,
+        memo_id: i32,
+        from: i32,
+        to: Option<i32>,
+    ) -> Result<Vec<DiffLine>, Error>;
+    /// 以修订内容覆盖 memo，当前内容会作为新修订保留
+    async fn restore_memo_revision(
+        &self,
+        user: Option<&User>,
+        memo_id: i32,
+        revision_id: i32,
+    ) -> Result<MemoModel, Error>;
 }
 
 #[async_trait]
-impl<T: MemoRepository + UserRepository + ResourceRepository + WorkspaceRepository> MemoService
-    for Service<T>
+impl<
+        T: MemoRepository
+            + MemoRelationRepository
+            + MemoRevisionRepository
+            + ReactionRepository
+            + UserRepository
+            + ResourceRepository
+            + WorkspaceRepository
+            + QuotaRepository,
+    > MemoService for Service<T>
 {
     async fn get_user_memo_stats(&self, user: Option<&User>) -> Result<UserStats, Error> {
         let creator_id = user.map(|u| u.id);
@@ -80,9 +160,12 @@ impl<T: MemoRepository + UserRepository + ResourceRepository + WorkspaceReposito
                 ..
             } = memo.into();
 
-            for tag in tags {
-                let count = tag_count.remove(&tag).unwrap_or(0);
-                tag_count.insert(tag, count + 1);
+            // 子标签计入各级父标签，同一 memo 只计一次
+            let mut paths: Vec<&str> = tags.iter().flat_map(|t| md::tag_paths(t)).collect();
+            paths.sort();
+            paths.dedup();
+            for path in paths {
+                *tag_count.entry(path.to_owned()).or_insert(0) += 1;
             }
 
             if let Some(property) = property {
@@ -122,11 +205,309 @@ impl<T: MemoRepository + UserRepository + ResourceRepository + WorkspaceReposito
             memo_display_timestamps,
         })
     }
+
+    async fn relate_memo_relations(
+        &self,
+        user: Option<&User>,
+        memo_ids: Vec<i32>,
+    ) -> Result<HashMap<i32, Vec<MemoRelationModel>>, Error> {
+        let mut rtn: HashMap<i32, Vec<MemoRelationModel>> = HashMap::new();
+        if memo_ids.is_empty() {
+            return Ok(rtn);
+        }
+
+        let relations = self
+            .repo
+            .list_memo_relations(FindMemoRelation {
+                memo_id_list: memo_ids.clone(),
+                ..Default::default()
+            })
+            .await?;
+
+        // 关联同时属于两端的 memo
+        for relation in relations {
+            if permission::check_memo_relation(user, &relation).is_err() {
+                continue;
+            }
+            if memo_ids.contains(&relation.related_memo_id) {
+                rtn.entry(relation.related_memo_id)
+                    .or_default()
+                    .push(relation.clone());
+            }
+            if memo_ids.contains(&relation.memo_id) {
+                rtn.entry(relation.memo_id).or_default().push(relation);
+            }
+        }
+        Ok(rtn)
+    }
+
+    async fn relate_reactions(
+        &self,
+        memo_names: Vec<String>,
+    ) -> Result<HashMap<String, Vec<ReactionModel>>, Error> {
+        let mut rtn: HashMap<String, Vec<ReactionModel>> = HashMap::new();
+        if memo_names.is_empty() {
+            return Ok(rtn);
+        }
+
+        let reactions = self
+            .repo
+            .list_reactions(FindReaction {
+                content_id_list: memo_names,
+                ..Default::default()
+            })
+            .await?;
+        for reaction in reactions {
+            rtn.entry(reaction.content_id.clone())
+                .or_default()
+                .push(reaction);
+        }
+        Ok(rtn)
+    }
+
+    async fn complete_memos(
+        &self,
+        user: Option<&User>,
+        memos: Vec<MemoModel>,
+    ) -> Result<Vec<Memo>, Error> {
+        let memo_ids: Vec<i32> = memos.iter().map(|m| m.id).collect();
+        let memo_names = memos.iter().map(|m| m.get_name()).collect();
+        let mut relate_resources = self.relate_resources(memo_ids.clone()).await?;
+        let mut relate_relations = self.relate_memo_relations(user, memo_ids).await?;
+        let mut relate_reactions = self.relate_reactions(memo_names).await?;
+
+        let mut memo_list = Vec::new();
+        for memo in memos {
+            let resources = relate_resources.remove(&memo.id);
+            let relations = relate_relations.remove(&memo.id);
+            let reactions = relate_reactions.remove(&memo.get_name());
+            let mut memo: Memo = memo.into();
+            if let Some(resources) = resources {
+                memo.resources = resources.into_iter().map(|r| r.into()).collect();
+            }
+            if let Some(relations) = relations {
+                memo.relations = relations.into_iter().map(|r| r.into()).collect();
+            }
+            if let Some(reactions) = reactions {
+                memo.reactions = reactions.into_iter().map(|r| r.into()).collect();
+            }
+            memo_list.push(memo);
+        }
+        Ok(memo_list)
+    }
+
+    async fn list_tag_memos(
+        &self,
+        creator_id: i32,
+        memo_id: Option<i32>,
+        tag: &str,
+    ) -> Result<Vec<MemoModel>, Error> {
+        Ok(self
+            .repo
+            .list_memos(FindMemo {
+                id: memo_id,
+                creator_id: Some(creator_id),
+                payload_find: Some(FindMemoPayload {
+                    tags: Some(vec![tag.to_owned()]),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            })
+            .await?)
+    }
+
+    async fn delete_memos(&self, mut memo_ids: Vec<i32>) -> Result<(), Error> {
+        if memo_ids.is_empty() {
+            return Ok(());
+        }
+
+        // 评论随 memo 一起删除
+        let comments = self
+            .repo
+            .list_memo_relations(FindMemoRelation {
+                memo_id_list: memo_ids.clone(),
+                r#type: Some(RelationType::Comment),
+                ..Default::default()
+            })
+            .await?;
+        for comment in comments {
+            if memo_ids.contains(&comment.related_memo_id) && !memo_ids.contains(&comment.memo_id) {
+                memo_ids.push(comment.memo_id);
+            }
+        }
+
+        self.repo.delete_memos(memo_ids).await?;
+        Ok(())
+    }
+
+    async fn get_memo_with_permission(
+        &self,
+        user: Option<&User>,
+        memo_id: i32,
+        action: Action,
+    ) -> Result<MemoModel, Error> {
+        let mut memos = self
+            .repo
+            .list_memos(FindMemo {
+                id: Some(memo_id),
+                ..Default::default()
+            })
+            .await?;
+        let memo = memos.pop().context(MemoNotFound)?;
+        permission::check_memo(user, &memo, action)?;
+        Ok(memo)
+    }
+
+    async fn list_memo_revisions(
+        &self,
+        user: Option<&User>,
+        memo_id: i32,
+    ) -> Result<Vec<MemoRevision>, Error> {
+        self.get_memo_with_permission(user, memo_id, Action::Read)
+            .await?;
+        Ok(self
+            .repo
+            .list_memo_revisions(FindMemoRevision {
+                memo_id: Some(memo_id),
+                ..Default::default()
+            })
+            .await?)
+    }
+
+    async fn get_memo_revision(
+        &self,
+        user: Option<&User>,
+        memo_id: i32,
+        revision_id: i32,
+    ) -> Result<MemoRevision, Error> {
+        self.get_memo_with_permission(user, memo_id, Action::Read)
+            .await?;
+        self.repo
+            .list_memo_revisions(FindMemoRevision {
+                id: Some(revision_id),
+                memo_id: Some(memo_id),
+            })
+            .await?
+            .pop()
+            .context(MemoRevisionNotFound)
+    }
+
+    async fn diff_memo_revisions(
+        &self,
+        user: Option<&User>,
+        memo_id: i32,
+        from: i32,
+        to: Option<i32>,
+    ) -> Result<Vec<DiffLine>, Error> {
+        let memo = self
+            .get_memo_with_permission(user, memo_id, Action::Read)
+            .await?;
+        let old = self.get_memo_revision(user, memo_id, from).await?.content;
+        let new = match to {
+            Some(to) => self.get_memo_revision(user, memo_id, to).await?.content,
+            None => memo.content,
+        };
+        Ok(diff::line_diff(&old, &new))
+    }
+
+    async fn restore_memo_revision(
+        &self,
+        user: Option<&User>,
+        memo_id: i32,
+        revision_id: i32,
+    ) -> Result<MemoModel, Error> {
+        let memo = self
+            .get_memo_with_permission(user, memo_id, Action::Write)
+            .await?;
+        let revision = self.get_memo_revision(user, memo_id, revision_id).await?;
+
+        // 重新解析标签与属性，保持 payload 与内容一致
+        let payload = md::get_memo_property(&revision.content);
+        let reference_ids = self
+            .merge_content_references(user, memo_id, &memo.payload, &payload)
+            .await?;
+        self.repo
+            .update_memo(UpdateMemo {
+                id: memo_id,
+                creator_id: memo.creator_id,
+                content: Some(revision.content),
+                payload: Some(payload),
+                ..Default::default()
+            })
+            .await?;
+        self.repo
+            .set_memo_relations(memo_id, RelationType::Reference, reference_ids)
+            .await?;
+
+        self.get_memo_with_permission(user, memo_id, Action::Read)
+            .await
+    }
+}
+
+impl<T: MemoRepository + MemoRelationRepository> Service<T> {
+    /// 过滤出当前用户可读的 memo
+    async fn readable_memo_ids(
+        &self,
+        user: Option<&User>,
+        ids: Vec<i32>,
+    ) -> Result<Vec<i32>, Error> {
+        if ids.is_empty() {
+            return Ok(ids);
+        }
+        let memos = self
+            .repo
+            .list_memos(FindMemo {
+                id_list: ids,
+                ..Default::default()
+            })
+            .await?;
+        Ok(memos
+            .into_iter()
+            .filter(|m| permission::check_memo(user, m, Action::Read).is_ok())
+            .map(|m| m.id)
+            .collect())
+    }
+
+    /// 内容变更后的引用：保留 SetMemoRelations 手动添加的引用，替换内容中的引用
+    async fn merge_content_references(
+        &self,
+        user: Option<&User>,
+        memo_id: i32,
+        old: &MemoPayload,
+        new: &MemoPayload,
+    ) -> Result<Vec<i32>, Error> {
+        let old_ids = old.reference_ids();
+        let mut reference_ids: Vec<i32> = self
+            .repo
+            .list_memo_relations(FindMemoRelation {
+                memo_id: Some(memo_id),
+                r#type: Some(RelationType::Reference),
+                ..Default::default()
+            })
+            .await?
+            .into_iter()
+            .map(|r| r.related_memo_id)
+            .filter(|id| !old_ids.contains(id))
+            .collect();
+        reference_ids.extend(self.readable_memo_ids(user, new.reference_ids()).await?);
+        reference_ids.sort();
+        reference_ids.dedup();
+        reference_ids.retain(|&id| id != memo_id);
+        Ok(reference_ids)
+    }
 }
 
 #[tonic::async_trait]
-impl<T: MemoRepository + UserRepository + ResourceRepository + WorkspaceRepository>
-    memo_service_server::MemoService for Service<T>
+impl<
+        T: MemoRepository
+            + MemoRelationRepository
+            + MemoRevisionRepository
+            + ReactionRepository
+            + UserRepository
+            + ResourceRepository
+            + WorkspaceRepository
+            + QuotaRepository,
+    > memo_service_server::MemoService for Service<T>
 {
     async fn create_memo(
         &self,
@@ -137,31 +518,35 @@ impl<T: MemoRepository + UserRepository + ResourceRepository + WorkspaceReposito
         let mut create: CreateMemo = request.get_ref().try_into().context(InvalidMemoData)?;
         create.creator_id = user.id;
 
-        let memo: Memo = self
+        let memo = self
             .repo
             .create_memo(create)
             .await?
-            .context(MaybeCreateMemo)?
-            .into();
+            .context(MaybeCreateMemo)?;
 
-        Ok(Response::new(memo))
+        let reference_ids = self
+            .readable_memo_ids(Some(user), memo.payload.reference_ids())
+            .await?;
+        if !reference_ids.is_empty() {
+            self.repo
+                .set_memo_relations(memo.id, RelationType::Reference, reference_ids)
+                .await?;
+        }
+
+        Ok(Response::new(memo.into()))
     }
 
     async fn get_memo(&self, request: Request<GetMemoRequest>) -> Result<Response<Memo>, Status> {
+        let user = request.get_current_user().ok();
         let id = request.get_ref().get_id()?;
-        let mut memos = self
-            .repo
-            .list_memos(FindMemo {
-                id: Some(id),
-                ..Default::default()
-            })
+        let memo = self
+            .get_memo_with_permission(user, id, Action::Read)
             .await?;
-
-        let mut memo: Memo = memos.pop().context(MemoNotFound)?.into();
-
-        let relate_resources = self.relate_resource(id).await?;
-        memo.resources = relate_resources.into_iter().map(|r| r.into()).collect();
-        // TODO relate/reaction
+        let memo = self
+            .complete_memos(user, vec![memo])
+            .await?
+            .pop()
+            .context(MemoNotFound)?;
 
         Ok(Response::new(memo))
     }
@@ -170,12 +555,9 @@ impl<T: MemoRepository + UserRepository + ResourceRepository + WorkspaceReposito
         &self,
         request: Request<ListMemosRequest>,
     ) -> Result<Response<ListMemosResponse>, Status> {
-        let user = request.get_current_user();
+        let user = request.get_current_user().ok();
         let mut find: FindMemo = request.get_ref().try_into().context(InvalidMemoFilter)?;
-        find.completed(
-            user.ok().map(|u| u.id),
-            self.is_display_with_update_time().await,
-        );
+        find.completed(user.map(|u| u.id), self.is_display_with_update_time().await);
         let page_token = find.page_token;
         let mut memos = self.repo.list_memos(find).await?;
 
@@ -187,18 +569,7 @@ impl<T: MemoRepository + UserRepository + ResourceRepository + WorkspaceReposito
             }
         }
 
-        let memo_ids = memos.iter().map(|m| m.id).collect();
-        let mut relate_resources = self.relate_resources(memo_ids).await?;
-        let mut memo_list = Vec::new();
-        for memo in memos {
-            let resources = relate_resources.remove(&memo.id);
-            let mut memo: Memo = memo.into();
-            if let Some(resources) = resources {
-                memo.resources = resources.into_iter().map(|r| r.into()).collect();
-            }
-            memo_list.push(memo);
-        }
-        // TODO relate/reaction
+        let memo_list = self.complete_memos(user, memos).await?;
         Ok(Response::new(ListMemosResponse {
             memos: memo_list,
             next_page_token,
@@ -212,10 +583,25 @@ impl<T: MemoRepository + UserRepository + ResourceRepository + WorkspaceReposito
     ) -> Result<Response<Memo>, Status> {
         let user = request.get_current_user()?;
         let mut update: UpdateMemo = request.get_ref().into();
-        update.creator_id = user.id;
+        let memo = self
+            .get_memo_with_permission(Some(user), update.id, Action::Write)
+            .await?;
+        update.creator_id = memo.creator_id;
         let memo_id = update.id;
+        let reference_ids = match &update.payload {
+            Some(payload) => Some(
+                self.merge_content_references(Some(user), memo_id, &memo.payload, payload)
+                    .await?,
+            ),
+            None => None,
+        };
 
         self.repo.update_memo(update).await?;
+        if let Some(reference_ids) = reference_ids {
+            self.repo
+                .set_memo_relations(memo_id, RelationType::Reference, reference_ids)
+                .await?;
+        }
 
         let mut memos = self
             .repo
@@ -225,11 +611,11 @@ impl<T: MemoRepository + UserRepository + ResourceRepository + WorkspaceReposito
             })
             .await?;
         let memo = memos.pop().context(MemoNotFound)?;
-
-        let resources = self.relate_resource(memo.id).await?;
-        let mut memo: Memo = memo.into();
-        memo.resources = resources.into_iter().map(|r| r.into()).collect();
-        // TODO relate/reaction
+        let memo = self
+            .complete_memos(Some(user), vec![memo])
+            .await?
+            .pop()
+            .context(MemoNotFound)?;
 
         Ok(Response::new(memo))
     }
@@ -239,7 +625,11 @@ impl<T: MemoRepository + UserRepository + ResourceRepository + WorkspaceReposito
         &self,
         request: Request<DeleteMemoRequest>,
     ) -> Result<Response<()>, Status> {
-        self.repo.delete_memo(request.get_ref().get_id()?).await?;
+        let user = request.get_current_user()?;
+        let id = request.get_ref().get_id()?;
+        self.get_memo_with_permission(Some(user), id, Action::Write)
+            .await?;
+        self.delete_memos(vec![id]).await?;
         Ok(Response::new(()))
     }
 
@@ -247,14 +637,66 @@ impl<T: MemoRepository + UserRepository + ResourceRepository + WorkspaceReposito
         &self,
         request: Request<RenameMemoTagRequest>,
     ) -> Result<Response<()>, Status> {
-        todo!()
+        let user = request.get_current_user()?;
+        let RenameMemoTagRequest {
+            parent,
+            old_tag,
+            new_tag,
+        } = request.get_ref();
+        ensure!(is_valid_tag(old_tag), InvalidMemoTag { tag: old_tag });
+        ensure!(is_valid_tag(new_tag), InvalidMemoTag { tag: new_tag });
+
+        let memo_id = get_tag_parent_id(parent)?;
+        let memos = self.list_tag_memos(user.id, memo_id, old_tag).await?;
+        let updates = memos
+            .into_iter()
+            .map(|memo| {
+                let content = md::rename_tag(&memo.content, old_tag, new_tag);
+                let payload = md::get_memo_property(&content);
+                UpdateMemo {
+                    id: memo.id,
+                    creator_id: user.id,
+                    content: Some(content),
+                    payload: Some(payload),
+                    ..Default::default()
+                }
+            })
+            .collect();
+
+        self.repo.update_memos(updates).await?;
+        Ok(Response::new(()))
     }
 
     async fn delete_memo_tag(
         &self,
         request: Request<DeleteMemoTagRequest>,
     ) -> Result<Response<()>, Status> {
-        todo!()
+        let user = request.get_current_user()?;
+        let DeleteMemoTagRequest {
+            parent,
+            tag,
+            delete_related_memos,
+        } = request.get_ref();
+        ensure!(is_valid_tag(tag), InvalidMemoTag { tag });
+
+        let memo_id = get_tag_parent_id(parent)?;
+        let memos = self.list_tag_memos(user.id, memo_id, tag).await?;
+        if *delete_related_memos {
+            self.delete_memos(memos.into_iter().map(|m| m.id).collect())
+                .await?;
+        } else {
+            let updates = memos
+                .into_iter()
+                .map(|memo| UpdateMemo {
+                    id: memo.id,
+                    creator_id: user.id,
+                    state: Some(State::Archived),
+                    ..Default::default()
+                })
+                .collect();
+            self.repo.update_memos(updates).await?;
+        }
+        Ok(Response::new(()))
     }
 
     /// SetMemoResources sets resources for a memo.
@@ -262,17 +704,25 @@ impl<T: MemoRepository + UserRepository + ResourceRepository + WorkspaceReposito
         &self,
         request: Request<SetMemoResourcesRequest>,
     ) -> Result<Response<()>, Status> {
+        let user = request.get_current_user()?;
         let memo_id = request.get_ref().get_id()?;
+        self.get_memo_with_permission(Some(user), memo_id, Action::Write)
+            .await?;
         let resources = &request.get_ref().resources;
         let relate_resources = self.relate_resource(memo_id).await?;
 
-        let new_res_ids = resources
+        let new_res_ids: Vec<i32> = resources
             .iter()
             .map(|s| s.get_id().unwrap_or_default())
             .collect();
-        let old_res_ids = relate_resources.iter().map(|s| s.id).collect();
+        let old_res_ids: Vec<i32> = relate_resources.iter().map(|s| s.id).collect();
+        // 只能关联自己有权修改的资源
+        for id in new_res_ids.iter().filter(|id| !old_res_ids.contains(id)) {
+            self.get_resource_with_permission(Some(user), *id, Action::Write)
+                .await?;
+        }
 
-        self.set_resources_memo(memo_id, new_res_ids, old_res_ids)
+        self.set_resources_memo(user, memo_id, new_res_ids, old_res_ids)
             .await?;
         Ok(Response::new(()))
     }
@@ -289,7 +739,33 @@ impl<T: MemoRepository + UserRepository + ResourceRepository + WorkspaceReposito
         &self,
         request: Request<SetMemoRelationsRequest>,
     ) -> Result<Response<()>, Status> {
-        // TODO
+        let user = request.get_current_user()?;
+        let memo_id = request.get_ref().get_id()?;
+        let memo = self
+            .get_memo_with_permission(Some(user), memo_id, Action::Write)
+            .await?;
+
+        // 内容中的引用始终保留，显式关联的 memo 须可读
+        let mut reference_ids = self
+            .readable_memo_ids(Some(user), memo.payload.reference_ids())
+            .await?;
+        for relation in &request.get_ref().relations {
+            if relation.r#type() != RelationType::Reference {
+                continue;
+            }
+            if let Some(related_memo_id) = relation.get_related_memo_id() {
+                self.get_memo_with_permission(Some(user), related_memo_id, Action::Read)
+                    .await?;
+                reference_ids.push(related_memo_id);
+            }
+        }
+        reference_ids.sort();
+        reference_ids.dedup();
+        reference_ids.retain(|&id| id != memo_id);
+
+        self.repo
+            .set_memo_relations(memo_id, RelationType::Reference, reference_ids)
+            .await?;
         Ok(Response::new(()))
     }
     /// ListMemoRelations lists relations for a memo.
@@ -297,45 +773,173 @@ impl<T: MemoRepository + UserRepository + ResourceRepository + WorkspaceReposito
         &self,
         request: Request<ListMemoRelationsRequest>,
     ) -> Result<Response<ListMemoRelationsResponse>, Status> {
-        Err(Status::unimplemented("unimplemented"))
+        let user = request.get_current_user().ok();
+        let memo_id = request.get_ref().get_id()?;
+        self.get_memo_with_permission(user, memo_id, Action::Read)
+            .await?;
+        let relations = self
+            .relate_memo_relations(user, vec![memo_id])
+            .await?
+            .remove(&memo_id)
+            .unwrap_or_default();
+
+        Ok(Response::new(ListMemoRelationsResponse {
+            relations: relations.into_iter().map(|r| r.into()).collect(),
+        }))
     }
     /// CreateMemoComment creates a comment for a memo.
     async fn create_memo_comment(
         &self,
         request: Request<CreateMemoCommentRequest>,
     ) -> Result<Response<Memo>, Status> {
-        Err(Status::unimplemented("unimplemented"))
+        let user = request.get_current_user()?;
+        let related_memo_id = request.get_ref().get_id()?;
+        self.get_memo_with_permission(Some(user), related_memo_id, Action::Read)
+            .await?;
+
+        let mut create: CreateMemo = request.get_ref().try_into().context(InvalidMemoData)?;
+        create.creator_id = user.id;
+        let mut memo = self
+            .repo
+            .create_memo(create)
+            .await?
+            .context(MaybeCreateMemo)?;
+
+        self.repo
+            .upsert_memo_relation(memo.id, related_memo_id, RelationType::Comment)
+            .await?;
+        memo.parent_id = Some(related_memo_id);
+
+        Ok(Response::new(memo.into()))
     }
     /// ListMemoComments lists comments for a memo.
     async fn list_memo_comments(
         &self,
         request: Request<ListMemoCommentsRequest>,
     ) -> Result<Response<ListMemoCommentsResponse>, Status> {
-        Err(Status::unimplemented("unimplemented"))
+        let user = request.get_current_user().ok();
+        let id = request.get_ref().get_id()?;
+        self.get_memo_with_permission(user, id, Action::Read)
+            .await?;
+        let relations = self
+            .repo
+            .list_memo_relations(FindMemoRelation {
+                related_memo_id: Some(id),
+                r#type: Some(RelationType::Comment),
+                ..Default::default()
+            })
+            .await?;
+        if relations.is_empty() {
+            return Ok(Response::new(ListMemoCommentsResponse::default()));
+        }
+
+        let mut memos = self
+            .repo
+            .list_memos(FindMemo {
+                id_list: relations.into_iter().map(|r| r.memo_id).collect(),
+                state: Some(State::Normal),
+                direction: Direction::Asc,
+                ..Default::default()
+            })
+            .await?;
+        memos.retain(|m| permission::check_memo(user, m, Action::Read).is_ok());
+
+        let memos = self.complete_memos(user, memos).await?;
+        Ok(Response::new(ListMemoCommentsResponse { memos }))
     }
     /// ListMemoReactions lists reactions for a memo.
     async fn list_memo_reactions(
         &self,
         request: Request<ListMemoReactionsRequest>,
     ) -> Result<Response<ListMemoReactionsResponse>, Status> {
-        Err(Status::unimplemented("unimplemented"))
+        let user = request.get_current_user().ok();
+        let memo_id = request.get_ref().get_id()?;
+        self.get_memo_with_permission(user, memo_id, Action::Read)
+            .await?;
+        let content_id = format!("{}/{}", prefix::MEMO_NAME_PREFIX, memo_id);
+        let reactions = self
+            .repo
+            .list_reactions(FindReaction {
+                content_id_list: vec![content_id],
+                ..Default::default()
+            })
+            .await?;
+
+        Ok(Response::new(ListMemoReactionsResponse {
+            reactions: reactions.into_iter().map(|r| r.into()).collect(),
+        }))
     }
     /// UpsertMemoReaction upserts a reaction for a memo.
     async fn upsert_memo_reaction(
         &self,
         request: Request<UpsertMemoReactionRequest>,
     ) -> Result<Response<Reaction>, Status> {
-        Err(Status::unimplemented("unimplemented"))
+        let user = request.get_current_user()?;
+        let memo_id = request.get_ref().get_id()?;
+        self.get_memo_with_permission(Some(user), memo_id, Action::Read)
+            .await?;
+        let reaction_type = request
+            .get_ref()
+            .reaction
+            .as_ref()
+            .map(|r| r.reaction_type.clone())
+            .unwrap_or_default();
+
+        let reactions = self.get_memo_reactions().await;
+        ensure!(
+            reactions.contains(&reaction_type),
+            InvalidReactionType { reaction_type }
+        );
+
+        let reaction = self
+            .repo
+            .upsert_reaction(ReactionModel {
+                creator_id: user.id,
+                content_id: format!("{}/{}", prefix::MEMO_NAME_PREFIX, memo_id),
+                reaction_type,
+                ..Default::default()
+            })
+            .await?
+            .context(MaybeUpsertReaction)?;
+
+        Ok(Response::new(reaction.into()))
     }
     /// DeleteMemoReaction deletes a reaction for a memo.
     async fn delete_memo_reaction(
         &self,
         request: Request<DeleteMemoReactionRequest>,
     ) -> Result<Response<()>, Status> {
-        Err(Status::unimplemented("unimplemented"))
+        let user = request.get_current_user()?;
+        let reaction = self
+            .repo
+            .list_reactions(FindReaction {
+                id: Some(request.get_ref().id),
+                ..Default::default()
+            })
+            .await?
+            .pop()
+            .context(ReactionNotFound)?;
+        permission::check_reaction(Some(user), &reaction)?;
+        self.repo
+            .delete_reaction(reaction.id, reaction.creator_id)
+            .await?;
+        Ok(Response::new(()))
+    }
+}
+
+/// memos/- 表示用户的全部 memo
+fn get_tag_parent_id(parent: &str) -> Result<Option<i32>, prefix::Error> {
+    if prefix::get_name_parent_token(parent, prefix::MEMO_NAME_PREFIX)? == "-" {
+        Ok(None)
+    } else {
+        prefix::get_id_parent_token(parent, prefix::MEMO_NAME_PREFIX).map(Some)
     }
 }
 
+fn is_valid_tag(tag: &str) -> bool {
+    !tag.is_empty() && !tag.starts_with('#') && !tag.contains(char::is_whitespace)
+}
+
 #[derive(Debug, Snafu)]
 pub enum Error {
     #[snafu(context(false))]
@@ -372,4 +976,51 @@ pub enum Error {
 
     #[snafu(display("Invalid memo filter: {source}"), context(suffix(false)))]
     InvalidMemoFilter { source: crate::api::memo::Error },
+
+    #[snafu(context(false))]
+    ListMemoRelation {
+        source: crate::dao::memo_relation::ListMemoRelationError,
+    },
+
+    #[snafu(context(false))]
+    RelateResource { source: super::resource::Error },
+
+    #[snafu(context(false))]
+    ListReaction {
+        source: crate::dao::reaction::ListReactionError,
+    },
+
+    #[snafu(
+        display("Maybe upsert reaction failed, because return none"),
+        context(suffix(false))
+    )]
+    MaybeUpsertReaction,
+
+    #[snafu(
+        display("Invalid reaction type: {reaction_type}"),
+        context(suffix(false))
+    )]
+    InvalidReactionType { reaction_type: String },
+
+    #[snafu(display("Invalid memo tag: {tag}"), context(suffix(false)))]
+    InvalidMemoTag { tag: String },
+
+    #[snafu(display("Reaction not found"), context(suffix(false)))]
+    ReactionNotFound,
+
+    #[snafu(context(false))]
+    Permission { source: PermissionDenied },
+
+    #[snafu(context(false))]
+    ListMemoRevision {
+        source: crate::dao::memo_revision::ListMemoRevisionError,
+    },
+
+    #[snafu(display("Memo revision not found"), context(suffix(false)))]
+    MemoRevisionNotFound,
+
+    #[snafu(context(false))]
+    SetMemoRelation {
+        source: crate::dao::memo_relation::SetMemoRelationError,
+    },
 }
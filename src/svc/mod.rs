@@ -1,41 +1,72 @@
+pub mod access_token;
 pub mod auth;
 pub mod idp;
 pub mod inbox;
 pub mod markdown;
 pub mod memo;
+pub mod password;
+pub mod permission;
 pub mod resource;
 pub mod session;
 pub mod user;
 pub mod webhook;
 pub mod workspace;
 
+use std::sync::Arc;
+
 use snafu::Snafu;
+use time::Duration;
 use tonic::{Code, Request, Status};
 use tracing::error;
 
 use crate::ctrl::AuthSession;
+use crate::dao::idp::{CreateIdpError, DeleteIdpError, ListIdpError, UpdateIdpError};
 use crate::dao::memo::{CreateMemoError, DeleteMemoError, ListMemoError, UpdateMemoError};
+use crate::dao::memo_relation::{
+    ListMemoRelationError, SetMemoRelationError, UpsertMemoRelationError,
+};
+use crate::dao::reaction::{DeleteReactionError, ListReactionError, UpsertReactionError};
 use crate::dao::resource::{
     CreateResourceError, DeleteResourceError, GetResourceError, ListResourceError,
-    RelateResourceError, SetResourceError,
+    RelateResourceError, SetResourceError, UpdateResourceError,
 };
 use crate::dao::user::{
-    FindUserError, FindUserSettingError, GetHostUserError, PetchUserError, UpsertUserSettingError,
+    CreateUserError, FindUserError, FindUserSettingError, GetHostUserError, ListUserError,
+    PetchUserError, UpdateUserError, UpsertUserSettingError,
 };
 use crate::dao::workspace::FindWorkspaceSettingError;
 use crate::model::user::User;
 
+use self::resource::cache::ResourceCache;
+
 #[derive(Debug, Clone)]
 pub struct EmptyService;
 
 #[derive(Debug, Clone)]
 pub struct Service<R> {
     repo: R,
+    cache: Arc<ResourceCache>,
+    /// 未关联资源与回收站资源的保留时长
+    gc_grace: Duration,
 }
 
 impl<R> Service<R> {
     pub fn new(repo: R) -> Self {
-        Self { repo }
+        Self {
+            repo,
+            cache: Arc::default(),
+            gc_grace: Duration::days(resource::DEFAULT_GC_GRACE_DAYS),
+        }
+    }
+
+    pub fn with_cache(mut self, cache: ResourceCache) -> Self {
+        self.cache = Arc::new(cache);
+        self
+    }
+
+    pub fn with_gc_grace(mut self, gc_grace: Duration) -> Self {
+        self.gc_grace = gc_grace;
+        self
     }
 }
 
@@ -75,7 +106,36 @@ impl From<user::Error> for Status {
     fn from(value: user::Error) -> Self {
         error!("{value}");
         match value {
-            user::Error::UserNotFound { .. } => Status::not_found(value.to_string()),
+            user::Error::UserNotFound { .. } | user::Error::AccessTokenNotFound => {
+                Status::not_found(value.to_string())
+            }
+            user::Error::InvalidAccessToken => Status::unauthenticated(value.to_string()),
+            user::Error::InvalidAccessTokenExpiry
+            | user::Error::InvalidUsername { .. }
+            | user::Error::InvalidUserField { .. }
+            | user::Error::EmptyPassword => Status::invalid_argument(value.to_string()),
+            user::Error::UserExists { .. } => Status::already_exists(value.to_string()),
+            user::Error::RegistrationDisabled | user::Error::PasswordAuthDisabled => {
+                Status::permission_denied(value.to_string())
+            }
+            _ => Status::internal(value.to_string()),
+        }
+    }
+}
+
+impl From<idp::Error> for Status {
+    fn from(value: idp::Error) -> Self {
+        error!("{value}");
+        match value {
+            idp::Error::IdpNotFound => Status::not_found(value.to_string()),
+            idp::Error::InvalidIdpField { .. }
+            | idp::Error::InvalidIdentifierFilter { .. }
+            | idp::Error::InvalidUsername { .. } => Status::invalid_argument(value.to_string()),
+            idp::Error::OAuth2 { .. } => Status::unauthenticated(value.to_string()),
+            idp::Error::IdentifierNotAllowed { .. }
+            | idp::Error::RegistrationDisabled
+            | idp::Error::UserArchived => Status::permission_denied(value.to_string()),
+            idp::Error::UserExists { .. } => Status::already_exists(value.to_string()),
             _ => Status::internal(value.to_string()),
         }
     }
@@ -85,7 +145,13 @@ impl From<memo::Error> for Status {
     fn from(value: memo::Error) -> Self {
         error!("{value}");
         match value {
-            memo::Error::InvalidMemoFilter { .. } => Status::invalid_argument(value.to_string()),
+            memo::Error::InvalidMemoFilter { .. }
+            | memo::Error::InvalidReactionType { .. }
+            | memo::Error::InvalidMemoTag { .. } => Status::invalid_argument(value.to_string()),
+            memo::Error::MemoNotFound
+            | memo::Error::ReactionNotFound
+            | memo::Error::MemoRevisionNotFound => Status::not_found(value.to_string()),
+            memo::Error::Permission { .. } => Status::permission_denied(value.to_string()),
             _ => Status::internal(value.to_string()),
         }
     }
@@ -95,7 +161,15 @@ impl From<resource::Error> for Status {
     fn from(value: resource::Error) -> Self {
         error!("{value}");
         match value {
-            resource::Error::ResourceNotFound { .. } => Status::not_found(value.to_string()),
+            resource::Error::ResourceNotFound | resource::Error::MemoNotFound => {
+                Status::not_found(value.to_string())
+            }
+            resource::Error::InvalidResourceMemo { .. }
+            | resource::Error::InvalidResourceFilename
+            | resource::Error::InvalidQuota
+            | resource::Error::FileSizeLimit { .. } => Status::invalid_argument(value.to_string()),
+            resource::Error::QuotaExceeded => Status::resource_exhausted(value.to_string()),
+            resource::Error::Permission { .. } => Status::permission_denied(value.to_string()),
             _ => Status::internal(value.to_string()),
         }
     }
@@ -103,20 +177,107 @@ impl From<resource::Error> for Status {
 
 into_status!(crate::api::prefix::Error, Code::InvalidArgument);
 into_status!(CurrentUserError, Code::Unauthenticated);
+into_status!(permission::PermissionDenied, Code::PermissionDenied);
 into_status!(auth::Error, Code::Internal);
 into_status!(CreateMemoError, Code::Internal);
 into_status!(DeleteMemoError, Code::Internal);
 into_status!(ListMemoError, Code::Internal);
 into_status!(UpdateMemoError, Code::Internal);
+into_status!(UpsertMemoRelationError, Code::Internal);
+into_status!(SetMemoRelationError, Code::Internal);
+into_status!(ListMemoRelationError, Code::Internal);
+into_status!(UpsertReactionError, Code::Internal);
+into_status!(ListReactionError, Code::Internal);
+into_status!(DeleteReactionError, Code::Internal);
 into_status!(CreateResourceError, Code::Internal);
 into_status!(DeleteResourceError, Code::Internal);
 into_status!(GetResourceError, Code::Internal);
 into_status!(ListResourceError, Code::Internal);
 into_status!(RelateResourceError, Code::Internal);
 into_status!(SetResourceError, Code::Internal);
+into_status!(UpdateResourceError, Code::Internal);
 into_status!(FindUserError, Code::Internal);
 into_status!(FindUserSettingError, Code::Internal);
 into_status!(GetHostUserError, Code::Internal);
 into_status!(PetchUserError, Code::Internal);
+into_status!(ListUserError, Code::Internal);
+into_status!(CreateUserError, Code::Internal);
+into_status!(UpdateUserError, Code::Internal);
 into_status!(UpsertUserSettingError, Code::Internal);
 into_status!(FindWorkspaceSettingError, Code::Internal);
+into_status!(ListIdpError, Code::Internal);
+into_status!(CreateIdpError, Code::Internal);
+into_status!(UpdateIdpError, Code::Internal);
+into_status!(DeleteIdpError, Code::Internal);
+
+/// 服务层测试：临时数据库与带登录用户的请求
+#[cfg(test)]
+pub mod test {
+    use std::{convert::Infallible, future::poll_fn, sync::Arc};
+
+    use axum_login::{
+        tower_sessions::{MemoryStore, SessionManagerLayer},
+        AuthManagerLayerBuilder,
+    };
+    use hyper::{Request as HttpRequest, Response as HttpResponse};
+    use tonic::Request;
+    use tower::{Layer, Service as _};
+
+    use crate::{
+        api::v1::gen::user::Role,
+        ctrl::{auth::Backend, AuthSession},
+        dao::{turso::Turso, user::UserRepository},
+        model::user::{CreateUser, User},
+        util,
+    };
+
+    use super::Service;
+
+    pub type TestService = Arc<Service<Turso>>;
+
+    /// 每次调用都使用新的数据库文件，按最新表结构初始化
+    pub async fn service() -> TestService {
+        let path = std::env::temp_dir().join(format!("memos-test-{}.db", util::uuid()));
+        let db = libsql::Builder::new_local(path).build().await.unwrap();
+        let repo = Turso::new(db);
+        repo.execute_batch(include_str!("../../migration/LATEST__SCHEMA.sql"))
+            .await
+            .unwrap();
+        Arc::new(Service::new(repo))
+    }
+
+    pub async fn create_user(svc: &TestService, username: &str, role: Role) -> User {
+        svc.repo
+            .create_user(CreateUser {
+                username: username.to_owned(),
+                role,
+                password_hash: String::new(),
+                ..Default::default()
+            })
+            .await
+            .unwrap()
+            .unwrap()
+    }
+
+    /// 经由认证中间件取得会话，再替换为指定用户
+    pub async fn request<T>(svc: &TestService, message: T, user: Option<&User>) -> Request<T> {
+        let session_layer = SessionManagerLayer::new(MemoryStore::default());
+        let auth_layer =
+            AuthManagerLayerBuilder::new(Backend::new(svc.clone()), session_layer).build();
+        let mut http = auth_layer.layer(tower::service_fn(|req: HttpRequest<()>| async move {
+            let mut res = HttpResponse::new(());
+            if let Some(auth_session) = req.extensions().get::<AuthSession>() {
+                res.extensions_mut().insert(auth_session.clone());
+            }
+            Ok::<_, Infallible>(res)
+        }));
+        poll_fn(|cx| http.poll_ready(cx)).await.unwrap();
+        let res = http.call(HttpRequest::new(())).await.unwrap();
+        let mut auth_session = res.extensions().get::<AuthSession>().cloned().unwrap();
+        auth_session.user = user.cloned();
+
+        let mut request = Request::new(message);
+        request.extensions_mut().insert(auth_session);
+        request
+    }
+}
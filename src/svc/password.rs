@@ -0,0 +1,96 @@
+//! 密码摘要：新密码使用 Argon2id，兼容上游 Go 版本的 bcrypt 与旧版无盐 SM3
+
+use argon2::{
+    password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+    Argon2,
+};
+use sm3::{Digest, Sm3};
+
+/// 与 hash 相同参数的固定摘要，用户不存在时用于校验以保持耗时一致
+const DUMMY_HASH: &str =
+    "$argon2id$v=19$m=19456,t=2,p=1$aW9OcNtqynSVL0fpW6R1hg$s3omTnL5y4py969s+uSiWRNV7ZccUNNCdmNEMAoKtdg";
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Verified {
+    Invalid,
+    Valid,
+    /// 旧版摘要，需在登录成功后重新计算
+    Rehash,
+}
+
+pub fn hash(password: &str) -> Result<String, argon2::password_hash::Error> {
+    let salt = SaltString::generate(&mut OsRng);
+    let hash = Argon2::default().hash_password(password.as_bytes(), &salt)?;
+    Ok(hash.to_string())
+}
+
+pub fn verify(password: &str, password_hash: &str) -> Verified {
+    let valid = if password_hash.starts_with("$argon2") {
+        PasswordHash::new(password_hash).is_ok_and(|hash| {
+            Argon2::default()
+                .verify_password(password.as_bytes(), &hash)
+                .is_ok()
+        })
+    } else if password_hash.starts_with("$2") {
+        // 上游 Go 版本的摘要保持原样，数据库仍可回退使用
+        bcrypt::verify(password, password_hash).unwrap_or(false)
+    } else {
+        let mut hasher = Sm3::new();
+        hasher.update(password);
+        let legacy = hex::encode(hasher.finalize());
+        if constant_time_eq(legacy.as_bytes(), password_hash.as_bytes()) {
+            return Verified::Rehash;
+        }
+        false
+    };
+    if valid {
+        Verified::Valid
+    } else {
+        Verified::Invalid
+    }
+}
+
+/// 不对应任何用户的校验，结果始终无效
+pub fn verify_dummy(password: &str) {
+    let _ = verify(password, DUMMY_HASH);
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod test {
+    use sm3::{Digest, Sm3};
+
+    use argon2::PasswordHash;
+
+    use super::{hash, verify, Verified, DUMMY_HASH};
+
+    #[test]
+    fn verify_hashes() {
+        let argon2 = hash("secret").unwrap();
+        assert!(argon2.starts_with("$argon2id$"));
+        assert_ne!(argon2, hash("secret").unwrap());
+        assert_eq!(verify("secret", &argon2), Verified::Valid);
+        assert_eq!(verify("wrong", &argon2), Verified::Invalid);
+
+        let bcrypt = bcrypt::hash("secret", 4).unwrap();
+        assert_eq!(verify("secret", &bcrypt), Verified::Valid);
+        assert_eq!(verify("wrong", &bcrypt), Verified::Invalid);
+
+        let mut hasher = Sm3::new();
+        hasher.update("secret");
+        let sm3 = hex::encode(hasher.finalize());
+        assert_eq!(verify("secret", &sm3), Verified::Rehash);
+        assert_eq!(verify("wrong", &sm3), Verified::Invalid);
+        assert_eq!(verify("secret", ""), Verified::Invalid);
+    }
+
+    #[test]
+    fn dummy_hash() {
+        let params = PasswordHash::new(DUMMY_HASH).unwrap().params;
+        let default = PasswordHash::new(&hash("secret").unwrap()).unwrap().params;
+        assert_eq!(params, default);
+    }
+}
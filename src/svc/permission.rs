@@ -0,0 +1,852 @@
+//! 访问控制：memo 可见性与用户角色
+
+use snafu::{ensure, Snafu};
+
+use crate::{
+    api::v1::gen::{user::Role, Visibility},
+    model::{
+        memo::{Memo as MemoModel, MemoRelation as MemoRelationModel},
+        reaction::Reaction as ReactionModel,
+        resource::Resource as ResourceModel,
+        user::User,
+    },
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Read,
+    Write,
+}
+
+#[derive(Debug, Snafu)]
+#[snafu(display("Permission denied"), visibility(pub))]
+pub struct PermissionDenied;
+
+/// HOST 与 ADMIN 可管理他人的数据
+pub fn is_superuser(user: &User) -> bool {
+    matches!(user.role, Role::Host | Role::Admin)
+}
+
+/// HOST > ADMIN > USER，管理操作只能作用于角色更低的用户
+fn rank(role: Role) -> u8 {
+    match role {
+        Role::Host => 3,
+        Role::Admin => 2,
+        Role::User => 1,
+        Role::Unspecified => 0,
+    }
+}
+
+fn outranks(user: &User, role: Role) -> bool {
+    is_superuser(user) && rank(user.role) > rank(role)
+}
+
+fn is_owner_or_superuser(user: Option<&User>, creator_id: i32) -> bool {
+    user.is_some_and(|u| u.id == creator_id || is_superuser(u))
+}
+
+/// PUBLIC 所有人可读，PROTECTED 登录可读，PRIVATE 仅创建者可读；
+/// 修改须先可读，且仅限创建者与角色高于创建者的管理员
+pub fn check_memo(
+    user: Option<&User>,
+    memo: &MemoModel,
+    action: Action,
+) -> Result<(), PermissionDenied> {
+    let allowed = match action {
+        Action::Read => can_read_memo(user, memo.visibility, memo.creator_id),
+        Action::Write => {
+            can_read_memo(user, memo.visibility, memo.creator_id)
+                && user.is_some_and(|u| u.id == memo.creator_id || outranks(u, memo.creator_role))
+        }
+    };
+    ensure!(allowed, PermissionDeniedSnafu);
+    Ok(())
+}
+
+fn can_read_memo(user: Option<&User>, visibility: Visibility, creator_id: i32) -> bool {
+    match visibility {
+        Visibility::Public => true,
+        Visibility::Protected => user.is_some(),
+        _ => user.is_some_and(|u| u.id == creator_id),
+    }
+}
+
+/// 关联带有两端 memo 的摘要，两端均可读时才可见
+pub fn check_memo_relation(
+    user: Option<&User>,
+    relation: &MemoRelationModel,
+) -> Result<(), PermissionDenied> {
+    ensure!(
+        can_read_memo(user, relation.memo_visibility, relation.memo_creator_id)
+            && can_read_memo(
+                user,
+                relation.related_memo_visibility,
+                relation.related_memo_creator_id
+            ),
+        PermissionDeniedSnafu
+    );
+    Ok(())
+}
+
+/// 已关联 memo 的资源随 memo 可读，其余仅创建者与管理员可读
+pub fn check_resource(
+    user: Option<&User>,
+    resource: &ResourceModel,
+    memo: Option<&MemoModel>,
+    action: Action,
+) -> Result<(), PermissionDenied> {
+    if action == Action::Read {
+        if let Some(memo) = memo {
+            return check_memo(user, memo, action);
+        }
+    }
+    ensure!(
+        is_owner_or_superuser(user, resource.creator_id),
+        PermissionDeniedSnafu
+    );
+    Ok(())
+}
+
+/// 表态的删除仅限创建者与管理员
+pub fn check_reaction(
+    user: Option<&User>,
+    reaction: &ReactionModel,
+) -> Result<(), PermissionDenied> {
+    ensure!(
+        is_owner_or_superuser(user, reaction.creator_id),
+        PermissionDeniedSnafu
+    );
+    Ok(())
+}
+
+/// 存储用量本人与管理员可查看，配额仅管理员可修改
+pub fn check_user_storage(
+    user: &User,
+    user_id: i32,
+    action: Action,
+) -> Result<(), PermissionDenied> {
+    let allowed = match action {
+        Action::Read => is_owner_or_superuser(Some(user), user_id),
+        Action::Write => is_superuser(user),
+    };
+    ensure!(allowed, PermissionDeniedSnafu);
+    Ok(())
+}
+
+/// 工作区设置仅管理员可查看与修改
+pub fn check_workspace_setting(user: &User) -> Result<(), PermissionDenied> {
+    ensure!(is_superuser(user), PermissionDeniedSnafu);
+    Ok(())
+}
+
+/// 资料本人可改，管理员可改角色更低的用户
+pub fn check_user_profile(user: &User, target: &User) -> Result<(), PermissionDenied> {
+    ensure!(
+        user.id == target.id || outranks(user, target.role),
+        PermissionDeniedSnafu
+    );
+    Ok(())
+}
+
+/// 创建用户、修改角色与归档仅限管理员对角色更低的用户，且授予的角色须低于自己
+pub fn check_user_admin(
+    user: &User,
+    target: Option<&User>,
+    role: Option<Role>,
+) -> Result<(), PermissionDenied> {
+    ensure!(
+        target.is_none_or(|t| t.id != user.id && outranks(user, t.role))
+            && role.is_none_or(|r| outranks(user, r))
+            && is_superuser(user),
+        PermissionDeniedSnafu
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        check_memo, check_memo_relation, check_reaction, check_resource, check_user_admin,
+        check_user_profile, check_user_storage, Action,
+    };
+    use crate::{
+        api::v1::gen::{user::Role, Visibility},
+        model::{
+            memo::{Memo, MemoRelation},
+            reaction::Reaction,
+            resource::Resource as ResourceModel,
+            user::User,
+        },
+    };
+
+    fn user(id: i32, role: Role) -> User {
+        User {
+            id,
+            role,
+            ..Default::default()
+        }
+    }
+
+    fn memo(creator_id: i32, visibility: Visibility) -> Memo {
+        Memo {
+            id: 1,
+            creator_id,
+            creator_role: Role::User,
+            visibility,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn get_memo() {
+        let owner = user(1, Role::User);
+        let other = user(2, Role::User);
+        let admin = user(3, Role::Admin);
+
+        let public = memo(1, Visibility::Public);
+        assert!(check_memo(None, &public, Action::Read).is_ok());
+
+        let protected = memo(1, Visibility::Protected);
+        assert!(check_memo(None, &protected, Action::Read).is_err());
+        assert!(check_memo(Some(&other), &protected, Action::Read).is_ok());
+
+        let private = memo(1, Visibility::Private);
+        assert!(check_memo(Some(&owner), &private, Action::Read).is_ok());
+        assert!(check_memo(Some(&other), &private, Action::Read).is_err());
+        assert!(check_memo(Some(&admin), &private, Action::Read).is_err());
+    }
+
+    #[test]
+    fn update_and_delete_memo() {
+        let public = memo(1, Visibility::Public);
+        assert!(check_memo(Some(&user(1, Role::User)), &public, Action::Write).is_ok());
+        assert!(check_memo(Some(&user(2, Role::User)), &public, Action::Write).is_err());
+        assert!(check_memo(Some(&user(3, Role::Admin)), &public, Action::Write).is_ok());
+        assert!(check_memo(Some(&user(4, Role::Host)), &public, Action::Write).is_ok());
+        assert!(check_memo(None, &public, Action::Write).is_err());
+
+        // 管理员不能修改 HOST 的 memo
+        let host_memo = Memo {
+            creator_role: Role::Host,
+            ..memo(4, Visibility::Public)
+        };
+        assert!(check_memo(Some(&user(3, Role::Admin)), &host_memo, Action::Write).is_err());
+        assert!(check_memo(Some(&user(4, Role::Host)), &host_memo, Action::Write).is_ok());
+
+        // 不可读的 memo 也不可修改
+        let private = memo(1, Visibility::Private);
+        assert!(check_memo(Some(&user(3, Role::Admin)), &private, Action::Write).is_err());
+        assert!(check_memo(Some(&user(4, Role::Host)), &private, Action::Write).is_err());
+        assert!(check_memo(Some(&user(1, Role::User)), &private, Action::Write).is_ok());
+    }
+
+    #[test]
+    fn memo_comments_relations_reactions() {
+        // 评论、关联与表态均要求可读 memo
+        let private = memo(1, Visibility::Private);
+        assert!(check_memo(Some(&user(2, Role::User)), &private, Action::Read).is_err());
+
+        let reaction = Reaction {
+            id: 1,
+            creator_id: 2,
+            ..Default::default()
+        };
+        assert!(check_reaction(Some(&user(2, Role::User)), &reaction).is_ok());
+        assert!(check_reaction(Some(&user(1, Role::User)), &reaction).is_err());
+        assert!(check_reaction(Some(&user(3, Role::Admin)), &reaction).is_ok());
+    }
+
+    #[test]
+    fn memo_relation_snippets() {
+        // 引用他人的私有 memo 不应泄露摘要
+        let relation = MemoRelation {
+            memo_id: 1,
+            memo_creator_id: 1,
+            memo_visibility: Visibility::Public,
+            related_memo_id: 2,
+            related_memo_creator_id: 2,
+            related_memo_visibility: Visibility::Private,
+            ..Default::default()
+        };
+        assert!(check_memo_relation(Some(&user(2, Role::User)), &relation).is_ok());
+        assert!(check_memo_relation(Some(&user(1, Role::User)), &relation).is_err());
+        assert!(check_memo_relation(None, &relation).is_err());
+    }
+
+    #[test]
+    fn get_and_delete_resource() {
+        let resource = ResourceModel {
+            id: 1,
+            creator_id: 1,
+            ..Default::default()
+        };
+        let other = user(2, Role::User);
+        assert!(check_resource(Some(&other), &resource, None, Action::Read).is_err());
+        assert!(check_resource(None, &resource, None, Action::Read).is_err());
+
+        let public = memo(1, Visibility::Public);
+        assert!(check_resource(None, &resource, Some(&public), Action::Read).is_ok());
+        assert!(check_resource(Some(&other), &resource, Some(&public), Action::Write).is_err());
+        assert!(check_resource(Some(&user(3, Role::Host)), &resource, None, Action::Write).is_ok());
+    }
+
+    #[test]
+    fn user_storage() {
+        let owner = user(1, Role::User);
+        assert!(check_user_storage(&owner, 1, Action::Read).is_ok());
+        assert!(check_user_storage(&owner, 1, Action::Write).is_err());
+        assert!(check_user_storage(&owner, 2, Action::Read).is_err());
+        assert!(check_user_storage(&user(3, Role::Admin), 1, Action::Write).is_ok());
+    }
+
+    #[test]
+    fn manage_users() {
+        let host = user(1, Role::Host);
+        let admin = user(2, Role::Admin);
+        let member = user(3, Role::User);
+        let other = user(4, Role::User);
+
+        assert!(check_user_profile(&member, &member).is_ok());
+        assert!(check_user_profile(&member, &other).is_err());
+        assert!(check_user_profile(&admin, &member).is_ok());
+        assert!(check_user_profile(&admin, &host).is_err());
+
+        assert!(check_user_admin(&admin, None, Some(Role::User)).is_ok());
+        assert!(check_user_admin(&admin, None, Some(Role::Admin)).is_err());
+        assert!(check_user_admin(&host, Some(&admin), Some(Role::User)).is_ok());
+        assert!(check_user_admin(&host, Some(&member), Some(Role::Host)).is_err());
+        assert!(check_user_admin(&admin, Some(&admin), None).is_err());
+        assert!(check_user_admin(&member, Some(&other), None).is_err());
+    }
+
+    mod rpc {
+        use prost_types::FieldMask;
+        use tonic::{Code, Response, Status};
+
+        use crate::{
+            api::v1::gen::{
+                memo_relation, memo_service_server::MemoService,
+                resource_service_server::ResourceService, user::Role, CreateMemoCommentRequest,
+                CreateMemoRequest, CreateResourceRequest, DeleteMemoReactionRequest,
+                DeleteMemoRequest, DeleteMemoTagRequest, DeleteResourceRequest, GetMemoRequest,
+                GetResourceBinaryRequest, GetResourceRequest, ListMemoCommentsRequest,
+                ListMemoRelationsRequest, ListMemosRequest, Memo, MemoRelation, Reaction,
+                RenameMemoTagRequest, Resource, SetMemoRelationsRequest, SetMemoResourcesRequest,
+                UpdateMemoRequest, UpdateResourceRequest, UpsertMemoReactionRequest, Visibility,
+            },
+            model::user::User,
+            svc::test::{self, TestService},
+        };
+
+        fn code<T>(result: Result<Response<T>, Status>) -> Code {
+            result.map(|_| Code::Ok).unwrap_or_else(|s| s.code())
+        }
+
+        async fn create_memo(svc: &TestService, user: &User, visibility: Visibility) -> String {
+            create_memo_with(svc, user, visibility, "memo").await
+        }
+
+        async fn create_memo_with(
+            svc: &TestService,
+            user: &User,
+            visibility: Visibility,
+            content: &str,
+        ) -> String {
+            let memo = Memo {
+                content: content.to_owned(),
+                visibility: visibility.into(),
+                ..Default::default()
+            };
+            let request = CreateMemoRequest { memo: Some(memo) };
+            svc.create_memo(test::request(svc, request, Some(user)).await)
+                .await
+                .unwrap()
+                .into_inner()
+                .name
+        }
+
+        async fn memo_content(svc: &TestService, name: &str, user: &User) -> Option<String> {
+            let request = GetMemoRequest {
+                name: name.to_owned(),
+            };
+            svc.get_memo(test::request(svc, request, Some(user)).await)
+                .await
+                .ok()
+                .map(|m| m.into_inner().content)
+        }
+
+        async fn create_text_resource(svc: &TestService, user: &User) -> Resource {
+            let request = CreateResourceRequest {
+                resource: Some(Resource {
+                    filename: "a.txt".to_owned(),
+                    r#type: "text/plain".to_owned(),
+                    content: b"hello".to_vec(),
+                    ..Default::default()
+                }),
+            };
+            svc.create_resource(test::request(svc, request, Some(user)).await)
+                .await
+                .unwrap()
+                .into_inner()
+        }
+
+        async fn get_memo(svc: &TestService, name: &str, user: Option<&User>) -> Code {
+            let request = GetMemoRequest {
+                name: name.to_owned(),
+            };
+            code(svc.get_memo(test::request(svc, request, user).await).await)
+        }
+
+        #[tokio::test]
+        async fn get_update_delete_memo() {
+            let svc = test::service().await;
+            let owner = test::create_user(&svc, "owner", Role::User).await;
+            let other = test::create_user(&svc, "other", Role::User).await;
+            let private = create_memo(&svc, &owner, Visibility::Private).await;
+            let protected = create_memo(&svc, &owner, Visibility::Protected).await;
+
+            assert_eq!(Code::Ok, get_memo(&svc, &private, Some(&owner)).await);
+            assert_eq!(
+                Code::PermissionDenied,
+                get_memo(&svc, &private, Some(&other)).await
+            );
+            assert_eq!(Code::PermissionDenied, get_memo(&svc, &private, None).await);
+            assert_eq!(Code::Ok, get_memo(&svc, &protected, Some(&other)).await);
+            assert_eq!(
+                Code::PermissionDenied,
+                get_memo(&svc, &protected, None).await
+            );
+
+            for (user, expected) in [(&other, Code::PermissionDenied), (&owner, Code::Ok)] {
+                let request = UpdateMemoRequest {
+                    memo: Some(Memo {
+                        name: protected.clone(),
+                        content: "edited".to_owned(),
+                        ..Default::default()
+                    }),
+                    update_mask: Some(FieldMask {
+                        paths: vec!["content".to_owned()],
+                    }),
+                };
+                let result = svc
+                    .update_memo(test::request(&svc, request, Some(user)).await)
+                    .await;
+                assert_eq!(expected, code(result));
+            }
+
+            for (user, expected) in [(&other, Code::PermissionDenied), (&owner, Code::Ok)] {
+                let request = DeleteMemoRequest {
+                    name: protected.clone(),
+                };
+                let result = svc
+                    .delete_memo(test::request(&svc, request, Some(user)).await)
+                    .await;
+                assert_eq!(expected, code(result));
+            }
+            assert_eq!(
+                Code::NotFound,
+                get_memo(&svc, &protected, Some(&owner)).await
+            );
+        }
+
+        #[tokio::test]
+        async fn admin_memo_write() {
+            let svc = test::service().await;
+            let host = test::create_user(&svc, "host", Role::Host).await;
+            let admin = test::create_user(&svc, "admin", Role::Admin).await;
+            let owner = test::create_user(&svc, "owner", Role::User).await;
+            let host_memo = create_memo(&svc, &host, Visibility::Public).await;
+            let private = create_memo(&svc, &owner, Visibility::Private).await;
+            let protected = create_memo(&svc, &owner, Visibility::Protected).await;
+
+            for (name, expected) in [
+                (&host_memo, Code::PermissionDenied),
+                (&private, Code::PermissionDenied),
+                (&protected, Code::Ok),
+            ] {
+                let request = UpdateMemoRequest {
+                    memo: Some(Memo {
+                        name: name.clone(),
+                        content: "edited".to_owned(),
+                        ..Default::default()
+                    }),
+                    update_mask: Some(FieldMask {
+                        paths: vec!["content".to_owned()],
+                    }),
+                };
+                let result = svc
+                    .update_memo(test::request(&svc, request, Some(&admin)).await)
+                    .await;
+                assert_eq!(expected, code(result));
+            }
+
+            for (name, expected) in [
+                (&host_memo, Code::PermissionDenied),
+                (&private, Code::PermissionDenied),
+            ] {
+                let request = DeleteMemoRequest { name: name.clone() };
+                let result = svc
+                    .delete_memo(test::request(&svc, request, Some(&admin)).await)
+                    .await;
+                assert_eq!(expected, code(result));
+            }
+        }
+
+        #[tokio::test]
+        async fn memo_resources() {
+            let svc = test::service().await;
+            let owner = test::create_user(&svc, "owner", Role::User).await;
+            let other = test::create_user(&svc, "other", Role::User).await;
+            let private = create_memo(&svc, &owner, Visibility::Private).await;
+
+            let request = CreateResourceRequest {
+                resource: Some(Resource {
+                    filename: "a.txt".to_owned(),
+                    r#type: "text/plain".to_owned(),
+                    content: b"hello".to_vec(),
+                    ..Default::default()
+                }),
+            };
+            let resource = svc
+                .create_resource(test::request(&svc, request, Some(&owner)).await)
+                .await
+                .unwrap()
+                .into_inner();
+
+            // 他人不能把资源挂到自己无权修改的 memo 上
+            for (user, expected) in [(&other, Code::PermissionDenied), (&owner, Code::Ok)] {
+                let request = SetMemoResourcesRequest {
+                    name: private.clone(),
+                    resources: vec![resource.clone()],
+                };
+                let result = svc
+                    .set_memo_resources(test::request(&svc, request, Some(user)).await)
+                    .await;
+                assert_eq!(expected, code(result));
+            }
+
+            // 资源随私有 memo 不可读
+            for (user, expected) in [
+                (Some(&other), Code::PermissionDenied),
+                (None, Code::PermissionDenied),
+                (Some(&owner), Code::Ok),
+            ] {
+                let request = GetResourceRequest {
+                    name: resource.name.clone(),
+                };
+                let result = svc
+                    .get_resource(test::request(&svc, request, user).await)
+                    .await;
+                assert_eq!(expected, code(result));
+            }
+
+            for (user, expected) in [(&other, Code::PermissionDenied), (&owner, Code::Ok)] {
+                let request = DeleteResourceRequest {
+                    name: resource.name.clone(),
+                };
+                let result = svc
+                    .delete_resource(test::request(&svc, request, Some(user)).await)
+                    .await;
+                assert_eq!(expected, code(result));
+            }
+        }
+
+        #[tokio::test]
+        async fn create_resource_on_memo() {
+            let svc = test::service().await;
+            let owner = test::create_user(&svc, "owner", Role::User).await;
+            let other = test::create_user(&svc, "other", Role::User).await;
+            let private = create_memo(&svc, &owner, Visibility::Private).await;
+
+            // 创建时指定的 memo 同样须有权修改
+            for (user, expected) in [(&other, Code::PermissionDenied), (&owner, Code::Ok)] {
+                let request = CreateResourceRequest {
+                    resource: Some(Resource {
+                        filename: "a.txt".to_owned(),
+                        r#type: "text/plain".to_owned(),
+                        content: b"hello".to_vec(),
+                        memo: Some(private.clone()),
+                        ..Default::default()
+                    }),
+                };
+                let result = svc
+                    .create_resource(test::request(&svc, request, Some(user)).await)
+                    .await;
+                assert_eq!(expected, code(result));
+            }
+        }
+
+        #[tokio::test]
+        async fn memo_reactions() {
+            let svc = test::service().await;
+            let owner = test::create_user(&svc, "owner", Role::User).await;
+            let other = test::create_user(&svc, "other", Role::User).await;
+            let private = create_memo(&svc, &owner, Visibility::Private).await;
+            let protected = create_memo(&svc, &owner, Visibility::Protected).await;
+
+            let upsert = |name: &str| UpsertMemoReactionRequest {
+                name: name.to_owned(),
+                reaction: Some(Reaction {
+                    reaction_type: "👍".to_owned(),
+                    ..Default::default()
+                }),
+            };
+            let result = svc
+                .upsert_memo_reaction(test::request(&svc, upsert(&private), Some(&other)).await)
+                .await;
+            assert_eq!(Code::PermissionDenied, code(result));
+
+            let reaction = svc
+                .upsert_memo_reaction(test::request(&svc, upsert(&protected), Some(&other)).await)
+                .await
+                .unwrap()
+                .into_inner();
+
+            // 表态只能由创建者删除，memo 作者也不行
+            for (user, expected) in [(&owner, Code::PermissionDenied), (&other, Code::Ok)] {
+                let request = DeleteMemoReactionRequest { id: reaction.id };
+                let result = svc
+                    .delete_memo_reaction(test::request(&svc, request, Some(user)).await)
+                    .await;
+                assert_eq!(expected, code(result));
+            }
+        }
+
+        #[tokio::test]
+        async fn list_memos_visibility() {
+            let svc = test::service().await;
+            let owner = test::create_user(&svc, "owner", Role::User).await;
+            let other = test::create_user(&svc, "other", Role::User).await;
+            for visibility in [
+                Visibility::Public,
+                Visibility::Protected,
+                Visibility::Private,
+            ] {
+                create_memo(&svc, &owner, visibility).await;
+            }
+
+            for (user, expected) in [(None, 1), (Some(&other), 2), (Some(&owner), 3)] {
+                let request = ListMemosRequest {
+                    old_filter: format!(r#"creator == "users/{}""#, owner.id),
+                    page_size: 10,
+                    ..Default::default()
+                };
+                let memos = svc
+                    .list_memos(test::request(&svc, request, user).await)
+                    .await
+                    .unwrap()
+                    .into_inner()
+                    .memos;
+                assert_eq!(expected, memos.len());
+            }
+        }
+
+        #[tokio::test]
+        async fn memo_comments() {
+            let svc = test::service().await;
+            let owner = test::create_user(&svc, "owner", Role::User).await;
+            let other = test::create_user(&svc, "other", Role::User).await;
+            let private = create_memo(&svc, &owner, Visibility::Private).await;
+            let protected = create_memo(&svc, &owner, Visibility::Protected).await;
+
+            let comment = |name: &str, visibility: Visibility| CreateMemoCommentRequest {
+                name: name.to_owned(),
+                comment: Some(Memo {
+                    content: "comment".to_owned(),
+                    visibility: visibility.into(),
+                    ..Default::default()
+                }),
+            };
+            for (name, expected) in [(&private, Code::PermissionDenied), (&protected, Code::Ok)] {
+                let request = comment(name, Visibility::Protected);
+                let result = svc
+                    .create_memo_comment(test::request(&svc, request, Some(&other)).await)
+                    .await;
+                assert_eq!(expected, code(result));
+            }
+            let request = comment(&protected, Visibility::Private);
+            svc.create_memo_comment(test::request(&svc, request, Some(&other)).await)
+                .await
+                .unwrap();
+
+            for (name, user, expected) in [
+                (&private, Some(&other), Code::PermissionDenied),
+                (&protected, None, Code::PermissionDenied),
+                (&protected, Some(&other), Code::Ok),
+            ] {
+                let request = ListMemoCommentsRequest { name: name.clone() };
+                let result = svc
+                    .list_memo_comments(test::request(&svc, request, user).await)
+                    .await;
+                assert_eq!(expected, code(result));
+            }
+
+            // 他人的私有评论对 memo 作者不可见
+            let count = |user| {
+                let svc = svc.clone();
+                let name = protected.clone();
+                async move {
+                    let request = ListMemoCommentsRequest { name };
+                    svc.list_memo_comments(test::request(&svc, request, Some(user)).await)
+                        .await
+                        .unwrap()
+                        .into_inner()
+                        .memos
+                        .len()
+                }
+            };
+            assert_eq!(1, count(&owner).await);
+            assert_eq!(2, count(&other).await);
+        }
+
+        #[tokio::test]
+        async fn memo_relations() {
+            let svc = test::service().await;
+            let owner = test::create_user(&svc, "owner", Role::User).await;
+            let other = test::create_user(&svc, "other", Role::User).await;
+            let memo = create_memo(&svc, &owner, Visibility::Private).await;
+            let public = create_memo(&svc, &other, Visibility::Public).await;
+            let hidden = create_memo(&svc, &other, Visibility::Private).await;
+
+            let set = |name: &str, related: &str| SetMemoRelationsRequest {
+                name: name.to_owned(),
+                relations: vec![MemoRelation {
+                    memo: Some(memo_relation::Memo {
+                        name: name.to_owned(),
+                        ..Default::default()
+                    }),
+                    related_memo: Some(memo_relation::Memo {
+                        name: related.to_owned(),
+                        ..Default::default()
+                    }),
+                    r#type: memo_relation::Type::Reference.into(),
+                }],
+            };
+            for (user, related, expected) in [
+                (&other, &public, Code::PermissionDenied),
+                (&owner, &hidden, Code::PermissionDenied),
+                (&owner, &public, Code::Ok),
+            ] {
+                let request = set(&memo, related);
+                let result = svc
+                    .set_memo_relations(test::request(&svc, request, Some(user)).await)
+                    .await;
+                assert_eq!(expected, code(result));
+            }
+
+            for (user, expected) in [
+                (None, Code::PermissionDenied),
+                (Some(&other), Code::PermissionDenied),
+                (Some(&owner), Code::Ok),
+            ] {
+                let request = ListMemoRelationsRequest { name: memo.clone() };
+                let result = svc
+                    .list_memo_relations(test::request(&svc, request, user).await)
+                    .await;
+                assert_eq!(expected, code(result));
+            }
+            let request = ListMemoRelationsRequest { name: memo.clone() };
+            let relations = svc
+                .list_memo_relations(test::request(&svc, request, Some(&owner)).await)
+                .await
+                .unwrap()
+                .into_inner()
+                .relations;
+            assert_eq!(1, relations.len());
+        }
+
+        #[tokio::test]
+        async fn memo_tags() {
+            let svc = test::service().await;
+            let owner = test::create_user(&svc, "owner", Role::User).await;
+            let other = test::create_user(&svc, "other", Role::User).await;
+            let memo = create_memo_with(&svc, &owner, Visibility::Public, "#tag memo").await;
+
+            // 只改动自己的 memo
+            for (user, expected) in [(&other, "#tag memo"), (&owner, "#renamed memo")] {
+                let request = RenameMemoTagRequest {
+                    parent: "memos/-".to_owned(),
+                    old_tag: "tag".to_owned(),
+                    new_tag: "renamed".to_owned(),
+                };
+                svc.rename_memo_tag(test::request(&svc, request, Some(user)).await)
+                    .await
+                    .unwrap();
+                assert_eq!(
+                    Some(expected.to_owned()),
+                    memo_content(&svc, &memo, &owner).await
+                );
+            }
+
+            for (user, exists) in [(&other, true), (&owner, false)] {
+                let request = DeleteMemoTagRequest {
+                    parent: memo.clone(),
+                    tag: "renamed".to_owned(),
+                    delete_related_memos: true,
+                };
+                svc.delete_memo_tag(test::request(&svc, request, Some(user)).await)
+                    .await
+                    .unwrap();
+                assert_eq!(exists, memo_content(&svc, &memo, &owner).await.is_some());
+            }
+        }
+
+        #[tokio::test]
+        async fn update_and_read_resource() {
+            let svc = test::service().await;
+            let owner = test::create_user(&svc, "owner", Role::User).await;
+            let other = test::create_user(&svc, "other", Role::User).await;
+            let private = create_memo(&svc, &owner, Visibility::Private).await;
+            let foreign = create_memo(&svc, &other, Visibility::Public).await;
+            let resource = create_text_resource(&svc, &owner).await;
+
+            let update = |memo: &str| UpdateResourceRequest {
+                resource: Some(Resource {
+                    name: resource.name.clone(),
+                    memo: Some(memo.to_owned()),
+                    ..Default::default()
+                }),
+                update_mask: Some(FieldMask {
+                    paths: vec!["memo".to_owned()],
+                }),
+            };
+            for (user, memo, expected) in [
+                (&other, &foreign, Code::PermissionDenied),
+                (&owner, &foreign, Code::PermissionDenied),
+                (&owner, &private, Code::Ok),
+            ] {
+                let result = svc
+                    .update_resource(test::request(&svc, update(memo), Some(user)).await)
+                    .await;
+                assert_eq!(expected, code(result));
+            }
+
+            // 资源随私有 memo 不可读
+            for (user, expected) in [
+                (None, Code::PermissionDenied),
+                (Some(&other), Code::PermissionDenied),
+                (Some(&owner), Code::Ok),
+            ] {
+                let request = GetResourceBinaryRequest {
+                    name: resource.name.clone(),
+                    ..Default::default()
+                };
+                let result = svc
+                    .get_resource_binary(test::request(&svc, request, user).await)
+                    .await;
+                assert_eq!(expected, code(result));
+            }
+
+            let unlinked = create_text_resource(&svc, &owner).await;
+            for (user, expected) in [(&other, Code::PermissionDenied), (&owner, Code::Ok)] {
+                let request = DeleteResourceRequest {
+                    name: unlinked.name.clone(),
+                };
+                let result = svc
+                    .delete_resource(test::request(&svc, request, Some(user)).await)
+                    .await;
+                assert_eq!(expected, code(result));
+            }
+        }
+    }
+}
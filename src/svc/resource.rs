@@ -1,21 +1,29 @@
-use std::path;
-use std::sync::Arc;
-use std::{collections::HashMap, io::Cursor};
+pub mod cache;
+pub mod metadata;
+pub mod store;
+pub mod thumbnail;
+
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
 
 use async_trait::async_trait;
-use image::{ImageFormat, ImageReader};
+use bytes::Bytes;
+use futures::{StreamExt, TryStreamExt};
+use sm3::{Digest, Sm3};
 use snafu::{ensure, OptionExt, ResultExt, Snafu};
-use tokio::{
-    fs::{self, File, OpenOptions},
-    io::AsyncWriteExt,
-};
-use tokio_util::io::ReaderStream;
+use time::OffsetDateTime;
+use tokio::{fs::File, io::AsyncReadExt};
 use tonic::{Request, Response, Status};
+use tracing::{error, warn};
 
+use crate::dao::memo::MemoRepository;
+use crate::dao::quota::QuotaRepository;
 use crate::dao::resource::ResourceRepository;
 use crate::dao::workspace::WorkspaceRepository;
 use crate::google::api::HttpBody;
 
+use crate::model::gen::WorkspaceStorageSetting;
 use crate::svc::workspace::WorkspaceSettingService;
 use crate::{
     api::prefix::ExtractName,
@@ -24,14 +32,25 @@ use crate::{
         CreateResourceRequest, DeleteResourceRequest, GetResourceBinaryRequest, GetResourceRequest,
         ListResourcesRequest, ListResourcesResponse, Resource, UpdateResourceRequest,
     },
-    model::resource::{FindResource, Resource as ResourceModel},
+    model::{
+        gen::memo_payload::Location,
+        memo::{FindMemo, UpdateMemo},
+        resource::{
+            FindResource, Resource as ResourceModel, ResourceBlob, ResourceGcReport, UpdateResource,
+        },
+        system::StoragePrivacySetting,
+        user::{User, UserQuota, UserStorage},
+    },
+    util,
 };
 
+use self::store::ByteStream;
+use self::thumbnail::Thumbnail;
+use super::permission::{self, Action, PermissionDenied};
 use super::{RequestExt, Service};
 
-const RESOURCE_PATH: &str = ".resource_cache";
-const THUMBNAIL_IMAGE_PATH: &str = ".thumbnail_cache";
 const MEBI_BYTE: usize = 1024 * 1024;
+pub const DEFAULT_GC_GRACE_DAYS: i64 = 7;
 
 #[async_trait]
 pub trait ResourceService:
@@ -41,36 +60,70 @@ pub trait ResourceService:
         ResourceServiceServer::from_arc(self)
     }
 
+    /// 新关联的图片带有拍摄位置时写入 memo
     async fn set_resources_memo(
         &self,
+        user: &User,
         memo_id: i32,
         new_res_ids: Vec<i32>,
         old_res_ids: Vec<i32>,
     ) -> Result<(), Error>;
     async fn get_resource_by_id(&self, id: i32) -> Result<ResourceModel, Error>;
-    async fn get_whole_resource(&self, id: i32) -> Result<ResourceModel, Error>;
+    /// 获取资源并按关联 memo 的可见性与用户角色校验权限
+    async fn get_resource_with_permission(
+        &self,
+        user: Option<&User>,
+        id: i32,
+        action: Action,
+    ) -> Result<ResourceModel, Error>;
     async fn relate_resources(
         &self,
         memo_ids: Vec<i32>,
     ) -> Result<HashMap<i32, Vec<ResourceModel>>, Error>;
     async fn relate_resource(&self, memo_id: i32) -> Result<Vec<ResourceModel>, Error>;
-    async fn get_resource_stream(
+    /// 本地缓存的资源文件或缩略图，缓存不存在时从存储加载
+    async fn get_resource_file(
         &self,
-        id: i32,
+        resource: &ResourceModel,
+        thumbnail: Option<Thumbnail>,
+    ) -> Result<File, Error>;
+    /// 边接收边写入存储，超出上传限制时立即中止
+    async fn create_resource_stream(
+        &self,
+        user: &User,
         filename: String,
-        thumbnail: bool,
-    ) -> Result<ReaderStream<File>, Error>;
+        content_type: Option<String>,
+        stream: ByteStream<'_>,
+    ) -> Result<ResourceModel, Error>;
+    /// 启动时清理已不对应任何资源的缓存文件
+    async fn sweep_resource_cache(&self) -> Result<(), Error>;
+    async fn list_trashed_resources(&self, user: &User) -> Result<Vec<ResourceModel>, Error>;
+    async fn restore_resource(&self, user: &User, id: i32) -> Result<ResourceModel, Error>;
+    /// 清理超过宽限期的未关联资源与回收站资源，dry_run 时只统计
+    async fn collect_orphan_resources(&self, dry_run: bool) -> Result<ResourceGcReport, Error>;
+    /// 存储用量含回收站中尚未清理的资源
+    async fn get_user_storage(&self, user: &User, user_id: i32) -> Result<UserStorage, Error>;
+    async fn set_user_quota(&self, user: &User, quota: UserQuota) -> Result<UserStorage, Error>;
+    async fn get_storage_privacy(&self, user: &User) -> Result<StoragePrivacySetting, Error>;
+    async fn set_storage_privacy(
+        &self,
+        user: &User,
+        setting: StoragePrivacySetting,
+    ) -> Result<StoragePrivacySetting, Error>;
 }
 
 #[async_trait]
-impl<R: ResourceRepository + WorkspaceRepository> ResourceService for Service<R> {
+impl<R: ResourceRepository + MemoRepository + WorkspaceRepository + QuotaRepository> ResourceService
+    for Service<R>
+{
     async fn set_resources_memo(
         &self,
+        user: &User,
         memo_id: i32,
         new_res_ids: Vec<i32>,
         old_res_ids: Vec<i32>,
     ) -> Result<(), Error> {
-        let add_res_ids = new_res_ids
+        let add_res_ids: Vec<i32> = new_res_ids
             .iter()
             .filter(|&i| !old_res_ids.contains(i))
             .copied()
@@ -81,10 +134,20 @@ impl<R: ResourceRepository + WorkspaceRepository> ResourceService for Service<R>
             .copied()
             .collect();
 
-        Ok(self
-            .repo
-            .set_resources_memo(memo_id, add_res_ids, del_res_ids)
-            .await?)
+        self.repo
+            .set_resources_memo(memo_id, add_res_ids.clone(), del_res_ids)
+            .await?;
+
+        let location = self
+            .relate_resource(memo_id)
+            .await?
+            .into_iter()
+            .filter(|r| add_res_ids.contains(&r.id))
+            .find_map(|r| r.location);
+        if let Some(location) = location {
+            self.keep_memo_location(user, memo_id, location).await?;
+        }
+        Ok(())
     }
 
     async fn get_resource_by_id(&self, id: i32) -> Result<ResourceModel, Error> {
@@ -98,8 +161,26 @@ impl<R: ResourceRepository + WorkspaceRepository> ResourceService for Service<R>
         rs.pop().context(ResourceNotFound)
     }
 
-    async fn get_whole_resource(&self, id: i32) -> Result<ResourceModel, Error> {
-        self.repo.get_resource(id).await?.context(ResourceNotFound)
+    async fn get_resource_with_permission(
+        &self,
+        user: Option<&User>,
+        id: i32,
+        action: Action,
+    ) -> Result<ResourceModel, Error> {
+        let res = self.get_resource_by_id(id).await?;
+        let memo = match res.memo_id {
+            Some(memo_id) => self
+                .repo
+                .list_memos(FindMemo {
+                    id: Some(memo_id),
+                    ..Default::default()
+                })
+                .await?
+                .pop(),
+            None => None,
+        };
+        permission::check_resource(user, &res, memo.as_ref(), action)?;
+        Ok(res)
     }
 
     async fn relate_resources(
@@ -114,83 +195,466 @@ impl<R: ResourceRepository + WorkspaceRepository> ResourceService for Service<R>
         Ok(rs.into_values().next().unwrap_or(vec![]))
     }
 
-    async fn get_resource_stream(
+    async fn get_resource_file(
         &self,
-        id: i32,
+        resource: &ResourceModel,
+        thumbnail: Option<Thumbnail>,
+    ) -> Result<File, Error> {
+        let key = cache_key(resource);
+        let Some(thumbnail) = thumbnail else {
+            if let Some(file) = self.cache.resource.get(&key).await {
+                return Ok(file);
+            }
+            let blob = self.load_resource_content(resource.id).await?;
+            return self
+                .cache
+                .resource
+                .put(&key, &blob)
+                .await
+                .context(WriteResource);
+        };
+
+        let key = thumbnail.cache_key(&key);
+        if let Some(file) = self.cache.thumbnail.get(&key).await {
+            return Ok(file);
+        }
+        let blob = self.load_resource_content(resource.id).await?;
+        let bytes = thumbnail::generate(blob, thumbnail).await?;
+        self.cache
+            .thumbnail
+            .put(&key, &bytes)
+            .await
+            .context(WriteResource)
+    }
+
+    async fn create_resource_stream(
+        &self,
+        user: &User,
         filename: String,
-        thumbnail: bool,
-    ) -> Result<ReaderStream<File>, Error> {
-        let filename = format!("{}.{}", id, filename);
-        let resource_path = path::Path::new(RESOURCE_PATH).join(&filename);
-        let thumbnail_path = path::Path::new(THUMBNAIL_IMAGE_PATH).join(&filename);
-
-        let exists = if thumbnail {
-            fs::try_exists(&thumbnail_path).await.unwrap_or(false)
+        content_type: Option<String>,
+        stream: ByteStream<'_>,
+    ) -> Result<ResourceModel, Error> {
+        let limit = self.get_upload_size_limit().await;
+        let max_upload_size_bytes = limit * MEBI_BYTE;
+        let quota = self.remaining_quota(user.id).await?;
+
+        let received = Arc::new(AtomicUsize::new(0));
+        let hasher = Arc::new(Mutex::new(Sm3::new()));
+        let counter = received.clone();
+        let digest = hasher.clone();
+        let stream = stream.map(move |chunk| {
+            let chunk = chunk?;
+            let size = counter.fetch_add(chunk.len(), Ordering::Relaxed) + chunk.len();
+            if size >= max_upload_size_bytes {
+                return Err(std::io::Error::other("file size exceeds limit"));
+            }
+            if quota.is_some_and(|quota| size > quota) {
+                return Err(std::io::Error::other("storage quota exceeded"));
+            }
+            if let Ok(mut hasher) = digest.lock() {
+                hasher.update(&chunk);
+            }
+            Ok(chunk)
+        });
+        let mut stream = stream.peekable();
+        let head = match std::pin::Pin::new(&mut stream).peek().await {
+            Some(Ok(chunk)) => chunk.clone(),
+            _ => Default::default(),
+        };
+
+        let mut create = ResourceModel {
+            uid: util::uuid(),
+            creator_id: user.id,
+            r#type: detect_mime(&head, &filename, content_type),
+            filename,
+            ..Default::default()
+        };
+        let setting = self.get_storage_setting().await;
+        let privacy = self.get_storage_privacy_setting().await;
+        let resource = if privacy.strip_image_metadata && metadata::supported(&create.r#type) {
+            // 改写元数据需要完整内容，图片大小同样受上传限制约束
+            let chunks: Result<Vec<Bytes>, _> = stream.try_collect().await;
+            let size = received.load(Ordering::Relaxed);
+            ensure!(size < max_upload_size_bytes, FileSizeLimit { size: limit });
+            ensure!(quota.is_none_or(|quota| size <= quota), QuotaExceeded);
+            create.blob = chunks.context(ReadUpload)?.concat();
+            create.size = create.blob.len();
+            // 上传时尚未关联 memo，位置随资源保存
+            create.location = strip_image_metadata(&privacy, &mut create);
+            self.create_resource_content(&setting, create).await?
         } else {
-            fs::try_exists(&resource_path).await.unwrap_or(false)
+            let saved = store::for_setting(&setting)?
+                .save_stream(&mut create, Box::pin(stream))
+                .await;
+            let size = received.load(Ordering::Relaxed);
+            ensure!(size < max_upload_size_bytes, FileSizeLimit { size: limit });
+            ensure!(quota.is_none_or(|quota| size <= quota), QuotaExceeded);
+            saved?;
+
+            create.size = size;
+            create.hash = hasher
+                .lock()
+                .map(|h| hex::encode(h.clone().finalize()))
+                .unwrap_or_default();
+            self.create_resource_with_blob(create).await?
         };
-        if !exists {
-            if thumbnail {
-                creator_dir(THUMBNAIL_IMAGE_PATH).await?;
-            } else {
-                creator_dir(RESOURCE_PATH).await?;
-            }
+        self.pregenerate_thumbnails(&resource);
+        Ok(resource)
+    }
+
+    async fn sweep_resource_cache(&self) -> Result<(), Error> {
+        let live: HashSet<String> = self
+            .repo
+            .list_resources(FindResource::default())
+            .await?
+            .iter()
+            .map(cache_key)
+            .collect();
+        self.cache
+            .resource
+            .sweep(|key| live.contains(key))
+            .await
+            .context(SweepCache)?;
+        self.cache
+            .thumbnail
+            .sweep(|key| thumbnail::resource_key(key).is_some_and(|key| live.contains(key)))
+            .await
+            .context(SweepCache)?;
+        Ok(())
+    }
+
+    async fn list_trashed_resources(&self, user: &User) -> Result<Vec<ResourceModel>, Error> {
+        Ok(self
+            .repo
+            .list_resources(FindResource {
+                creator_id: Some(user.id),
+                trashed: true,
+                ..Default::default()
+            })
+            .await?)
+    }
 
-            let ResourceModel { blob, .. } = Self::get_whole_resource(self, id).await?;
-            if thumbnail {
-                let mut bytes = Vec::new();
-                {
-                    let img = ImageReader::new(Cursor::new(blob))
-                        .with_guessed_format()
-                        .context(OpenResource)?
-                        .decode()
-                        .context(ImageDecode)?;
-                    let img = img.thumbnail(512, 512);
-                    let format = ImageFormat::from_path(&filename).context(ImageEncode)?;
-                    img.write_to(&mut Cursor::new(&mut bytes), format)
-                        .context(ImageEncode)?;
+    async fn restore_resource(&self, user: &User, id: i32) -> Result<ResourceModel, Error> {
+        let res = self
+            .repo
+            .list_resources(FindResource {
+                id: Some(id),
+                trashed: true,
+                ..Default::default()
+            })
+            .await?
+            .pop()
+            .context(ResourceNotFound)?;
+        permission::check_resource(Some(user), &res, None, Action::Write)?;
+
+        self.repo
+            .update_resource(UpdateResource {
+                id,
+                trashed: Some(false),
+                ..Default::default()
+            })
+            .await?;
+        self.get_resource_by_id(id).await
+    }
+
+    async fn collect_orphan_resources(&self, dry_run: bool) -> Result<ResourceGcReport, Error> {
+        let before = (OffsetDateTime::now_utc() - self.gc_grace).unix_timestamp();
+        let orphans = self.repo.list_orphan_resources(before).await?;
+
+        let mut report = ResourceGcReport {
+            dry_run,
+            ..Default::default()
+        };
+        for res in orphans {
+            if !dry_run {
+                // 单个资源失败不影响其余资源
+                if let Err(e) = self.remove_resource(&res).await {
+                    error!("Failed to collect resource {}: {e}", res.id);
+                    continue;
                 }
-                save_file(&thumbnail_path, &bytes).await?;
-            } else {
-                save_file(&resource_path, &blob).await?;
             }
+            report.add(&res);
         }
+        Ok(report)
+    }
 
-        let read_path = if thumbnail {
-            thumbnail_path
-        } else {
-            resource_path
+    async fn get_user_storage(&self, user: &User, user_id: i32) -> Result<UserStorage, Error> {
+        permission::check_user_storage(user, user_id, Action::Read)?;
+        self.find_user_storage(user_id).await
+    }
+
+    async fn set_user_quota(&self, user: &User, quota: UserQuota) -> Result<UserStorage, Error> {
+        permission::check_user_storage(user, quota.user_id, Action::Write)?;
+        ensure!(
+            quota.bytes_limit >= 0 && quota.files_limit >= 0,
+            InvalidQuota
+        );
+        let user_id = quota.user_id;
+        self.repo.upsert_user_quota(quota).await?;
+        self.find_user_storage(user_id).await
+    }
+
+    async fn get_storage_privacy(&self, user: &User) -> Result<StoragePrivacySetting, Error> {
+        permission::check_workspace_setting(user)?;
+        Ok(self.repo.find_storage_privacy_setting().await?)
+    }
+
+    async fn set_storage_privacy(
+        &self,
+        user: &User,
+        setting: StoragePrivacySetting,
+    ) -> Result<StoragePrivacySetting, Error> {
+        permission::check_workspace_setting(user)?;
+        self.repo.upsert_storage_privacy_setting(&setting).await?;
+        Ok(setting)
+    }
+}
+
+impl<R: ResourceRepository + QuotaRepository> Service<R> {
+    async fn find_user_storage(&self, user_id: i32) -> Result<UserStorage, Error> {
+        let usage = self.repo.get_storage_usage(user_id).await?;
+        let quota = self.repo.find_user_quota(user_id).await?;
+        Ok(UserStorage::new(user_id, usage, quota))
+    }
+
+    /// 剩余可用字节数，None 表示不限制；文件数已满时直接拒绝
+    async fn remaining_quota(&self, user_id: i32) -> Result<Option<usize>, Error> {
+        let storage = self.find_user_storage(user_id).await?;
+        ensure!(!storage.files_exhausted(), QuotaExceeded);
+        Ok(storage.remaining_bytes())
+    }
+}
+
+impl<R: ResourceRepository> Service<R> {
+    /// 保存新资源及其内容，相同摘要的内容只保存一份
+    async fn create_resource_content(
+        &self,
+        setting: &WorkspaceStorageSetting,
+        mut create: ResourceModel,
+    ) -> Result<ResourceModel, Error> {
+        create.hash = hash_content(&create.blob);
+        if let Some(blob) = self.repo.find_resource_blob(&create.hash).await? {
+            let content = std::mem::take(&mut create.blob);
+            let mut linked = create.clone();
+            linked.link_blob(&blob);
+            // 内容可能已被并发的删除释放，此时按新内容保存
+            if let Some((resource, _)) = self.repo.create_resource_with_blob(linked, None).await? {
+                return Ok(resource);
+            }
+            create.blob = content;
+        }
+        store::for_setting(setting)?.save(&mut create).await?;
+        self.create_resource_with_blob(create).await
+    }
+
+    /// 记录已保存的内容及引用它的资源，并发上传相同内容时以先写入的为准
+    async fn create_resource_with_blob(
+        &self,
+        mut create: ResourceModel,
+    ) -> Result<ResourceModel, Error> {
+        let saved = ResourceModel {
+            storage_type: create.storage_type,
+            reference: create.reference.clone(),
+            payload: create.payload.clone(),
+            ..Default::default()
+        };
+        let blob = ResourceBlob {
+            hash: create.hash.clone(),
+            size: create.size,
+            blob: std::mem::take(&mut create.blob),
+            storage_type: create.storage_type,
+            reference: create.reference.clone(),
+            payload: create.payload.clone(),
+            ref_count: 0,
         };
+        let (resource, created) = self
+            .repo
+            .create_resource_with_blob(create, Some(blob))
+            .await?
+            .context(MaybeCreateResource)?;
+        if !created {
+            // 丢弃刚保存的副本
+            store::for_resource(&saved)?.delete(&saved).await?;
+        }
+        Ok(resource)
+    }
+
+    async fn load_resource_content(&self, id: i32) -> Result<Bytes, Error> {
+        let whole = self
+            .repo
+            .get_resource(id)
+            .await?
+            .context(ResourceNotFound)?;
+        let blob = store::for_resource(&whole)?.load(whole).await?;
+        Ok(blob.into())
+    }
+
+    /// 上传图片后在后台生成各尺寸的 WebP 缩略图
+    fn pregenerate_thumbnails(&self, resource: &ResourceModel) {
+        if !thumbnail::supported(&resource.r#type) {
+            return;
+        }
+        let svc = self.clone();
+        let (id, key) = (resource.id, cache_key(resource));
+        let mime = resource.r#type.clone();
+        tokio::spawn(async move {
+            if let Err(e) = svc.cache_thumbnails(id, &key, &mime).await {
+                error!("Failed to pregenerate thumbnails: {e}");
+            }
+        });
+    }
+
+    async fn cache_thumbnails(&self, id: i32, key: &str, mime: &str) -> Result<(), Error> {
+        let mut blob = None;
+        for size in thumbnail::SIZES {
+            let thumbnail = Thumbnail::new(Some(size), true, mime);
+            let key = thumbnail.cache_key(key);
+            if self.cache.thumbnail.get(&key).await.is_some() {
+                continue;
+            }
+            if blob.is_none() {
+                blob = Some(self.load_resource_content(id).await?);
+            }
+            let bytes = thumbnail::generate(blob.clone().unwrap_or_default(), thumbnail).await?;
+            self.cache
+                .thumbnail
+                .put(&key, &bytes)
+                .await
+                .context(WriteResource)?;
+        }
+        Ok(())
+    }
+
+    /// 删除资源，内容在最后一个引用删除后才释放
+    async fn remove_resource(&self, resource: &ResourceModel) -> Result<(), Error> {
+        if resource.hash.is_empty() {
+            store::for_resource(resource)?.delete(resource).await?;
+            self.repo
+                .delete_resource(resource.id, resource.creator_id)
+                .await?;
+            self.cache.remove(&cache_key(resource)).await;
+            return Ok(());
+        }
 
-        let read_file = File::open(&read_path).await.context(OpenResource)?;
-        Ok(ReaderStream::new(read_file))
+        self.repo
+            .delete_resource(resource.id, resource.creator_id)
+            .await?;
+        if let Some(blob) = self.repo.release_resource_blob(&resource.hash).await? {
+            let blob: ResourceModel = blob.into();
+            store::for_resource(&blob)?.delete(&blob).await?;
+            self.cache.remove(&resource.hash).await;
+        }
+        Ok(())
     }
 }
 
-async fn creator_dir(dir: impl AsRef<path::Path>) -> Result<(), Error> {
-    if !fs::try_exists(&dir).await.context(CreateCachedDir)? {
-        fs::create_dir(dir).await.context(CreateCachedDir)?;
+/// 相同内容共用缓存，旧资源没有摘要时以 id 与更新时间区分
+fn cache_key(resource: &ResourceModel) -> String {
+    if resource.hash.is_empty() {
+        format!("{}-{}", resource.id, resource.updated_ts)
+    } else {
+        resource.hash.clone()
+    }
+}
+
+impl<R: ResourceRepository + MemoRepository> Service<R> {
+    /// 只能关联到自己有权修改的 memo
+    async fn check_memo_writable(&self, user: &User, memo_id: i32) -> Result<(), Error> {
+        let memo = self
+            .repo
+            .list_memos(FindMemo {
+                id: Some(memo_id),
+                ..Default::default()
+            })
+            .await?
+            .pop()
+            .context(MemoNotFound)?;
+        permission::check_memo(Some(user), &memo, Action::Write)?;
+        Ok(())
     }
-    Ok(())
+
+    /// 关联的 memo 尚无位置时写入图片的拍摄位置
+    async fn keep_memo_location(
+        &self,
+        user: &User,
+        memo_id: i32,
+        location: Location,
+    ) -> Result<(), Error> {
+        let memo = self
+            .repo
+            .list_memos(FindMemo {
+                id: Some(memo_id),
+                ..Default::default()
+            })
+            .await?
+            .pop()
+            .context(MemoNotFound)?;
+        if memo.payload.location.is_some()
+            || permission::check_memo(Some(user), &memo, Action::Write).is_err()
+        {
+            return Ok(());
+        }
+
+        let mut payload = memo.payload;
+        payload.location = Some(location);
+        self.repo
+            .update_memo(UpdateMemo {
+                id: memo.id,
+                creator_id: memo.creator_id,
+                payload: Some(payload),
+                ..Default::default()
+            })
+            .await?;
+        Ok(())
+    }
+}
+
+/// 按存储设置移除图片元数据，返回需要保留的拍摄位置
+fn strip_image_metadata(
+    setting: &StoragePrivacySetting,
+    create: &mut ResourceModel,
+) -> Option<Location> {
+    // 客户端声明的类型不可信，按内容识别
+    let mime = detect_mime(&create.blob, &create.filename, Some(create.r#type.clone()));
+    if !setting.strip_image_metadata || !metadata::supported(&mime) {
+        return None;
+    }
+    let Some(stripped) = metadata::strip(&create.blob, &mime) else {
+        warn!("Failed to parse image metadata: {}", create.filename);
+        return None;
+    };
+    create.blob = stripped.blob;
+    create.size = create.blob.len();
+    let (latitude, longitude) = stripped.location.filter(|_| setting.keep_image_location)?;
+    Some(Location {
+        placeholder: String::new(),
+        latitude,
+        longitude,
+    })
+}
+
+fn hash_content(blob: &[u8]) -> String {
+    let mut hasher = Sm3::new();
+    hasher.update(blob);
+    hex::encode(hasher.finalize())
 }
 
-async fn save_file(path: impl AsRef<path::Path>, blob: &[u8]) -> Result<(), Error> {
-    let mut resource_file = OpenOptions::new()
-        .create(true)
-        .write(true)
-        .read(false)
-        .truncate(true)
-        .open(&path)
-        .await
-        .context(OpenResource)?;
-
-    resource_file.write_all(blob).await.context(WriteResource)?;
-    Ok(())
+/// 优先按文件头识别类型，其次按扩展名，最后采用客户端声明的类型
+fn detect_mime(head: &[u8], filename: &str, content_type: Option<String>) -> String {
+    if let Some(kind) = infer::get(head) {
+        return kind.mime_type().to_owned();
+    }
+    if let Some(mime) = mime_guess::from_path(filename).first() {
+        return mime.to_string();
+    }
+    content_type
+        .filter(|t| !t.is_empty())
+        .unwrap_or_else(|| "application/octet-stream".to_owned())
 }
 
 #[tonic::async_trait]
-impl<R: ResourceRepository + WorkspaceRepository> resource_service_server::ResourceService
-    for Service<R>
+impl<R: ResourceRepository + MemoRepository + WorkspaceRepository + QuotaRepository>
+    resource_service_server::ResourceService for Service<R>
 {
     async fn list_resources(
         &self,
@@ -215,7 +679,10 @@ impl<R: ResourceRepository + WorkspaceRepository> resource_service_server::Resou
     ) -> Result<Response<()>, Status> {
         let user = request.get_current_user()?;
         let id = request.get_ref().get_id()?;
-        self.repo.delete_resource(id, user.id).await?;
+        let res = self
+            .get_resource_with_permission(Some(user), id, Action::Write)
+            .await?;
+        self.remove_resource(&res).await?;
         Ok(Response::new(()))
     }
 
@@ -223,8 +690,11 @@ impl<R: ResourceRepository + WorkspaceRepository> resource_service_server::Resou
         &self,
         request: Request<GetResourceRequest>,
     ) -> Result<Response<Resource>, Status> {
+        let user = request.get_current_user().ok();
         let id = request.get_ref().get_id()?;
-        let res = self.get_resource_by_id(id).await?;
+        let res = self
+            .get_resource_with_permission(user, id, Action::Read)
+            .await?;
         Ok(Response::new(res.into()))
     }
 
@@ -232,7 +702,62 @@ impl<R: ResourceRepository + WorkspaceRepository> resource_service_server::Resou
         &self,
         request: Request<UpdateResourceRequest>,
     ) -> Result<Response<Resource>, Status> {
-        Err(Status::unimplemented("unimplemented"))
+        let user = request.get_current_user()?;
+        let UpdateResourceRequest {
+            resource: Some(resource),
+            update_mask: Some(field_mask),
+        } = request.get_ref()
+        else {
+            return Err(Status::invalid_argument(
+                "resource and update_mask are required",
+            ));
+        };
+        let id = resource.get_id()?;
+        let old = self
+            .get_resource_with_permission(Some(user), id, Action::Write)
+            .await?;
+
+        let mut update = UpdateResource {
+            id,
+            ..Default::default()
+        };
+        for path in &field_mask.paths {
+            match path.as_str() {
+                "filename" => {
+                    ensure!(
+                        !resource.filename.trim().is_empty(),
+                        InvalidResourceFilename
+                    );
+                    update.filename = Some(resource.filename.clone());
+                }
+                "memo" => {
+                    let memo = resource.memo.as_deref().unwrap_or_default();
+                    // 为空时解除关联
+                    let memo_id = if memo.is_empty() {
+                        None
+                    } else {
+                        Some(resource.get_memo().context(InvalidResourceMemo { memo })?)
+                    };
+                    if let Some(memo_id) = memo_id {
+                        self.check_memo_writable(user, memo_id).await?;
+                    }
+                    update.memo_id = Some(memo_id);
+                }
+                _ => (),
+            }
+        }
+
+        let linked_memo_id = update.memo_id.flatten();
+        self.repo.update_resource(update).await?;
+        if let (Some(location), Some(memo_id)) = (old.location.clone(), linked_memo_id) {
+            self.keep_memo_location(user, memo_id, location).await?;
+        }
+        // 旧资源的缓存 key 含更新时间，更新后即失效
+        if old.hash.is_empty() {
+            self.cache.remove(&cache_key(&old)).await;
+        }
+        let res = self.get_resource_by_id(id).await?;
+        Ok(Response::new(res.into()))
     }
 
     async fn create_resource(
@@ -250,12 +775,23 @@ impl<R: ResourceRepository + WorkspaceRepository> resource_service_server::Resou
             let max_upload_size_bytes = limit * MEBI_BYTE;
             ensure!(max_upload_size_bytes > size, FileSizeLimit { size: limit });
 
-            // 默认保存到 turso
-            let resource = self
-                .repo
-                .create_resource(create)
-                .await?
-                .context(MaybeCreateResource)?;
+            let privacy = self.get_storage_privacy_setting().await;
+            create.location = strip_image_metadata(&privacy, &mut create);
+            let quota = self.remaining_quota(user.id).await?;
+            ensure!(
+                quota.is_none_or(|quota| create.blob.len() <= quota),
+                QuotaExceeded
+            );
+
+            if let Some(memo_id) = create.memo_id {
+                self.check_memo_writable(user, memo_id).await?;
+            }
+            let setting = self.get_storage_setting().await;
+            let resource = self.create_resource_content(&setting, create).await?;
+            if let (Some(location), Some(memo_id)) = (resource.location.clone(), resource.memo_id) {
+                self.keep_memo_location(user, memo_id, location).await?;
+            }
+            self.pregenerate_thumbnails(&resource);
             Ok(Response::new(resource.into()))
         } else {
             Err(Status::data_loss("null request"))
@@ -266,7 +802,22 @@ impl<R: ResourceRepository + WorkspaceRepository> resource_service_server::Resou
         &self,
         request: Request<GetResourceBinaryRequest>,
     ) -> Result<Response<HttpBody>, Status> {
-        Err(Status::unimplemented("unimplemented"))
+        let user = request.get_current_user().ok();
+        let id = request.get_ref().get_id()?;
+        let res = self
+            .get_resource_with_permission(user, id, Action::Read)
+            .await?;
+        let thumbnail = (request.get_ref().thumbnail && thumbnail::supported(&res.r#type))
+            .then(|| Thumbnail::new(None, false, &res.r#type));
+
+        let mut file = self.get_resource_file(&res, thumbnail).await?;
+        let mut data = Vec::new();
+        file.read_to_end(&mut data).await.context(OpenResource)?;
+        Ok(Response::new(HttpBody {
+            content_type: thumbnail.map_or(res.r#type, |t| t.mime().to_owned()),
+            data,
+            extensions: vec![],
+        }))
     }
 }
 
@@ -287,6 +838,40 @@ pub enum Error {
     },
     #[snafu(display("Resource not found"), context(suffix(false)))]
     ResourceNotFound,
+    #[snafu(display("Memo not found"), context(suffix(false)))]
+    MemoNotFound,
+    #[snafu(display("Invalid resource memo: {memo}"), context(suffix(false)))]
+    InvalidResourceMemo { memo: String },
+    #[snafu(display("Resource filename is empty"), context(suffix(false)))]
+    InvalidResourceFilename,
+    #[snafu(context(false))]
+    Permission { source: PermissionDenied },
+    #[snafu(context(false))]
+    Store { source: store::Error },
+    #[snafu(context(false))]
+    CreateResource {
+        source: crate::dao::resource::CreateResourceError,
+    },
+    #[snafu(context(false))]
+    UpdateResource {
+        source: crate::dao::resource::UpdateResourceError,
+    },
+    #[snafu(context(false))]
+    FindResourceBlob {
+        source: crate::dao::resource::FindResourceBlobError,
+    },
+    #[snafu(context(false))]
+    ReleaseResourceBlob {
+        source: crate::dao::resource::ReleaseResourceBlobError,
+    },
+    #[snafu(context(false))]
+    ListMemo {
+        source: crate::dao::memo::ListMemoError,
+    },
+    #[snafu(context(false))]
+    UpdateMemo {
+        source: crate::dao::memo::UpdateMemoError,
+    },
     #[snafu(context(false))]
     ListResource {
         source: crate::dao::resource::ListResourceError,
@@ -299,17 +884,43 @@ pub enum Error {
     RelateResources {
         source: crate::dao::resource::RelateResourceError,
     },
+    #[snafu(context(false))]
+    FindUserQuota {
+        source: crate::dao::quota::FindUserQuotaError,
+    },
+    #[snafu(context(false))]
+    UpsertUserQuota {
+        source: crate::dao::quota::UpsertUserQuotaError,
+    },
+    #[snafu(context(false))]
+    GetStorageUsage {
+        source: crate::dao::quota::GetStorageUsageError,
+    },
+    #[snafu(context(false))]
+    FindWorkspaceSetting {
+        source: crate::dao::workspace::FindWorkspaceSettingError,
+    },
+    #[snafu(context(false))]
+    UpsertWorkspaceSetting {
+        source: crate::dao::workspace::UpsertWorkspaceSettingError,
+    },
 
     #[snafu(
         display("File size exceeds allowed limit of {size} MiB"),
         context(suffix(false))
     )]
     FileSizeLimit { size: usize },
+    #[snafu(display("Storage quota exceeded"), context(suffix(false)))]
+    QuotaExceeded,
+    #[snafu(display("Quota limits must not be negative"), context(suffix(false)))]
+    InvalidQuota,
+    #[snafu(display("Failed to read upload: {source}"), context(suffix(false)))]
+    ReadUpload { source: std::io::Error },
     #[snafu(
-        display("Failed to create cached dir: {source}"),
+        display("Failed to sweep resource cache: {source}"),
         context(suffix(false))
     )]
-    CreateCachedDir { source: std::io::Error },
+    SweepCache { source: std::io::Error },
     #[snafu(display("Failed to open resource: {source}"), context(suffix(false)))]
     OpenResource { source: std::io::Error },
     #[snafu(display("Failed to write resource: {source}"), context(suffix(false)))]
@@ -321,4 +932,147 @@ pub enum Error {
         context(suffix(false))
     )]
     ImageEncode { source: image::ImageError },
+    #[snafu(
+        display("Failed to generate thumbnail: {source}"),
+        context(suffix(false))
+    )]
+    GenerateThumbnail { source: tokio::task::JoinError },
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        api::prefix::ExtractName,
+        api::v1::gen::{
+            memo_service_server::MemoService, resource_service_server::ResourceService, user::Role,
+            CreateMemoRequest, CreateResourceRequest, DeleteResourceRequest, GetMemoRequest, Memo,
+            Resource, SetMemoResourcesRequest, Visibility,
+        },
+        dao::resource::ResourceRepository,
+        model::{resource::Resource as ResourceModel, system::StoragePrivacySetting, user::User},
+        svc::test::{self, TestService},
+    };
+
+    use super::{metadata, Error, ResourceService as _};
+
+    async fn create(svc: &TestService, owner: &User) -> Resource {
+        let request = CreateResourceRequest {
+            resource: Some(Resource {
+                filename: "a.txt".to_owned(),
+                r#type: "text/plain".to_owned(),
+                content: b"same".to_vec(),
+                ..Default::default()
+            }),
+        };
+        svc.create_resource(test::request(svc, request, Some(owner)).await)
+            .await
+            .unwrap()
+            .into_inner()
+    }
+
+    #[tokio::test]
+    async fn shared_blob() {
+        let svc = test::service().await;
+        let owner = test::create_user(&svc, "owner", Role::User).await;
+        let a = create(&svc, &owner).await;
+        let b = create(&svc, &owner).await;
+        let hash = super::hash_content(b"same");
+
+        // 仍有引用时内容不会释放
+        let request = DeleteResourceRequest { name: a.name };
+        svc.delete_resource(test::request(&svc, request, Some(&owner)).await)
+            .await
+            .unwrap();
+        assert!(svc.repo.find_resource_blob(&hash).await.unwrap().is_some());
+        assert!(svc
+            .repo
+            .release_resource_blob(&hash)
+            .await
+            .unwrap()
+            .is_none());
+
+        let request = DeleteResourceRequest { name: b.name };
+        svc.delete_resource(test::request(&svc, request, Some(&owner)).await)
+            .await
+            .unwrap();
+        assert!(svc.repo.find_resource_blob(&hash).await.unwrap().is_none());
+
+        // 内容已释放时不能再引用
+        let linked = ResourceModel {
+            uid: "linked".to_owned(),
+            creator_id: owner.id,
+            hash,
+            ..Default::default()
+        };
+        let created = svc.repo.create_resource_with_blob(linked, None).await;
+        assert!(created.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn image_location() {
+        let svc = test::service().await;
+        let host = test::create_user(&svc, "host", Role::Host).await;
+        let owner = test::create_user(&svc, "owner", Role::User).await;
+        let setting = StoragePrivacySetting {
+            strip_image_metadata: true,
+            keep_image_location: true,
+        };
+        let denied = svc.set_storage_privacy(&owner, setting.clone()).await;
+        assert!(matches!(denied, Err(Error::Permission { .. })));
+        svc.set_storage_privacy(&host, setting).await.unwrap();
+        assert!(
+            svc.get_storage_privacy(&host)
+                .await
+                .unwrap()
+                .keep_image_location
+        );
+
+        // 上传时未关联 memo，位置随资源保存
+        let request = CreateResourceRequest {
+            resource: Some(Resource {
+                filename: "a.jpg".to_owned(),
+                r#type: "image/jpeg".to_owned(),
+                content: metadata::test::jpeg(),
+                ..Default::default()
+            }),
+        };
+        let resource = svc
+            .create_resource(test::request(&svc, request, Some(&owner)).await)
+            .await
+            .unwrap()
+            .into_inner();
+        let id = resource.get_id().unwrap();
+        let stored = svc.get_resource_by_id(id).await.unwrap();
+        let location = stored.location.unwrap();
+        assert!((location.latitude - 30.26).abs() < 1e-9);
+
+        let request = CreateMemoRequest {
+            memo: Some(Memo {
+                content: "memo".to_owned(),
+                visibility: Visibility::Private.into(),
+                ..Default::default()
+            }),
+        };
+        let memo = svc
+            .create_memo(test::request(&svc, request, Some(&owner)).await)
+            .await
+            .unwrap()
+            .into_inner();
+        let request = SetMemoResourcesRequest {
+            name: memo.name.clone(),
+            resources: vec![resource],
+        };
+        svc.set_memo_resources(test::request(&svc, request, Some(&owner)).await)
+            .await
+            .unwrap();
+
+        let request = GetMemoRequest { name: memo.name };
+        let memo = svc
+            .get_memo(test::request(&svc, request, Some(&owner)).await)
+            .await
+            .unwrap()
+            .into_inner();
+        let location = memo.location.unwrap();
+        assert!((location.longitude + 120.15).abs() < 1e-9);
+    }
 }
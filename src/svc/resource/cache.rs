@@ -0,0 +1,256 @@
+//! 资源与缩略图的本地磁盘缓存，按最近使用淘汰
+
+use std::collections::{BTreeMap, HashMap};
+use std::io;
+use std::path::PathBuf;
+use std::sync::{Mutex, MutexGuard};
+use std::time::SystemTime;
+
+use tokio::fs::{self, File};
+use tokio::io::AsyncWriteExt;
+
+use super::thumbnail;
+use crate::util;
+
+const RESOURCE_PATH: &str = ".resource_cache";
+const THUMBNAIL_IMAGE_PATH: &str = ".thumbnail_cache";
+const TMP_PREFIX: &str = ".tmp-";
+const MEBI_BYTE: u64 = 1024 * 1024;
+pub const DEFAULT_RESOURCE_CACHE_MIB: u64 = 1024;
+pub const DEFAULT_THUMBNAIL_CACHE_MIB: u64 = 256;
+
+#[derive(Debug)]
+pub struct ResourceCache {
+    pub resource: DiskCache,
+    pub thumbnail: DiskCache,
+}
+
+impl ResourceCache {
+    pub fn new(resource_mib: u64, thumbnail_mib: u64) -> Self {
+        Self {
+            resource: DiskCache::new(RESOURCE_PATH, resource_mib * MEBI_BYTE),
+            thumbnail: DiskCache::new(THUMBNAIL_IMAGE_PATH, thumbnail_mib * MEBI_BYTE),
+        }
+    }
+
+    /// 删除资源缓存及其全部尺寸与格式的缩略图
+    pub async fn remove(&self, key: &str) {
+        self.resource.remove(key).await;
+        for key in thumbnail::variants(key) {
+            self.thumbnail.remove(&key).await;
+        }
+    }
+}
+
+impl Default for ResourceCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_RESOURCE_CACHE_MIB, DEFAULT_THUMBNAIL_CACHE_MIB)
+    }
+}
+
+#[derive(Debug)]
+pub struct DiskCache {
+    dir: PathBuf,
+    capacity: u64,
+    index: Mutex<Index>,
+}
+
+impl DiskCache {
+    pub fn new(dir: impl Into<PathBuf>, capacity: u64) -> Self {
+        Self {
+            dir: dir.into(),
+            capacity,
+            index: Mutex::default(),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Index> {
+        self.index.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(key)
+    }
+
+    pub async fn get(&self, key: &str) -> Option<File> {
+        if !self.lock().touch(key) {
+            return None;
+        }
+        match File::open(self.path(key)).await {
+            Ok(file) => Some(file),
+            Err(_) => {
+                self.lock().remove(key);
+                None
+            }
+        }
+    }
+
+    /// 先写临时文件再重命名，并发读取不会看到写了一半的内容
+    pub async fn put(&self, key: &str, bytes: &[u8]) -> io::Result<File> {
+        fs::create_dir_all(&self.dir).await?;
+        let tmp = self.dir.join(format!("{TMP_PREFIX}{}", util::uuid()));
+        let written = async {
+            let mut file = File::create(&tmp).await?;
+            file.write_all(bytes).await?;
+            file.sync_all().await?;
+            fs::rename(&tmp, self.path(key)).await
+        }
+        .await;
+        if let Err(e) = written {
+            let _ = fs::remove_file(&tmp).await;
+            return Err(e);
+        }
+
+        // 先打开再淘汰，超出容量的单个文件也能读取
+        let file = File::open(self.path(key)).await?;
+        let evicted = {
+            let mut index = self.lock();
+            index.insert(key.to_owned(), bytes.len() as u64);
+            index.evict(self.capacity, key)
+        };
+        for key in evicted {
+            let _ = fs::remove_file(self.path(&key)).await;
+        }
+        Ok(file)
+    }
+
+    pub async fn remove(&self, key: &str) {
+        self.lock().remove(key);
+        let _ = fs::remove_file(self.path(key)).await;
+    }
+
+    /// 启动时清理临时文件与已失效的缓存，其余按修改时间载入索引
+    pub async fn sweep(&self, is_live: impl Fn(&str) -> bool) -> io::Result<()> {
+        if !fs::try_exists(&self.dir).await? {
+            return Ok(());
+        }
+
+        let mut files = Vec::new();
+        let mut dir = fs::read_dir(&self.dir).await?;
+        while let Some(entry) = dir.next_entry().await? {
+            let metadata = entry.metadata().await?;
+            if !metadata.is_file() {
+                continue;
+            }
+            let key = entry.file_name().to_string_lossy().into_owned();
+            if key.starts_with(TMP_PREFIX) || !is_live(&key) {
+                let _ = fs::remove_file(entry.path()).await;
+                continue;
+            }
+            let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+            files.push((modified, key, metadata.len()));
+        }
+        files.sort();
+
+        let evicted = {
+            let mut index = self.lock();
+            for (_, key, size) in files {
+                index.insert(key, size);
+            }
+            index.evict(self.capacity, "")
+        };
+        for key in evicted {
+            let _ = fs::remove_file(self.path(&key)).await;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default)]
+struct Index {
+    entries: HashMap<String, Entry>,
+    /// 使用序号到 key，序号越小越久未使用
+    recency: BTreeMap<u64, String>,
+    tick: u64,
+    total: u64,
+}
+
+#[derive(Debug)]
+struct Entry {
+    size: u64,
+    tick: u64,
+}
+
+impl Index {
+    fn next_tick(&mut self) -> u64 {
+        self.tick += 1;
+        self.tick
+    }
+
+    fn touch(&mut self, key: &str) -> bool {
+        let tick = self.next_tick();
+        let Some(entry) = self.entries.get_mut(key) else {
+            return false;
+        };
+        self.recency.remove(&entry.tick);
+        entry.tick = tick;
+        self.recency.insert(tick, key.to_owned());
+        true
+    }
+
+    fn insert(&mut self, key: String, size: u64) {
+        self.remove(&key);
+        let tick = self.next_tick();
+        self.total += size;
+        self.recency.insert(tick, key.clone());
+        self.entries.insert(key, Entry { size, tick });
+    }
+
+    fn remove(&mut self, key: &str) -> bool {
+        let Some(entry) = self.entries.remove(key) else {
+            return false;
+        };
+        self.recency.remove(&entry.tick);
+        self.total -= entry.size;
+        true
+    }
+
+    /// 淘汰最久未使用的条目直到不超过容量，keep 不会被淘汰
+    fn evict(&mut self, capacity: u64, keep: &str) -> Vec<String> {
+        let mut evicted = Vec::new();
+        let mut skipped = None;
+        while self.total > capacity {
+            let Some((_, key)) = self.recency.pop_first() else {
+                break;
+            };
+            if key == keep {
+                skipped = self.entries.get(&key).map(|e| (e.tick, key));
+                continue;
+            }
+            if let Some(entry) = self.entries.remove(&key) {
+                self.total -= entry.size;
+            }
+            evicted.push(key);
+        }
+        if let Some((tick, key)) = skipped {
+            self.recency.insert(tick, key);
+        }
+        evicted
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Index;
+
+    #[test]
+    fn lru_eviction() {
+        let mut index = Index::default();
+        index.insert("a".to_owned(), 4);
+        index.insert("b".to_owned(), 4);
+        index.insert("c".to_owned(), 4);
+        assert!(index.touch("a"));
+
+        assert_eq!(index.evict(8, "c"), vec!["b".to_owned()]);
+        assert_eq!(index.total, 8);
+
+        // 正在写入的条目即使超出容量也保留
+        index.insert("d".to_owned(), 16);
+        assert_eq!(index.evict(8, "d"), vec!["c".to_owned(), "a".to_owned()]);
+        assert!(index.touch("d"));
+        assert!(!index.touch("a"));
+
+        assert!(index.remove("d"));
+        assert_eq!(index.total, 0);
+    }
+}
@@ -0,0 +1,473 @@
+//! 移除上传图片中的 EXIF/XMP 等元数据，仅保留方向，可选取出 GPS 坐标
+
+const ORIENTATION: u16 = 0x0112;
+const GPS_IFD: u16 = 0x8825;
+const GPS_LATITUDE_REF: u16 = 1;
+const GPS_LATITUDE: u16 = 2;
+const GPS_LONGITUDE_REF: u16 = 3;
+const GPS_LONGITUDE: u16 = 4;
+const EXIF_HEADER: &[u8] = b"Exif\0\0";
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+#[derive(Debug, Default)]
+pub struct Stripped {
+    pub blob: Vec<u8>,
+    /// (纬度, 经度)
+    pub location: Option<(f64, f64)>,
+}
+
+pub fn supported(mime: &str) -> bool {
+    matches!(mime, "image/jpeg" | "image/png" | "image/webp")
+}
+
+/// 无法解析时返回 None，由调用方决定是否保留原内容
+pub fn strip(blob: &[u8], mime: &str) -> Option<Stripped> {
+    match mime {
+        "image/jpeg" => strip_jpeg(blob),
+        "image/png" => strip_png(blob),
+        "image/webp" => strip_webp(blob),
+        _ => None,
+    }
+}
+
+/// 去掉 APP1(Exif/XMP)、APP13(IPTC) 与注释段，SOS 之后的图像数据原样保留
+fn strip_jpeg(blob: &[u8]) -> Option<Stripped> {
+    if !blob.starts_with(&[0xFF, 0xD8]) {
+        return None;
+    }
+    let mut out = Vec::with_capacity(blob.len());
+    out.extend_from_slice(&blob[..2]);
+    // 方向写回到 SOI 或 JFIF 的 APP0 之后
+    let mut insert_at = out.len();
+    let mut exif = Exif::default();
+    let mut pos = 2;
+    loop {
+        if *blob.get(pos)? != 0xFF {
+            return None;
+        }
+        let marker = *blob.get(pos + 1)?;
+        match marker {
+            // 填充字节
+            0xFF => {
+                pos += 1;
+                continue;
+            }
+            0xDA | 0xD9 => {
+                out.extend_from_slice(&blob[pos..]);
+                break;
+            }
+            _ => (),
+        }
+        let len = u16::from_be_bytes([*blob.get(pos + 2)?, *blob.get(pos + 3)?]) as usize;
+        if len < 2 {
+            return None;
+        }
+        let segment = blob.get(pos..pos + 2 + len)?;
+        let data = &segment[4..];
+        match marker {
+            0xE1 => {
+                if let Some(tiff) = data.strip_prefix(EXIF_HEADER) {
+                    exif.merge(parse_exif(tiff));
+                }
+            }
+            0xED | 0xFE => (),
+            0xE0 if insert_at == out.len() => {
+                out.extend_from_slice(segment);
+                insert_at = out.len();
+            }
+            _ => out.extend_from_slice(segment),
+        }
+        pos += 2 + len;
+    }
+
+    if let Some(orientation) = exif.rotated() {
+        let data = [EXIF_HEADER, &orientation_exif(orientation)].concat();
+        let len = (data.len() + 2) as u16;
+        let segment = [&[0xFF, 0xE1][..], &len.to_be_bytes(), &data].concat();
+        out.splice(insert_at..insert_at, segment);
+    }
+    Some(Stripped {
+        blob: out,
+        location: exif.location,
+    })
+}
+
+/// 去掉 eXIf 与文本块，XMP 保存在 iTXt 中
+fn strip_png(blob: &[u8]) -> Option<Stripped> {
+    let body = blob.strip_prefix(PNG_SIGNATURE)?;
+    let mut out = Vec::with_capacity(blob.len());
+    out.extend_from_slice(PNG_SIGNATURE);
+    // eXIf 必须位于 IDAT 之前
+    let mut idat_at = None;
+    let mut exif = Exif::default();
+    let mut pos = 0;
+    while pos < body.len() {
+        let len = u32::from_be_bytes(body.get(pos..pos + 4)?.try_into().ok()?) as usize;
+        let chunk = body.get(pos..pos.checked_add(12 + len)?)?;
+        match &chunk[4..8] {
+            b"eXIf" => exif.merge(parse_exif(&chunk[8..8 + len])),
+            b"tEXt" | b"zTXt" | b"iTXt" => (),
+            kind => {
+                if kind == b"IDAT" && idat_at.is_none() {
+                    idat_at = Some(out.len());
+                }
+                out.extend_from_slice(chunk);
+            }
+        }
+        pos += 12 + len;
+    }
+
+    if let (Some(orientation), Some(idat_at)) = (exif.rotated(), idat_at) {
+        let data = [&b"eXIf"[..], &orientation_exif(orientation)].concat();
+        let len = (data.len() - 4) as u32;
+        let chunk = [&len.to_be_bytes()[..], &data, &crc32(&data).to_be_bytes()].concat();
+        out.splice(idat_at..idat_at, chunk);
+    }
+    Some(Stripped {
+        blob: out,
+        location: exif.location,
+    })
+}
+
+/// 去掉 EXIF 与 XMP 块并更新 VP8X 标志位和 RIFF 长度
+fn strip_webp(blob: &[u8]) -> Option<Stripped> {
+    if blob.get(..4)? != b"RIFF" || blob.get(8..12)? != b"WEBP" {
+        return None;
+    }
+    let mut out = Vec::with_capacity(blob.len());
+    out.extend_from_slice(&blob[..12]);
+    let mut vp8x = None;
+    let mut exif = Exif::default();
+    let mut pos = 12;
+    while pos < blob.len() {
+        let len = u32::from_le_bytes(blob.get(pos + 4..pos + 8)?.try_into().ok()?) as usize;
+        let data = blob.get(pos + 8..pos.checked_add(8 + len)?)?;
+        // 块按偶数字节对齐，末尾的填充字节可能缺失
+        let end = (pos + 8 + len + (len & 1)).min(blob.len());
+        match &blob[pos..pos + 4] {
+            b"EXIF" => exif.merge(parse_exif(data.strip_prefix(EXIF_HEADER).unwrap_or(data))),
+            b"XMP " => (),
+            kind => {
+                if kind == b"VP8X" {
+                    vp8x = Some(out.len() + 8);
+                }
+                out.extend_from_slice(&blob[pos..end]);
+            }
+        }
+        pos = end;
+    }
+
+    // EXIF 块只允许出现在扩展格式中
+    if let Some(flags) = vp8x {
+        out[flags] &= !(0x04 | 0x08);
+        if let Some(orientation) = exif.rotated() {
+            out[flags] |= 0x08;
+            let data = orientation_exif(orientation);
+            out.extend_from_slice(b"EXIF");
+            out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            out.extend_from_slice(&data);
+        }
+    }
+    let size = (out.len() - 8) as u32;
+    out[4..8].copy_from_slice(&size.to_le_bytes());
+    Some(Stripped {
+        blob: out,
+        location: exif.location,
+    })
+}
+
+#[derive(Debug, Default)]
+struct Exif {
+    orientation: Option<u16>,
+    location: Option<(f64, f64)>,
+}
+
+impl Exif {
+    fn merge(&mut self, other: Exif) {
+        self.orientation = self.orientation.or(other.orientation);
+        self.location = self.location.or(other.location);
+    }
+
+    /// 非默认方向才需要写回
+    fn rotated(&self) -> Option<u16> {
+        self.orientation.filter(|o| *o != 1)
+    }
+}
+
+struct Tiff<'a> {
+    data: &'a [u8],
+    little_endian: bool,
+}
+
+impl<'a> Tiff<'a> {
+    fn new(data: &'a [u8]) -> Option<Self> {
+        let little_endian = match data.get(..4)? {
+            b"II*\0" => true,
+            b"MM\0*" => false,
+            _ => return None,
+        };
+        Some(Self {
+            data,
+            little_endian,
+        })
+    }
+
+    fn u16(&self, offset: usize) -> Option<u16> {
+        let bytes = self.data.get(offset..offset + 2)?.try_into().ok()?;
+        Some(if self.little_endian {
+            u16::from_le_bytes(bytes)
+        } else {
+            u16::from_be_bytes(bytes)
+        })
+    }
+
+    fn u32(&self, offset: usize) -> Option<u32> {
+        let bytes = self.data.get(offset..offset + 4)?.try_into().ok()?;
+        Some(if self.little_endian {
+            u32::from_le_bytes(bytes)
+        } else {
+            u32::from_be_bytes(bytes)
+        })
+    }
+
+    /// IFD 条目的 (tag, 数量, 值或偏移所在位置)
+    fn entries(&self, ifd: usize) -> impl Iterator<Item = (u16, u32, usize)> + '_ {
+        let count = self.u16(ifd).unwrap_or(0) as usize;
+        (0..count).filter_map(move |i| {
+            let entry = ifd + 2 + i * 12;
+            Some((self.u16(entry)?, self.u32(entry + 4)?, entry + 8))
+        })
+    }
+
+    fn rational(&self, offset: usize) -> Option<f64> {
+        let numerator = self.u32(offset)?;
+        let denominator = self.u32(offset + 4)?;
+        (denominator != 0).then_some(numerator as f64 / denominator as f64)
+    }
+
+    /// 度、分、秒三个有理数
+    fn degrees(&self, count: u32, value: usize) -> Option<f64> {
+        if count != 3 {
+            return None;
+        }
+        let offset = self.u32(value)? as usize;
+        Some(
+            self.rational(offset)?
+                + self.rational(offset + 8)? / 60.0
+                + self.rational(offset + 16)? / 3600.0,
+        )
+    }
+}
+
+fn parse_exif(data: &[u8]) -> Exif {
+    let mut exif = Exif::default();
+    let Some(tiff) = Tiff::new(data) else {
+        return exif;
+    };
+    let Some(ifd0) = tiff.u32(4) else {
+        return exif;
+    };
+
+    let mut gps = None;
+    for (tag, _, value) in tiff.entries(ifd0 as usize) {
+        match tag {
+            ORIENTATION => exif.orientation = tiff.u16(value).filter(|o| (1..=8).contains(o)),
+            GPS_IFD => gps = tiff.u32(value),
+            _ => (),
+        }
+    }
+
+    if let Some(gps) = gps {
+        let (mut latitude, mut longitude) = (None, None);
+        let (mut south, mut west) = (false, false);
+        for (tag, count, value) in tiff.entries(gps as usize) {
+            match tag {
+                GPS_LATITUDE_REF => south = tiff.data.get(value) == Some(&b'S'),
+                GPS_LATITUDE => latitude = tiff.degrees(count, value),
+                GPS_LONGITUDE_REF => west = tiff.data.get(value) == Some(&b'W'),
+                GPS_LONGITUDE => longitude = tiff.degrees(count, value),
+                _ => (),
+            }
+        }
+        if let (Some(latitude), Some(longitude)) = (latitude, longitude) {
+            let latitude = if south { -latitude } else { latitude };
+            let longitude = if west { -longitude } else { longitude };
+            exif.location = Some((latitude, longitude));
+        }
+    }
+    exif
+}
+
+/// 只含方向一个条目的 TIFF 数据
+fn orientation_exif(orientation: u16) -> Vec<u8> {
+    let mut tiff = b"MM\0*\0\0\0\x08\0\x01".to_vec();
+    tiff.extend_from_slice(&ORIENTATION.to_be_bytes());
+    // SHORT，数量 1
+    tiff.extend_from_slice(&[0, 3, 0, 0, 0, 1]);
+    tiff.extend_from_slice(&orientation.to_be_bytes());
+    // 值的填充与下一个 IFD 偏移
+    tiff.extend_from_slice(&[0; 6]);
+    tiff
+}
+
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::{crc32, parse_exif, strip, EXIF_HEADER, PNG_SIGNATURE};
+
+    /// 小端 TIFF：方向 6，北纬 30°15'36"，西经 120°9'0"
+    fn tiff() -> Vec<u8> {
+        let mut t = b"II*\0".to_vec();
+        t.extend(8u32.to_le_bytes());
+        let entry = |t: &mut Vec<u8>, tag: u16, kind: u16, count: u32, value: [u8; 4]| {
+            t.extend(tag.to_le_bytes());
+            t.extend(kind.to_le_bytes());
+            t.extend(count.to_le_bytes());
+            t.extend(value);
+        };
+        t.extend(2u16.to_le_bytes());
+        entry(&mut t, 0x0112, 3, 1, [6, 0, 0, 0]);
+        entry(&mut t, 0x8825, 4, 1, 38u32.to_le_bytes());
+        t.extend(0u32.to_le_bytes());
+        t.extend(4u16.to_le_bytes());
+        entry(&mut t, 1, 2, 2, *b"N\0\0\0");
+        entry(&mut t, 2, 5, 3, 92u32.to_le_bytes());
+        entry(&mut t, 3, 2, 2, *b"W\0\0\0");
+        entry(&mut t, 4, 5, 3, 116u32.to_le_bytes());
+        t.extend(0u32.to_le_bytes());
+        for n in [30u32, 15, 36, 120, 9, 0] {
+            t.extend(n.to_le_bytes());
+            t.extend(1u32.to_le_bytes());
+        }
+        t
+    }
+
+    /// 带方向、GPS 与 XMP 的 JPEG
+    pub fn jpeg() -> Vec<u8> {
+        let segment = |marker: u8, data: &[u8]| {
+            let len = (data.len() + 2) as u16;
+            [&[0xFF, marker][..], &len.to_be_bytes(), data].concat()
+        };
+        [
+            &[0xFF, 0xD8][..],
+            &segment(0xE0, b"JFIF\0"),
+            &segment(0xE1, &[EXIF_HEADER, &tiff()].concat()),
+            &segment(0xE1, b"http://ns.adobe.com/xap/1.0/\0<x/>"),
+            &segment(0xDB, &[0; 4]),
+            &[0xFF, 0xDA, 1, 2, 3, 0xFF, 0xD9],
+        ]
+        .concat()
+    }
+
+    #[test]
+    fn exif_gps() {
+        let exif = parse_exif(&tiff());
+        assert_eq!(exif.orientation, Some(6));
+        let (latitude, longitude) = exif.location.unwrap();
+        assert!((latitude - 30.26).abs() < 1e-9);
+        assert!((longitude + 120.15).abs() < 1e-9);
+    }
+
+    #[test]
+    fn strip_jpeg() {
+        let stripped = strip(&jpeg(), "image/jpeg").unwrap();
+        assert!(stripped.location.is_some());
+        let exif = parse_exif(&stripped.blob[21..]);
+        assert_eq!(exif.orientation, Some(6));
+        assert!(exif.location.is_none());
+        assert!(!stripped.blob.windows(4).any(|w| w == b"xap/"));
+        assert!(stripped.blob.ends_with(&[0xFF, 0xDA, 1, 2, 3, 0xFF, 0xD9]));
+        assert!(strip(b"not a jpeg", "image/jpeg").is_none());
+    }
+
+    #[test]
+    fn strip_png() {
+        let chunk = |kind: &[u8], data: &[u8]| {
+            let body = [kind, data].concat();
+            let len = data.len() as u32;
+            [&len.to_be_bytes()[..], &body, &crc32(&body).to_be_bytes()].concat()
+        };
+        let png = [
+            PNG_SIGNATURE,
+            &chunk(b"IHDR", &[0; 13]),
+            &chunk(b"eXIf", &tiff()),
+            &chunk(b"tEXt", b"Comment\0hello"),
+            &chunk(b"iTXt", b"XML:com.adobe.xmp\0\0\0\0\0<x/>"),
+            &chunk(b"IDAT", &[1, 2, 3]),
+            &chunk(b"IEND", &[]),
+        ]
+        .concat();
+
+        let stripped = strip(&png, "image/png").unwrap();
+        assert!(stripped.location.is_some());
+        let blob = &stripped.blob;
+        assert!(!blob.windows(4).any(|w| w == b"tEXt" || w == b"iTXt"));
+        let exif_at = blob.windows(4).position(|w| w == b"eXIf").unwrap();
+        let idat_at = blob.windows(4).position(|w| w == b"IDAT").unwrap();
+        assert!(exif_at < idat_at);
+        let len = u32::from_be_bytes(blob[exif_at - 4..exif_at].try_into().unwrap()) as usize;
+        let exif = parse_exif(&blob[exif_at + 4..exif_at + 4 + len]);
+        assert_eq!(exif.orientation, Some(6));
+        assert!(exif.location.is_none());
+        let crc = &blob[exif_at + 4 + len..exif_at + 8 + len];
+        assert_eq!(crc, crc32(&blob[exif_at..exif_at + 4 + len]).to_be_bytes());
+        assert!(blob.ends_with(&chunk(b"IEND", &[])));
+        assert!(strip(b"not a png", "image/png").is_none());
+    }
+
+    #[test]
+    fn strip_webp() {
+        let chunk = |kind: &[u8], data: &[u8]| {
+            let len = data.len() as u32;
+            let pad: &[u8] = if data.len() % 2 == 1 { &[0] } else { &[] };
+            [kind, &len.to_le_bytes(), data, pad].concat()
+        };
+        let mut vp8x = vec![0x04 | 0x08, 0, 0, 0];
+        vp8x.extend([0; 6]);
+        let body = [
+            &b"WEBP"[..],
+            &chunk(b"VP8X", &vp8x),
+            &chunk(b"VP8 ", &[1, 2, 3]),
+            &chunk(b"EXIF", &[EXIF_HEADER, &tiff()].concat()),
+            &chunk(b"XMP ", b"<x/>"),
+        ]
+        .concat();
+        let webp = [&b"RIFF"[..], &(body.len() as u32).to_le_bytes(), &body].concat();
+
+        let stripped = strip(&webp, "image/webp").unwrap();
+        assert!(stripped.location.is_some());
+        let blob = &stripped.blob;
+        assert_eq!(
+            u32::from_le_bytes(blob[4..8].try_into().unwrap()) as usize,
+            blob.len() - 8
+        );
+        // 只保留方向，XMP 标志位清除
+        assert_eq!(blob[20], 0x08);
+        assert!(!blob.windows(4).any(|w| w == b"XMP "));
+        assert!(blob.windows(8).any(|w| w == b"VP8 \x03\0\0\0"));
+        let exif_at = blob.windows(4).position(|w| w == b"EXIF").unwrap();
+        let exif = parse_exif(&blob[exif_at + 8..]);
+        assert_eq!(exif.orientation, Some(6));
+        assert!(exif.location.is_none());
+        assert!(strip(b"RIFF\0\0\0\0WAVE", "image/webp").is_none());
+    }
+
+    #[test]
+    fn png_crc() {
+        assert_eq!(crc32(b"IEND"), 0xAE42_6082);
+    }
+}
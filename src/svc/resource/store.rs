@@ -0,0 +1,537 @@
+//! 资源内容的存储后端：数据库、本地文件与 S3 兼容存储
+
+use std::path::{Component, Path, PathBuf};
+use std::pin::Pin;
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures::{stream, Stream, TryStreamExt};
+use s3::{creds::Credentials, Bucket, Region};
+use snafu::{ensure, OptionExt, ResultExt, Snafu};
+use time::OffsetDateTime;
+use tokio::{fs, io::AsyncWriteExt};
+use tokio_util::io::StreamReader;
+
+use crate::{
+    model::{
+        gen::{
+            resource_payload::{Payload, S3Object},
+            workspace_storage_setting::StorageType,
+            ResourcePayload, ResourceStorageType, StorageS3Config, WorkspaceStorageSetting,
+        },
+        resource::Resource as ResourceModel,
+    },
+    util,
+};
+
+/// 本地存储的根目录，filepath_template 生成的路径相对于此
+const LOCAL_STORE_PATH: &str = ".resource_store";
+const DEFAULT_FILEPATH_TEMPLATE: &str = "assets/{timestamp}_{filename}";
+
+pub type ByteStream<'a> = Pin<Box<dyn Stream<Item = std::io::Result<Bytes>> + Send + 'a>>;
+
+#[async_trait]
+pub trait ResourceStore: Send + Sync {
+    /// 保存 resource.blob
+    async fn save(&self, resource: &mut ResourceModel) -> Result<(), Error> {
+        let blob = Bytes::from(std::mem::take(&mut resource.blob));
+        self.save_stream(resource, Box::pin(stream::once(async { Ok(blob) })))
+            .await
+    }
+    /// 边接收边保存，并回填 resource 的 storage_type、reference 与 payload
+    async fn save_stream(
+        &self,
+        resource: &mut ResourceModel,
+        stream: ByteStream<'_>,
+    ) -> Result<(), Error>;
+    /// resource 需包含 blob 列
+    async fn load(&self, resource: ResourceModel) -> Result<Vec<u8>, Error>;
+    async fn delete(&self, resource: &ResourceModel) -> Result<(), Error>;
+}
+
+/// 新资源按工作区存储设置选择后端
+pub fn for_setting(setting: &WorkspaceStorageSetting) -> Result<Box<dyn ResourceStore>, Error> {
+    let template = setting.filepath_template.clone();
+    match setting.storage_type() {
+        StorageType::Local => Ok(Box::new(LocalStore::new(template))),
+        StorageType::S3 => {
+            let config = setting.s3_config.clone().context(MissingS3Config)?;
+            Ok(Box::new(S3Store::new(config, template)?))
+        }
+        StorageType::Database | StorageType::Unspecified => Ok(Box::new(DatabaseStore)),
+    }
+}
+
+/// 已有资源按其 storage_type 选择后端，S3 使用保存时的配置
+pub fn for_resource(resource: &ResourceModel) -> Result<Box<dyn ResourceStore>, Error> {
+    match resource.storage_type {
+        ResourceStorageType::Unspecified => Ok(Box::new(DatabaseStore)),
+        ResourceStorageType::Local => Ok(Box::new(LocalStore::new(String::new()))),
+        ResourceStorageType::S3 => {
+            let S3Object { s3_config, .. } = s3_object(&resource.payload)?;
+            let config = s3_config.clone().context(MissingS3Config)?;
+            Ok(Box::new(S3Store::new(config, String::new())?))
+        }
+        ResourceStorageType::External => UnsupportedStorage {
+            storage_type: resource.storage_type.as_str_name(),
+        }
+        .fail(),
+    }
+}
+
+/// 内容保存在 resource.blob 列，流式上传也需在内存中拼接
+pub struct DatabaseStore;
+
+#[async_trait]
+impl ResourceStore for DatabaseStore {
+    async fn save_stream(
+        &self,
+        resource: &mut ResourceModel,
+        stream: ByteStream<'_>,
+    ) -> Result<(), Error> {
+        let chunks: Vec<Bytes> = stream.try_collect().await.context(ReadStream)?;
+        resource.blob = chunks.concat();
+        resource.storage_type = ResourceStorageType::Unspecified;
+        Ok(())
+    }
+
+    async fn load(&self, resource: ResourceModel) -> Result<Vec<u8>, Error> {
+        Ok(resource.blob)
+    }
+
+    async fn delete(&self, _resource: &ResourceModel) -> Result<(), Error> {
+        // 随记录一并删除
+        Ok(())
+    }
+}
+
+/// 内容保存在本地文件，reference 为相对路径
+pub struct LocalStore {
+    root: PathBuf,
+    template: String,
+}
+
+impl LocalStore {
+    pub fn new(template: String) -> Self {
+        Self::with_root(PathBuf::from(LOCAL_STORE_PATH), template)
+    }
+
+    fn with_root(root: PathBuf, template: String) -> Self {
+        Self { root, template }
+    }
+
+    fn full_path(&self, reference: &str) -> Result<PathBuf, Error> {
+        ensure!(is_relative_path(reference), InvalidReference { reference });
+        Ok(self.root.join(reference))
+    }
+}
+
+#[async_trait]
+impl ResourceStore for LocalStore {
+    async fn save_stream(
+        &self,
+        resource: &mut ResourceModel,
+        mut stream: ByteStream<'_>,
+    ) -> Result<(), Error> {
+        let reference = render_filepath(
+            &self.template,
+            &resource.filename,
+            OffsetDateTime::now_utc(),
+            &util::uuid(),
+        );
+        let mut path = self.full_path(&reference)?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).await.context(WriteFile)?;
+        }
+
+        // 同名文件已存在时在文件名前加 uuid，不覆盖其他资源的内容
+        let mut reference = reference;
+        let mut file = match create_new(&path).await {
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                reference = unique_reference(&reference, &util::uuid());
+                path = self.full_path(&reference)?;
+                create_new(&path).await
+            }
+            file => file,
+        }
+        .context(WriteFile)?;
+        let written = async {
+            while let Some(chunk) = stream.try_next().await? {
+                file.write_all(&chunk).await?;
+            }
+            file.flush().await
+        }
+        .await;
+        if let Err(e) = written {
+            // 不保留写了一半的文件
+            let _ = fs::remove_file(&path).await;
+            return Err(e).context(WriteFile);
+        }
+
+        resource.blob = Vec::new();
+        resource.reference = reference;
+        resource.storage_type = ResourceStorageType::Local;
+        Ok(())
+    }
+
+    async fn load(&self, resource: ResourceModel) -> Result<Vec<u8>, Error> {
+        let path = self.full_path(&resource.reference)?;
+        fs::read(path).await.context(ReadFile)
+    }
+
+    async fn delete(&self, resource: &ResourceModel) -> Result<(), Error> {
+        let path = self.full_path(&resource.reference)?;
+        match fs::remove_file(path).await {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e).context(DeleteFile),
+            _ => Ok(()),
+        }
+    }
+}
+
+async fn create_new(path: &Path) -> std::io::Result<fs::File> {
+    fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(path)
+        .await
+}
+
+/// 在路径最后一段前加上 uuid
+fn unique_reference(reference: &str, uuid: &str) -> String {
+    match reference.rsplit_once('/') {
+        Some((dir, filename)) => format!("{dir}/{uuid}_{filename}"),
+        None => format!("{uuid}_{reference}"),
+    }
+}
+
+/// 内容保存在 S3 兼容存储，payload 记录 bucket 配置与 key
+pub struct S3Store {
+    bucket: Box<Bucket>,
+    config: StorageS3Config,
+    template: String,
+}
+
+impl S3Store {
+    pub fn new(config: StorageS3Config, template: String) -> Result<Self, Error> {
+        let region = Region::Custom {
+            region: config.region.clone(),
+            endpoint: config.endpoint.clone(),
+        };
+        let credentials = Credentials::new(
+            Some(&config.access_key_id),
+            Some(&config.access_key_secret),
+            None,
+            None,
+            None,
+        )
+        .context(S3Credentials)?;
+        let mut bucket = Bucket::new(&config.bucket, region, credentials).context(S3)?;
+        if config.use_path_style {
+            // MinIO 等自建服务通常只支持路径风格
+            bucket = bucket.with_path_style();
+        }
+        Ok(Self {
+            bucket,
+            config,
+            template,
+        })
+    }
+}
+
+#[async_trait]
+impl ResourceStore for S3Store {
+    async fn save_stream(
+        &self,
+        resource: &mut ResourceModel,
+        stream: ByteStream<'_>,
+    ) -> Result<(), Error> {
+        let key = render_filepath(
+            &self.template,
+            &resource.filename,
+            OffsetDateTime::now_utc(),
+            &util::uuid(),
+        );
+        // 大文件走分片上传，内存中只保留单个分片
+        let mut reader = StreamReader::new(stream);
+        self.bucket
+            .put_object_stream_with_content_type(&mut reader, &key, &resource.r#type)
+            .await
+            .context(S3)?;
+
+        resource.blob = Vec::new();
+        resource.reference = key.clone();
+        resource.storage_type = ResourceStorageType::S3;
+        resource.payload = ResourcePayload {
+            payload: Some(Payload::S3Object(S3Object {
+                s3_config: Some(self.config.clone()),
+                key,
+                last_presigned_time: None,
+            })),
+        };
+        Ok(())
+    }
+
+    async fn load(&self, resource: ResourceModel) -> Result<Vec<u8>, Error> {
+        let key = &s3_object(&resource.payload)?.key;
+        let data = self.bucket.get_object(key).await.context(S3)?;
+        Ok(data.to_vec())
+    }
+
+    async fn delete(&self, resource: &ResourceModel) -> Result<(), Error> {
+        let key = &s3_object(&resource.payload)?.key;
+        self.bucket.delete_object(key).await.context(S3)?;
+        Ok(())
+    }
+}
+
+fn s3_object(payload: &ResourcePayload) -> Result<&S3Object, Error> {
+    match &payload.payload {
+        Some(Payload::S3Object(object)) => Ok(object),
+        _ => MissingS3Object.fail(),
+    }
+}
+
+/// 按模板生成存储路径，支持 {filename} {timestamp} {year} {month} {day}
+/// {hour} {minute} {second} {uuid}，模板不含 {filename} 时作为目录
+fn render_filepath(template: &str, filename: &str, now: OffsetDateTime, uuid: &str) -> String {
+    let template = if template.trim().is_empty() {
+        DEFAULT_FILEPATH_TEMPLATE.to_owned()
+    } else if !template.contains("{filename}") {
+        format!("{}/{{filename}}", template.trim_end_matches('/'))
+    } else {
+        template.to_owned()
+    };
+    // 文件名只保留最后一段，避免跳出模板目录
+    let filename = Path::new(filename)
+        .file_name()
+        .and_then(|f| f.to_str())
+        .unwrap_or(uuid);
+
+    template
+        .replace("{filename}", filename)
+        .replace("{timestamp}", &now.unix_timestamp().to_string())
+        .replace("{year}", &now.year().to_string())
+        .replace("{month}", &format!("{:02}", now.month() as u8))
+        .replace("{day}", &format!("{:02}", now.day()))
+        .replace("{hour}", &format!("{:02}", now.hour()))
+        .replace("{minute}", &format!("{:02}", now.minute()))
+        .replace("{second}", &format!("{:02}", now.second()))
+        .replace("{uuid}", uuid)
+        .trim_start_matches('/')
+        .to_owned()
+}
+
+fn is_relative_path(path: &str) -> bool {
+    !path.is_empty()
+        && Path::new(path)
+            .components()
+            .all(|c| matches!(c, Component::Normal(_) | Component::CurDir))
+}
+
+#[derive(Debug, Snafu)]
+#[snafu(context(suffix(false)))]
+pub enum Error {
+    #[snafu(display("Failed to read upload stream: {source}"))]
+    ReadStream { source: std::io::Error },
+    #[snafu(display("Failed to read resource file: {source}"))]
+    ReadFile { source: std::io::Error },
+    #[snafu(display("Failed to write resource file: {source}"))]
+    WriteFile { source: std::io::Error },
+    #[snafu(display("Failed to delete resource file: {source}"))]
+    DeleteFile { source: std::io::Error },
+    #[snafu(display("Invalid resource reference: {reference}"))]
+    InvalidReference { reference: String },
+    #[snafu(display("S3 storage is not configured"))]
+    MissingS3Config,
+    #[snafu(display("Resource payload has no S3 object"))]
+    MissingS3Object,
+    #[snafu(display("Invalid S3 credentials: {source}"))]
+    S3Credentials {
+        source: s3::creds::error::CredentialsError,
+    },
+    #[snafu(display("S3 request failed: {source}"))]
+    S3 { source: s3::error::S3Error },
+    #[snafu(display("Unsupported resource storage: {storage_type}"))]
+    UnsupportedStorage { storage_type: &'static str },
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        collections::HashMap,
+        sync::{Arc, Mutex},
+    };
+
+    use axum::{
+        body::Bytes,
+        extract::{Path, State},
+        http::{HeaderMap, StatusCode},
+        routing::put,
+        Router,
+    };
+    use time::OffsetDateTime;
+
+    use crate::{
+        model::{gen::StorageS3Config, resource::Resource as ResourceModel},
+        util,
+    };
+
+    use super::{
+        for_resource, is_relative_path, render_filepath, LocalStore, ResourceStore, S3Store,
+    };
+
+    fn resource(filename: &str, blob: &[u8]) -> ResourceModel {
+        ResourceModel {
+            filename: filename.to_owned(),
+            r#type: "text/plain".to_owned(),
+            blob: blob.to_vec(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn filepath_template() {
+        // 2024-03-05 06:07:08 UTC
+        let now = OffsetDateTime::from_unix_timestamp(1709618828).unwrap();
+        assert_eq!(
+            render_filepath("", "a.png", now, "u"),
+            "assets/1709618828_a.png"
+        );
+        assert_eq!(
+            render_filepath("{year}/{month}/{day}/{uuid}_{filename}", "a.png", now, "u"),
+            "2024/03/05/u_a.png"
+        );
+        assert_eq!(
+            render_filepath("files/", "../../a.png", now, "u"),
+            "files/a.png"
+        );
+    }
+
+    #[test]
+    fn local_reference() {
+        assert!(is_relative_path("assets/a.png"));
+        assert!(!is_relative_path("../a.png"));
+        assert!(!is_relative_path("/etc/passwd"));
+        assert!(!is_relative_path(""));
+    }
+
+    #[tokio::test]
+    async fn local_round_trip() {
+        let root = std::env::temp_dir().join(format!("memos-store-{}", util::uuid()));
+        let store = LocalStore::with_root(root.clone(), "assets/{filename}".to_owned());
+
+        // 同名文件不会互相覆盖
+        let mut a = resource("a.txt", b"first");
+        let mut b = resource("a.txt", b"second");
+        store.save(&mut a).await.unwrap();
+        store.save(&mut b).await.unwrap();
+        assert_eq!("assets/a.txt", a.reference);
+        assert_ne!(a.reference, b.reference);
+        assert!(a.blob.is_empty());
+
+        store.delete(&b).await.unwrap();
+        assert!(store.load(b).await.is_err());
+        assert_eq!(b"first".to_vec(), store.load(a).await.unwrap());
+        let _ = tokio::fs::remove_dir_all(root).await;
+    }
+
+    type Objects = Arc<Mutex<HashMap<String, Vec<u8>>>>;
+
+    /// 本地模拟的 MinIO，只支持路径风格的单对象读写
+    async fn mock_s3() -> (String, Objects) {
+        fn signed(headers: &HeaderMap) -> bool {
+            headers
+                .get("authorization")
+                .and_then(|h| h.to_str().ok())
+                .is_some_and(|h| h.starts_with("AWS4-HMAC-SHA256 Credential=key/"))
+        }
+
+        async fn put_object(
+            State(objects): State<Objects>,
+            Path((bucket, key)): Path<(String, String)>,
+            headers: HeaderMap,
+            body: Bytes,
+        ) -> StatusCode {
+            if !signed(&headers) {
+                return StatusCode::FORBIDDEN;
+            }
+            let mut objects = objects.lock().unwrap();
+            objects.insert(format!("{bucket}/{key}"), body.to_vec());
+            StatusCode::OK
+        }
+
+        async fn get_object(
+            State(objects): State<Objects>,
+            Path((bucket, key)): Path<(String, String)>,
+            headers: HeaderMap,
+        ) -> (StatusCode, Vec<u8>) {
+            if !signed(&headers) {
+                return (StatusCode::FORBIDDEN, Vec::new());
+            }
+            let objects = objects.lock().unwrap();
+            match objects.get(&format!("{bucket}/{key}")) {
+                Some(data) => (StatusCode::OK, data.clone()),
+                None => (StatusCode::NOT_FOUND, Vec::new()),
+            }
+        }
+
+        async fn delete_object(
+            State(objects): State<Objects>,
+            Path((bucket, key)): Path<(String, String)>,
+            headers: HeaderMap,
+        ) -> StatusCode {
+            if !signed(&headers) {
+                return StatusCode::FORBIDDEN;
+            }
+            objects.lock().unwrap().remove(&format!("{bucket}/{key}"));
+            StatusCode::NO_CONTENT
+        }
+
+        let objects = Objects::default();
+        let app = Router::new()
+            .route(
+                "/{bucket}/{*key}",
+                put(put_object).get(get_object).delete(delete_object),
+            )
+            .with_state(objects.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        (format!("http://{addr}"), objects)
+    }
+
+    #[tokio::test]
+    async fn s3_round_trip() {
+        let (endpoint, objects) = mock_s3().await;
+        let config = StorageS3Config {
+            access_key_id: "key".to_owned(),
+            access_key_secret: "secret".to_owned(),
+            endpoint,
+            region: "us-east-1".to_owned(),
+            bucket: "memos".to_owned(),
+            use_path_style: true,
+        };
+        let store = S3Store::new(config, "{uuid}/{filename}".to_owned()).unwrap();
+
+        let mut res = resource("a.txt", b"hello");
+        store.save(&mut res).await.unwrap();
+        assert!(res.blob.is_empty());
+        assert!(objects
+            .lock()
+            .unwrap()
+            .contains_key(&format!("memos/{}", res.reference)));
+
+        // 已有资源按 payload 中记录的配置读取
+        let store = for_resource(&res).unwrap();
+        let stored = || ResourceModel {
+            storage_type: res.storage_type,
+            reference: res.reference.clone(),
+            payload: res.payload.clone(),
+            ..Default::default()
+        };
+        assert_eq!(b"hello".to_vec(), store.load(stored()).await.unwrap());
+        store.delete(&res).await.unwrap();
+        assert!(objects.lock().unwrap().is_empty());
+        assert!(store.load(stored()).await.is_err());
+    }
+}
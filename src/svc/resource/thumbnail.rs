@@ -0,0 +1,134 @@
+//! 缩略图生成：按 EXIF 方向旋转，提供多种尺寸，浏览器支持时输出 WebP
+
+use std::io::Cursor;
+
+use bytes::Bytes;
+use image::{
+    codecs::webp::WebPEncoder, DynamicImage, ExtendedColorType, ImageDecoder, ImageFormat,
+    ImageReader,
+};
+use snafu::ResultExt;
+
+use super::{Error, GenerateThumbnail, ImageDecode, ImageEncode, OpenResource};
+
+/// 可选的缩略图边长，请求的尺寸向上取最接近的一档
+pub const SIZES: [u32; 3] = [128, 256, 512];
+pub const DEFAULT_SIZE: u32 = 512;
+const FORMATS: [ImageFormat; 3] = [ImageFormat::WebP, ImageFormat::Png, ImageFormat::Jpeg];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Thumbnail {
+    pub size: u32,
+    pub format: ImageFormat,
+}
+
+impl Thumbnail {
+    pub fn new(size: Option<u32>, webp: bool, mime: &str) -> Self {
+        let size = size
+            .and_then(|size| SIZES.into_iter().find(|preset| *preset >= size))
+            .unwrap_or(DEFAULT_SIZE);
+        // 不支持 WebP 时，JPEG 保持 JPEG，其余用 PNG 保留透明通道
+        let format = if webp {
+            ImageFormat::WebP
+        } else if mime == "image/jpeg" {
+            ImageFormat::Jpeg
+        } else {
+            ImageFormat::Png
+        };
+        Self { size, format }
+    }
+
+    pub fn mime(&self) -> &'static str {
+        self.format.to_mime_type()
+    }
+
+    pub fn cache_key(&self, key: &str) -> String {
+        format!("{key}-{}.{}", self.size, self.format.extensions_str()[0])
+    }
+
+    fn render(&self, blob: &[u8]) -> Result<Vec<u8>, Error> {
+        let mut decoder = ImageReader::new(Cursor::new(blob))
+            .with_guessed_format()
+            .context(OpenResource)?
+            .into_decoder()
+            .context(ImageDecode)?;
+        let orientation = decoder.orientation().context(ImageDecode)?;
+        let mut img = DynamicImage::from_decoder(decoder).context(ImageDecode)?;
+        img.apply_orientation(orientation);
+        let img = img.thumbnail(self.size, self.size);
+
+        let mut bytes = Vec::new();
+        match self.format {
+            ImageFormat::WebP => {
+                let rgba = img.to_rgba8();
+                WebPEncoder::new_lossless(&mut bytes)
+                    .encode(&rgba, rgba.width(), rgba.height(), ExtendedColorType::Rgba8)
+                    .context(ImageEncode)?;
+            }
+            // JPEG 不支持透明通道
+            ImageFormat::Jpeg => DynamicImage::from(img.to_rgb8())
+                .write_to(&mut Cursor::new(&mut bytes), ImageFormat::Jpeg)
+                .context(ImageEncode)?,
+            format => img
+                .write_to(&mut Cursor::new(&mut bytes), format)
+                .context(ImageEncode)?,
+        }
+        Ok(bytes)
+    }
+}
+
+/// 解码与缩放较耗 CPU，放到阻塞线程池执行
+pub async fn generate(blob: Bytes, thumbnail: Thumbnail) -> Result<Vec<u8>, Error> {
+    tokio::task::spawn_blocking(move || thumbnail.render(&blob))
+        .await
+        .context(GenerateThumbnail)?
+}
+
+/// 仅为能解码的图片类型生成缩略图，如 SVG 直接返回原图
+pub fn supported(mime: &str) -> bool {
+    mime.starts_with("image/")
+        && ImageFormat::from_mime_type(mime).is_some_and(|f| f.reading_enabled())
+}
+
+/// 同一资源所有可能的缩略图缓存 key
+pub fn variants(key: &str) -> impl Iterator<Item = String> + '_ {
+    SIZES.into_iter().flat_map(move |size| {
+        FORMATS
+            .into_iter()
+            .map(move |format| Thumbnail { size, format }.cache_key(key))
+    })
+}
+
+/// 由缩略图缓存 key 还原资源缓存 key
+pub fn resource_key(thumbnail_key: &str) -> Option<&str> {
+    thumbnail_key.rsplit_once('-').map(|(key, _)| key)
+}
+
+#[cfg(test)]
+mod test {
+    use image::ImageFormat;
+
+    use super::{resource_key, variants, Thumbnail};
+
+    #[test]
+    fn size_and_format() {
+        let thumbnail = Thumbnail::new(Some(200), true, "image/png");
+        assert_eq!(thumbnail.size, 256);
+        assert_eq!(thumbnail.mime(), "image/webp");
+        assert_eq!(Thumbnail::new(None, false, "image/jpeg").size, 512);
+        assert_eq!(Thumbnail::new(Some(4096), false, "image/jpeg").size, 512);
+        assert_eq!(
+            Thumbnail::new(Some(64), false, "image/gif").format,
+            ImageFormat::Png
+        );
+    }
+
+    #[test]
+    fn cache_key() {
+        let thumbnail = Thumbnail::new(Some(128), true, "image/png");
+        assert_eq!(thumbnail.cache_key("1-1700000000"), "1-1700000000-128.webp");
+        assert_eq!(resource_key("1-1700000000-128.webp"), Some("1-1700000000"));
+        assert_eq!(resource_key("abcdef-512.png"), Some("abcdef"));
+        assert_eq!(variants("abcdef").count(), 9);
+    }
+}
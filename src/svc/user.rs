@@ -6,30 +6,43 @@ use crate::api::v1::r#gen::{
     Shortcut, UpdateShortcutRequest, UserStats,
 };
 use crate::dao::memo::MemoRepository;
+use crate::dao::memo_relation::MemoRelationRepository;
+use crate::dao::memo_revision::MemoRevisionRepository;
+use crate::dao::quota::QuotaRepository;
+use crate::dao::reaction::ReactionRepository;
 use crate::dao::resource::ResourceRepository;
 use crate::dao::workspace::WorkspaceRepository;
 use crate::google::api::HttpBody;
-use crate::model::user::User as UserModel;
+use crate::model::gen::UserSettingKey;
+use crate::model::user::{
+    AccessToken, CreateUser, UpdateUser, User as UserModel, UserSetting as UserSettingModel,
+};
 use crate::{
     api::{
         prefix::ExtractName,
         v1::gen::{
+            user::Role,
             user_service_server::{self, UserServiceServer},
             CreateUserAccessTokenRequest, CreateUserRequest, DeleteUserAccessTokenRequest,
             DeleteUserRequest, GetUserAvatarBinaryRequest, GetUserRequest, GetUserSettingRequest,
             ListUserAccessTokensRequest, ListUserAccessTokensResponse, ListUsersRequest,
-            ListUsersResponse, UpdateUserRequest, UpdateUserSettingRequest, User, UserAccessToken,
-            UserSetting,
+            ListUsersResponse, State, UpdateUserRequest, UpdateUserSettingRequest, User,
+            UserAccessToken, UserSetting,
         },
     },
     dao::user::UserRepository,
 };
 use async_trait::async_trait;
-use sm3::{Digest, Sm3};
-use snafu::{OptionExt, Snafu};
+use snafu::{ensure, OptionExt, ResultExt, Snafu};
+use time::OffsetDateTime;
 use tonic::{Request, Response, Status};
+use tracing::warn;
 
+use super::access_token;
 use super::memo::MemoService;
+use super::password::{self, Verified};
+use super::permission::{self, PermissionDeniedSnafu};
+use super::workspace::WorkspaceSettingService;
 use super::{RequestExt, Service};
 
 #[async_trait]
@@ -41,21 +54,47 @@ pub trait UserService: user_service_server::UserService + Clone + Send + Sync +
     async fn sign_in(&self, name: &str, password: &str) -> Result<UserModel, Error>;
     async fn petch_user(&self, id: i32) -> Result<UserModel, Error>;
     async fn find_user(&self, name: &str) -> Result<UserModel, Error>;
+    /// 按工作区设置自助注册，空库中的第一个用户成为 HOST
+    async fn sign_up(&self, username: &str, password: &str) -> Result<UserModel, Error>;
+    /// 校验 Bearer 令牌并返回所属用户
+    async fn authenticate_token(&self, token: &str) -> Result<UserModel, Error>;
 }
 
 #[async_trait]
-impl<R: UserRepository + MemoRepository + ResourceRepository + WorkspaceRepository> UserService
-    for Service<R>
+impl<
+        R: UserRepository
+            + MemoRepository
+            + MemoRelationRepository
+            + MemoRevisionRepository
+            + ReactionRepository
+            + ResourceRepository
+            + WorkspaceRepository
+            + QuotaRepository,
+    > UserService for Service<R>
 {
     async fn sign_in(&self, name: &str, password: &str) -> Result<UserModel, Error> {
-        let mut hasher = Sm3::new();
-        hasher.update(password);
-
-        let password_hash = hex::encode(hasher.finalize());
-        self.repo
-            .find_user(name, Some(&password_hash))
+        let Some(mut user) = self
+            .repo
+            .find_user(name)
             .await?
-            .context(Login)
+            .filter(|u| u.state != State::Archived)
+        else {
+            // 同样计算一次摘要，避免以响应时间探测用户名
+            password::verify_dummy(password);
+            return Login.fail();
+        };
+
+        match password::verify(password, &user.password_hash) {
+            Verified::Invalid => return Login.fail(),
+            Verified::Valid => (),
+            // 重新计算失败不影响本次登录
+            Verified::Rehash => match self.rehash_password(user.id, password).await {
+                // 会话以摘要校验，需与库中一致
+                Ok(password_hash) => user.password_hash = password_hash,
+                Err(e) => warn!("Failed to rehash password of user {}: {e}", user.id),
+            },
+        }
+        Ok(user)
     }
 
     async fn petch_user(&self, id: i32) -> Result<UserModel, Error> {
@@ -66,15 +105,107 @@ impl<R: UserRepository + MemoRepository + ResourceRepository + WorkspaceReposito
 
     async fn find_user(&self, name: &str) -> Result<UserModel, Error> {
         self.repo
-            .find_user(name, None)
+            .find_user(name)
             .await?
             .context(UserNotFound { ident: name })
     }
+
+    async fn sign_up(&self, username: &str, password: &str) -> Result<UserModel, Error> {
+        // 首个用户不受注册设置限制，是否为首个用户由插入语句判定
+        let setting = self.get_general_setting().await;
+        let allow_user = !setting.disallow_user_registration && !setting.disallow_password_auth;
+        if !allow_user && self.repo.host_user().await?.is_some() {
+            ensure!(!setting.disallow_user_registration, RegistrationDisabled);
+            return PasswordAuthDisabled.fail();
+        }
+
+        let username = username.trim();
+        self.ensure_username_available(username).await?;
+        ensure!(!password.is_empty(), EmptyPassword);
+        let create = CreateUser {
+            username: username.to_owned(),
+            password_hash: password::hash(password).context(HashPassword)?,
+            ..Default::default()
+        };
+        match self.repo.sign_up_user(create, allow_user).await? {
+            Some(user) => Ok(user),
+            // 并发注册时 HOST 已被他人占用
+            None if setting.disallow_user_registration => RegistrationDisabled.fail(),
+            None => PasswordAuthDisabled.fail(),
+        }
+    }
+
+    async fn authenticate_token(&self, token: &str) -> Result<UserModel, Error> {
+        let now = OffsetDateTime::now_utc().unix_timestamp();
+        let token = access_token::parse(token).context(InvalidAccessToken)?;
+        let tokens = self.find_access_tokens(token.user_id).await?;
+        ensure!(
+            tokens.iter().any(|t| access_token::verify(&token, t, now)),
+            InvalidAccessToken
+        );
+        let user = self.petch_user(token.user_id).await?;
+        ensure!(user.state != State::Archived, InvalidAccessToken);
+        Ok(user)
+    }
+}
+
+impl<R: UserRepository> Service<R> {
+    async fn rehash_password(&self, id: i32, password: &str) -> Result<String, Error> {
+        let password_hash = password::hash(password).context(HashPassword)?;
+        self.repo
+            .update_user(UpdateUser {
+                id,
+                password_hash: Some(password_hash.clone()),
+                ..Default::default()
+            })
+            .await?;
+        Ok(password_hash)
+    }
+
+    async fn ensure_username_available(&self, username: &str) -> Result<(), Error> {
+        ensure!(valid_username(username), InvalidUsername { username });
+        let exists = self.repo.find_user(username).await?.is_some();
+        ensure!(!exists, UserExists { username });
+        Ok(())
+    }
+
+    async fn find_access_tokens(&self, user_id: i32) -> Result<Vec<AccessToken>, Error> {
+        let setting = self
+            .repo
+            .find_user_setting(user_id)
+            .await?
+            .into_iter()
+            .find(|s| s.key == UserSettingKey::AccessTokens);
+        match setting {
+            Some(setting) => serde_json::from_str(&setting.value).context(AccessTokenFormat),
+            None => Ok(vec![]),
+        }
+    }
+
+    async fn save_access_tokens(&self, user_id: i32, tokens: &[AccessToken]) -> Result<(), Error> {
+        let value = serde_json::to_string(tokens).context(AccessTokenFormat)?;
+        self.repo
+            .upsert_user_setting(vec![UserSettingModel {
+                user_id,
+                key: UserSettingKey::AccessTokens,
+                value,
+            }])
+            .await?;
+        Ok(())
+    }
 }
 
 #[tonic::async_trait]
-impl<R: UserRepository + MemoRepository + ResourceRepository + WorkspaceRepository>
-    user_service_server::UserService for Service<R>
+impl<
+        R: UserRepository
+            + MemoRepository
+            + MemoRelationRepository
+            + MemoRevisionRepository
+            + ReactionRepository
+            + ResourceRepository
+            + WorkspaceRepository
+            + QuotaRepository,
+    > user_service_server::UserService for Service<R>
 {
     async fn get_user(&self, request: Request<GetUserRequest>) -> Result<Response<User>, Status> {
         let name = request.into_inner().get_name();
@@ -130,47 +261,193 @@ impl<R: UserRepository + MemoRepository + ResourceRepository + WorkspaceReposito
         &self,
         request: Request<ListUsersRequest>,
     ) -> Result<Response<ListUsersResponse>, Status> {
-        Err(Status::unimplemented("unimplemented"))
+        let user = request.get_current_user()?;
+        ensure!(permission::is_superuser(user), PermissionDeniedSnafu);
+        let users = self.repo.list_users().await?;
+        let users = users.into_iter().map(Into::into).collect();
+        Ok(Response::new(ListUsersResponse { users }))
     }
     async fn create_user(
         &self,
         request: Request<CreateUserRequest>,
     ) -> Result<Response<User>, Status> {
-        Err(Status::unimplemented("unimplemented"))
+        let user = request.get_current_user()?;
+        let Some(create) = &request.get_ref().user else {
+            return Err(Status::invalid_argument("user is required"));
+        };
+        let role = match Role::try_from(create.role) {
+            Ok(Role::Unspecified) | Err(_) => Role::User,
+            Ok(role) => role,
+        };
+        permission::check_user_admin(user, None, Some(role))?;
+
+        let username = create.username.trim();
+        self.ensure_username_available(username).await?;
+        ensure!(!create.password.is_empty(), EmptyPassword);
+
+        let created = self
+            .repo
+            .create_user(CreateUser {
+                username: username.to_owned(),
+                role,
+                email: create.email.clone(),
+                nickname: create.nickname.clone(),
+                avatar_url: create.avatar_url.clone(),
+                description: create.description.clone(),
+                password_hash: password::hash(&create.password).context(HashPassword)?,
+            })
+            .await?
+            .context(MaybeCreateUser)?;
+        Ok(Response::new(created.into()))
     }
     async fn update_user(
         &self,
         request: Request<UpdateUserRequest>,
     ) -> Result<Response<User>, Status> {
-        Err(Status::unimplemented("unimplemented"))
+        let user = request.get_current_user()?;
+        let UpdateUserRequest {
+            user: Some(patch),
+            update_mask: Some(field_mask),
+        } = request.get_ref()
+        else {
+            return Err(Status::invalid_argument(
+                "user and update_mask are required",
+            ));
+        };
+        let id = patch.get_id()?;
+        let target = UserService::petch_user(self, id).await?;
+        permission::check_user_profile(user, &target)?;
+
+        let mut update = UpdateUser {
+            id,
+            ..Default::default()
+        };
+        for path in &field_mask.paths {
+            match path.as_str() {
+                "username" => {
+                    let username = patch.username.trim();
+                    if username != target.username {
+                        self.ensure_username_available(username).await?;
+                    }
+                    update.username = Some(username.to_owned());
+                }
+                "nickname" => update.nickname = Some(patch.nickname.clone()),
+                "email" => update.email = Some(patch.email.clone()),
+                "avatar_url" => update.avatar_url = Some(patch.avatar_url.clone()),
+                "description" => update.description = Some(patch.description.clone()),
+                "password" => {
+                    ensure!(!patch.password.is_empty(), EmptyPassword);
+                    update.password_hash =
+                        Some(password::hash(&patch.password).context(HashPassword)?);
+                }
+                "role" => {
+                    let role = Role::try_from(patch.role)
+                        .ok()
+                        .filter(|r| *r != Role::Unspecified)
+                        .context(InvalidUserField { field: path })?;
+                    permission::check_user_admin(user, Some(&target), Some(role))?;
+                    update.role = Some(role);
+                }
+                "state" => {
+                    let state = State::try_from(patch.state)
+                        .ok()
+                        .filter(|s| *s != State::Unspecified)
+                        .context(InvalidUserField { field: path })?;
+                    permission::check_user_admin(user, Some(&target), None)?;
+                    update.state = Some(state);
+                }
+                _ => (),
+            }
+        }
+
+        self.repo.update_user(update).await?;
+        let updated = UserService::petch_user(self, id).await?;
+        Ok(Response::new(updated.into()))
     }
     /// ListUserAccessTokens returns a list of access tokens for a user.
     async fn list_user_access_tokens(
         &self,
         request: Request<ListUserAccessTokensRequest>,
     ) -> Result<Response<ListUserAccessTokensResponse>, Status> {
-        // TODO
-        Ok(Response::new(ListUserAccessTokensResponse::default()))
+        let user = request.get_current_user()?;
+        let id = request.get_ref().get_id()?;
+        // 令牌只归本人管理
+        ensure!(id == user.id, PermissionDeniedSnafu);
+
+        let access_tokens = self
+            .find_access_tokens(id)
+            .await?
+            .into_iter()
+            .map(|t| {
+                let name = access_token::identifier(id, &t);
+                access_token::to_api(t, name)
+            })
+            .collect();
+        Ok(Response::new(ListUserAccessTokensResponse {
+            access_tokens,
+        }))
     }
     /// CreateUserAccessToken creates a new access token for a user.
     async fn create_user_access_token(
         &self,
         request: Request<CreateUserAccessTokenRequest>,
     ) -> Result<Response<UserAccessToken>, Status> {
-        Err(Status::unimplemented("unimplemented"))
+        let user = request.get_current_user()?;
+        let id = request.get_ref().get_id()?;
+        ensure!(id == user.id, PermissionDeniedSnafu);
+
+        let now = OffsetDateTime::now_utc().unix_timestamp();
+        let expires_ts = request.get_ref().expires_at.as_ref().map(|t| t.seconds);
+        ensure!(
+            expires_ts.is_none_or(|ts| ts > now),
+            InvalidAccessTokenExpiry
+        );
+
+        let description = request.get_ref().description.clone();
+        let (token, entry) = access_token::issue(id, description, expires_ts, now);
+        let mut tokens = self.find_access_tokens(id).await?;
+        tokens.push(entry.clone());
+        self.save_access_tokens(id, &tokens).await?;
+        Ok(Response::new(access_token::to_api(entry, token)))
     }
     /// DeleteUserAccessToken deletes an access token for a user.
     async fn delete_user_access_token(
         &self,
         request: Request<DeleteUserAccessTokenRequest>,
     ) -> Result<Response<()>, Status> {
-        Err(Status::unimplemented("unimplemented"))
+        let user = request.get_current_user()?;
+        let id = request.get_ref().get_id()?;
+        ensure!(id == user.id, PermissionDeniedSnafu);
+
+        // 接受列表中的标识或完整令牌
+        let token = access_token::parse(&request.get_ref().access_token)
+            .filter(|t| t.user_id == id)
+            .context(AccessTokenNotFound)?;
+        let mut tokens = self.find_access_tokens(id).await?;
+        let len = tokens.len();
+        tokens.retain(|t| t.id != token.id);
+        ensure!(tokens.len() < len, AccessTokenNotFound);
+        self.save_access_tokens(id, &tokens).await?;
+        Ok(Response::new(()))
     }
+    /// 归档而非删除，保留用户的 memo 与资源
     async fn delete_user(
         &self,
         request: Request<DeleteUserRequest>,
     ) -> Result<Response<()>, Status> {
-        Err(Status::unimplemented("unimplemented"))
+        let user = request.get_current_user()?;
+        let id = request.get_ref().get_id()?;
+        let target = UserService::petch_user(self, id).await?;
+        permission::check_user_admin(user, Some(&target), None)?;
+
+        self.repo
+            .update_user(UpdateUser {
+                id,
+                state: Some(State::Archived),
+                ..Default::default()
+            })
+            .await?;
+        Ok(Response::new(()))
     }
     async fn get_user_avatar_binary(
         &self,
@@ -216,6 +493,14 @@ impl<R: UserRepository + MemoRepository + ResourceRepository + WorkspaceReposito
     }
 }
 
+pub fn valid_username(username: &str) -> bool {
+    !username.is_empty()
+        && username.len() <= 32
+        && username
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+}
+
 #[derive(Debug, Snafu)]
 pub enum Error {
     #[snafu(
@@ -227,6 +512,35 @@ pub enum Error {
     #[snafu(display("User not found: {ident}"), context(suffix(false)))]
     UserNotFound { ident: String },
 
+    #[snafu(display("User already exists: {username}"), context(suffix(false)))]
+    UserExists { username: String },
+
+    #[snafu(display("Invalid username: {username}"), context(suffix(false)))]
+    InvalidUsername { username: String },
+
+    #[snafu(display("Password is empty"), context(suffix(false)))]
+    EmptyPassword,
+
+    #[snafu(display("Failed to hash password: {source}"), context(suffix(false)))]
+    HashPassword {
+        source: argon2::password_hash::Error,
+    },
+
+    #[snafu(display("User registration is disabled"), context(suffix(false)))]
+    RegistrationDisabled,
+
+    #[snafu(display("Password authentication is disabled"), context(suffix(false)))]
+    PasswordAuthDisabled,
+
+    #[snafu(display("Invalid user field: {field}"), context(suffix(false)))]
+    InvalidUserField { field: String },
+
+    #[snafu(
+        display("Maybe create user failed, because return none"),
+        context(suffix(false))
+    )]
+    MaybeCreateUser,
+
     #[snafu(context(false))]
     QueryUser {
         source: crate::dao::user::FindUserError,
@@ -236,4 +550,70 @@ pub enum Error {
     PetchUser {
         source: crate::dao::user::PetchUserError,
     },
+
+    #[snafu(display("Invalid or expired access token"), context(suffix(false)))]
+    InvalidAccessToken,
+
+    #[snafu(display("Access token not found"), context(suffix(false)))]
+    AccessTokenNotFound,
+
+    #[snafu(
+        display("Access token expiration must be in the future"),
+        context(suffix(false))
+    )]
+    InvalidAccessTokenExpiry,
+
+    #[snafu(
+        display("Failed to parse access tokens: {source}"),
+        context(suffix(false))
+    )]
+    AccessTokenFormat { source: serde_json::Error },
+
+    #[snafu(context(false))]
+    GetHostUser {
+        source: crate::dao::user::GetHostUserError,
+    },
+
+    #[snafu(context(false))]
+    ListUser {
+        source: crate::dao::user::ListUserError,
+    },
+
+    #[snafu(context(false))]
+    CreateUser {
+        source: crate::dao::user::CreateUserError,
+    },
+
+    #[snafu(context(false))]
+    UpdateUser {
+        source: crate::dao::user::UpdateUserError,
+    },
+
+    #[snafu(context(false))]
+    FindUserSetting {
+        source: crate::dao::user::FindUserSettingError,
+    },
+
+    #[snafu(context(false))]
+    UpsertUserSetting {
+        source: crate::dao::user::UpsertUserSettingError,
+    },
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{api::v1::gen::user::Role, svc::test};
+
+    use super::UserService;
+
+    #[tokio::test]
+    async fn first_sign_up_is_host() {
+        let svc = test::service().await;
+        let names = ["alice", "bob", "carol", "dave"];
+        let users = futures::future::join_all(names.iter().map(|n| svc.sign_up(n, "secret"))).await;
+
+        let roles: Vec<Role> = users.into_iter().map(|u| u.unwrap().role).collect();
+        assert_eq!(1, roles.iter().filter(|r| **r == Role::Host).count());
+        assert_eq!(3, roles.iter().filter(|r| **r == Role::User).count());
+    }
 }
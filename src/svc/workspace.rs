@@ -11,7 +11,13 @@ use crate::{
         },
     },
     dao::{user::UserRepository, workspace::WorkspaceRepository},
-    model::gen::{workspace_setting::Value as WorkspaceSettingValue, WorkspaceSettingKey},
+    model::{
+        gen::{
+            workspace_setting::Value as WorkspaceSettingValue, WorkspaceGeneralSetting,
+            WorkspaceSettingKey, WorkspaceStorageSetting,
+        },
+        system::StoragePrivacySetting,
+    },
 };
 use async_trait::async_trait;
 use tonic::{Request, Response, Status};
@@ -22,6 +28,7 @@ const OWNER: &str = "users/546";
 const VERSION: &str = "0.22.3";
 const MODE: &str = "prod";
 const DEFAULT_MAX_MIB: usize = 32;
+const DEFAULT_REACTIONS: [&str; 8] = ["👍", "👎", "❤️", "🎉", "😄", "😕", "😢", "😡"];
 
 #[async_trait]
 pub trait WorkspaceService:
@@ -41,7 +48,11 @@ pub trait WorkspaceSettingService:
     }
 
     async fn get_upload_size_limit(&self) -> usize;
+    async fn get_general_setting(&self) -> WorkspaceGeneralSetting;
+    async fn get_storage_setting(&self) -> WorkspaceStorageSetting;
+    async fn get_storage_privacy_setting(&self) -> StoragePrivacySetting;
     async fn is_display_with_update_time(&self) -> bool;
+    async fn get_memo_reactions(&self) -> Vec<String>;
 }
 
 #[async_trait]
@@ -68,21 +79,45 @@ impl<U: UserRepository> workspace_service_server::WorkspaceService for Service<U
 #[async_trait]
 impl<W: WorkspaceRepository> WorkspaceSettingService for Service<W> {
     async fn get_upload_size_limit(&self) -> usize {
+        let setting = self.get_storage_setting().await;
+        if setting.upload_size_limit_mb <= 0 {
+            DEFAULT_MAX_MIB
+        } else {
+            setting.upload_size_limit_mb as usize
+        }
+    }
+
+    async fn get_general_setting(&self) -> WorkspaceGeneralSetting {
+        if let Ok(Some(WorkspaceSettingValue::GeneralSetting(setting))) = self
+            .repo
+            .find_workspace_setting(WorkspaceSettingKey::General)
+            .await
+        {
+            setting
+        } else {
+            WorkspaceGeneralSetting::default()
+        }
+    }
+
+    async fn get_storage_setting(&self) -> WorkspaceStorageSetting {
         if let Ok(Some(WorkspaceSettingValue::StorageSetting(setting))) = self
             .repo
             .find_workspace_setting(WorkspaceSettingKey::Storage)
             .await
         {
-            if setting.upload_size_limit_mb <= 0 {
-                DEFAULT_MAX_MIB
-            } else {
-                setting.upload_size_limit_mb as usize
-            }
+            setting
         } else {
-            DEFAULT_MAX_MIB
+            WorkspaceStorageSetting::default()
         }
     }
 
+    async fn get_storage_privacy_setting(&self) -> StoragePrivacySetting {
+        self.repo
+            .find_storage_privacy_setting()
+            .await
+            .unwrap_or_default()
+    }
+
     async fn is_display_with_update_time(&self) -> bool {
         if let Ok(Some(WorkspaceSettingValue::MemoRelatedSetting(setting))) = self
             .repo
@@ -94,6 +129,19 @@ impl<W: WorkspaceRepository> WorkspaceSettingService for Service<W> {
             false
         }
     }
+
+    async fn get_memo_reactions(&self) -> Vec<String> {
+        if let Ok(Some(WorkspaceSettingValue::MemoRelatedSetting(setting))) = self
+            .repo
+            .find_workspace_setting(WorkspaceSettingKey::MemoRelated)
+            .await
+        {
+            if !setting.reactions.is_empty() {
+                return setting.reactions;
+            }
+        }
+        DEFAULT_REACTIONS.iter().map(|r| r.to_string()).collect()
+    }
 }
 
 #[tonic::async_trait]
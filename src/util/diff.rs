@@ -0,0 +1,128 @@
+use serde::Serialize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DiffOp {
+    Equal,
+    Insert,
+    Delete,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DiffLine {
+    pub op: DiffOp,
+    pub text: String,
+}
+
+/// LCS 表的单元格上限（约 16 MB），超出时中间部分整体视为删除后插入
+const MAX_LCS_CELLS: usize = 4 * 1024 * 1024;
+
+/// 基于最长公共子序列的逐行对比
+pub fn line_diff(old: &str, new: &str) -> Vec<DiffLine> {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+
+    // 先去掉相同的首尾行，只对中间变化的部分求 LCS
+    let prefix = old.iter().zip(&new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let line = |op, text: &str| DiffLine {
+        op,
+        text: text.to_owned(),
+    };
+    let mut diff = Vec::with_capacity(old.len().max(new.len()));
+    diff.extend(old[..prefix].iter().map(|l| line(DiffOp::Equal, l)));
+    let old_mid = &old[prefix..old.len() - suffix];
+    let new_mid = &new[prefix..new.len() - suffix];
+    if (old_mid.len() + 1).saturating_mul(new_mid.len() + 1) > MAX_LCS_CELLS {
+        diff.extend(old_mid.iter().map(|l| line(DiffOp::Delete, l)));
+        diff.extend(new_mid.iter().map(|l| line(DiffOp::Insert, l)));
+    } else {
+        lcs_diff(old_mid, new_mid, &mut diff);
+    }
+    diff.extend(
+        old[old.len() - suffix..]
+            .iter()
+            .map(|l| line(DiffOp::Equal, l)),
+    );
+    diff
+}
+
+fn lcs_diff(old: &[&str], new: &[&str], diff: &mut Vec<DiffLine>) {
+    // lcs[i][j]: old[i..] 与 new[j..] 的最长公共子序列长度
+    let mut lcs = vec![vec![0u32; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let line = |op, text: &str| DiffLine {
+        op,
+        text: text.to_owned(),
+    };
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() && j < new.len() {
+        if old[i] == new[j] {
+            diff.push(line(DiffOp::Equal, old[i]));
+            i += 1;
+            j += 1;
+        } else if lcs[i + 1][j] >= lcs[i][j + 1] {
+            diff.push(line(DiffOp::Delete, old[i]));
+            i += 1;
+        } else {
+            diff.push(line(DiffOp::Insert, new[j]));
+            j += 1;
+        }
+    }
+    diff.extend(old[i..].iter().map(|l| line(DiffOp::Delete, l)));
+    diff.extend(new[j..].iter().map(|l| line(DiffOp::Insert, l)));
+}
+
+mod test {
+    #[test]
+    fn test_line_diff() {
+        use super::{line_diff, DiffOp};
+
+        let diff = line_diff("a\nb\nc", "a\nc\nd");
+        let ops: Vec<(DiffOp, &str)> = diff.iter().map(|l| (l.op, l.text.as_str())).collect();
+        assert_eq!(
+            ops,
+            vec![
+                (DiffOp::Equal, "a"),
+                (DiffOp::Delete, "b"),
+                (DiffOp::Equal, "c"),
+                (DiffOp::Insert, "d"),
+            ]
+        );
+
+        assert!(line_diff("same", "same")
+            .iter()
+            .all(|l| l.op == DiffOp::Equal));
+        assert_eq!(line_diff("", "x")[0].op, DiffOp::Insert);
+    }
+
+    #[test]
+    fn test_large_line_diff() {
+        use super::{line_diff, DiffOp};
+
+        // 超出上限时不再求 LCS，首尾相同的行仍保留
+        let old: String = (0..5000).map(|i| format!("old {i}\n")).collect();
+        let new: String = (0..5000).map(|i| format!("new {i}\n")).collect();
+        let diff = line_diff(&format!("head\n{old}tail"), &format!("head\n{new}tail"));
+        assert_eq!(10002, diff.len());
+        assert_eq!((DiffOp::Equal, "head"), (diff[0].op, diff[0].text.as_str()));
+        assert!(diff[1..5001].iter().all(|l| l.op == DiffOp::Delete));
+        assert!(diff[5001..10001].iter().all(|l| l.op == DiffOp::Insert));
+        assert_eq!(DiffOp::Equal, diff[10001].op);
+    }
+}
@@ -1,4 +1,4 @@
-use std::sync::OnceLock;
+use std::{ops::Range, sync::OnceLock};
 
 use comrak::{
     nodes::{AstNode, ListType, NodeValue},
@@ -17,6 +17,8 @@ use crate::{
 };
 
 static TAG_REGEX: OnceLock<Regex> = OnceLock::new();
+/// [[memos/123]]
+static REFERENCE_REGEX: OnceLock<Regex> = OnceLock::new();
 pub fn parse_document(content: impl AsRef<str>) -> Vec<Node> {
     let content = content.as_ref();
     let arena = Arena::new();
@@ -39,35 +41,140 @@ pub fn get_memo_property(content: impl AsRef<str>) -> MemoPayload {
     let mut payload = parse_property(root);
     payload.tags.sort();
     payload.tags.dedup();
+    if let Some(property) = payload.property.as_mut() {
+        property.references.sort();
+        property.references.dedup();
+    }
     payload
 }
 
+/// 将内容中的 #old_tag 及其子标签替换到 #new_tag 下
+///
+/// 只改写提取标签时识别出的位置，代码与链接中的 # 保持原样
+pub fn rename_tag(content: impl AsRef<str>, old_tag: &str, new_tag: &str) -> String {
+    let content = content.as_ref();
+    let arena = Arena::new();
+    let mut options = comrak::Options::default();
+    options.extension.tasklist = true;
+    options.extension.strikethrough = true;
+    options.render.unsafe_ = true;
+    let root = comrak::parse_document(&arena, content, &options);
+
+    let mut line_starts = vec![0];
+    line_starts.extend(content.match_indices('\n').map(|(i, _)| i + 1));
+    let mut spans = Vec::new();
+    for text in tag_texts(root) {
+        let ast = text.data.borrow();
+        let NodeValue::Text(literal) = &ast.value else {
+            continue;
+        };
+        let (start, end) = (ast.sourcepos.start, ast.sourcepos.end);
+        if start.line != end.line {
+            continue;
+        }
+        let Some(offset) = line_starts
+            .get(start.line - 1)
+            .map(|i| i + start.column - 1)
+        else {
+            continue;
+        };
+        // 含转义或实体时文本与源码不一致，无法定位
+        if content.get(offset..offset + literal.len()) != Some(literal.as_str()) {
+            continue;
+        }
+        spans.extend(
+            tag_spans(literal)
+                .into_iter()
+                .map(|span| span.start + offset..span.end + offset),
+        );
+    }
+    spans.sort_by_key(|span| span.start);
+
+    let mut rtn = String::with_capacity(content.len());
+    let mut i = 0;
+    for span in spans {
+        let Some(rest) = content[span.clone()].strip_prefix(old_tag) else {
+            continue;
+        };
+        if !rest.is_empty() && !rest.starts_with('/') {
+            continue;
+        }
+        rtn.push_str(&content[i..span.start]);
+        rtn.push_str(new_tag);
+        i = span.start + old_tag.len();
+    }
+    rtn.push_str(&content[i..]);
+    rtn
+}
+
+/// 文本中标签名的位置（不含 #），# 须位于开头或空白之后，末尾标点不计入标签
+fn tag_spans(text: &str) -> Vec<Range<usize>> {
+    let re = TAG_REGEX.get_or_init(|| Regex::new(r"(?:^|\s)#(\S+)").unwrap());
+    re.captures_iter(text)
+        .filter_map(|c| c.get(1))
+        .filter_map(|m| {
+            let tag = m
+                .as_str()
+                .trim_end_matches(|c: char| ",.;:!?)]}'\"，。；：！？、）】」".contains(c));
+            (!tag.is_empty()).then(|| m.start()..m.start() + tag.len())
+        })
+        .collect()
+}
+
+/// 可能包含标签的文本节点，与 parse_property 的遍历范围一致
+fn tag_texts<'a>(node: &'a AstNode<'a>) -> Vec<&'a AstNode<'a>> {
+    match &node.data.borrow().value {
+        NodeValue::Text(_) => vec![node],
+        value if has_tag_children(value) => node.children().flat_map(tag_texts).collect(),
+        _ => vec![],
+    }
+}
+
+fn has_tag_children(value: &NodeValue) -> bool {
+    matches!(
+        value,
+        NodeValue::Document
+            | NodeValue::BlockQuote
+            | NodeValue::Paragraph
+            | NodeValue::Heading(_)
+            | NodeValue::List(_)
+            | NodeValue::Item(_)
+            | NodeValue::TaskItem(_)
+            | NodeValue::Strong
+    )
+}
+
+/// 标签自身及其祖先，如 a/b/c -> [a, a/b, a/b/c]
+pub fn tag_paths(tag: &str) -> Vec<&str> {
+    tag.match_indices('/')
+        .map(|(i, _)| &tag[..i])
+        .chain(std::iter::once(tag))
+        .filter(|t| !t.is_empty())
+        .collect()
+}
+
 fn parse_property<'a>(node: &'a AstNode<'a>) -> MemoPayload {
     match &node.data.borrow().value {
-        NodeValue::Document => parse_property_child(node),
-        NodeValue::BlockQuote => parse_property_child(node),
         NodeValue::CodeBlock(code) => MemoPayload::code(),
-        NodeValue::Paragraph => parse_property_child(node),
         NodeValue::Code(content) => MemoPayload::code(),
         NodeValue::Text(content) => {
-            let re = TAG_REGEX.get_or_init(|| Regex::new(r"#\S+$|#\S+\s").unwrap());
-            let matches = re.find_iter(content);
-            let mut tags = Vec::new();
-            for mat in matches {
-                let i = if mat.end() == content.len() && !content.ends_with(' ') {
-                    mat.end()
-                } else {
-                    mat.end() - 1
-                };
-                if let Some(tag) = content.get(mat.start() + 1..i) {
-                    tags.push(tag.to_string());
-                }
+            let tags = tag_spans(content)
+                .into_iter()
+                .map(|span| content[span].to_string())
+                .collect();
+            let mut payload = MemoPayload::tags(tags);
+
+            let re = REFERENCE_REGEX.get_or_init(|| Regex::new(r"\[\[memos/(\d+)\]\]").unwrap());
+            let references: Vec<String> = re
+                .captures_iter(content)
+                .filter_map(|c| c.get(1))
+                .map(|m| m.as_str().to_string())
+                .collect();
+            if !references.is_empty() {
+                payload.merge(MemoPayload::references(references));
             }
-            MemoPayload::tags(tags)
+            payload
         }
-        NodeValue::Heading(head) => parse_property_child(node),
-        NodeValue::List(list) => parse_property_child(node),
-        NodeValue::Item(list) => parse_property_child(node),
         NodeValue::TaskItem(checked) => {
             let mut payload = if checked.is_none() {
                 MemoPayload::incomplete_task()
@@ -78,8 +185,8 @@ fn parse_property<'a>(node: &'a AstNode<'a>) -> MemoPayload {
             payload.merge(p);
             payload
         }
-        NodeValue::Strong => parse_property_child(node),
         NodeValue::Link(link) => MemoPayload::link(),
+        value if has_tag_children(value) => parse_property_child(node),
         _ => MemoPayload::default(),
     }
 }
@@ -139,29 +246,25 @@ fn parse_node<'a>(node: &'a AstNode<'a>) -> Vec<Node> {
         }],
         NodeValue::Text(content) => {
             let mut nodes = Vec::new();
-            let tag_regex = TAG_REGEX.get_or_init(|| Regex::new(r"#\S+$|#\S+\s").unwrap());
-            let tag_matches = tag_regex.find_iter(content);
             let mut i = 0;
             let length = content.len();
-            for tag_match in tag_matches {
-                if i < tag_match.start() {
+            for span in tag_spans(content) {
+                // 跳过 #
+                let start = span.start - 1;
+                if i < start {
                     nodes.push(Node {
                         r#type: NodeType::Text.into(),
-                        node: content.get(i..tag_match.start()).map(|c| {
+                        node: content.get(i..start).map(|c| {
                             node::Node::TextNode(TextNode {
                                 content: c.to_owned(),
                             })
                         }),
                     });
                 }
-                i = if tag_match.end() == length && !content.ends_with(' ') {
-                    tag_match.end()
-                } else {
-                    tag_match.end() - 1
-                };
+                i = span.end;
                 nodes.push(Node {
                     r#type: NodeType::Tag.into(),
-                    node: content.get(tag_match.start() + 1..i).map(|c| {
+                    node: content.get(span).map(|c| {
                         node::Node::TagNode(TagNode {
                             content: c.to_owned(),
                         })
@@ -340,4 +443,46 @@ mod test {
         let MemoPayload { tags, .. } = super::get_memo_property(buffer);
         assert_eq!("LINK", tags[0]);
     }
+
+    #[test]
+    fn parse_reference() {
+        let buffer = "see [[memos/12]] and [[memos/3]], again [[memos/12]]";
+        let payload = super::get_memo_property(buffer);
+        let references = payload.property.map(|p| p.references).unwrap_or_default();
+        assert_eq!(vec!["12".to_string(), "3".to_string()], references);
+    }
+
+    #[test]
+    fn rename_tag() {
+        let buffer = "#work todo #work/plan #workshop\n#work";
+        let content = super::rename_tag(buffer, "work", "job");
+        assert_eq!("#job todo #job/plan #workshop\n#job", content);
+    }
+
+    #[test]
+    fn rename_tag_skip_code_and_url() {
+        let buffer = "#work `#work` http://x/#work\n\n```\n#work\n```\n\n- [ ] **#work**";
+        let content = super::rename_tag(buffer, "work", "job");
+        assert_eq!(
+            "#job `#work` http://x/#work\n\n```\n#work\n```\n\n- [ ] **#job**",
+            content
+        );
+        let tags = super::get_memo_property(buffer).tags;
+        assert_eq!(vec!["work".to_string()], tags);
+    }
+
+    #[test]
+    fn rename_tag_punctuation() {
+        let buffer = "#work, #work. #work/plan! （#work）";
+        let tags = super::get_memo_property(buffer).tags;
+        assert_eq!(vec!["work".to_string(), "work/plan".to_string()], tags);
+        let content = super::rename_tag(buffer, "work", "job");
+        assert_eq!("#job, #job. #job/plan! （#work）", content);
+    }
+
+    #[test]
+    fn tag_paths() {
+        assert_eq!(vec!["a", "a/b", "a/b/c"], super::tag_paths("a/b/c"));
+        assert_eq!(vec!["a"], super::tag_paths("a"));
+    }
 }
@@ -1,4 +1,4 @@
-pub mod ast;
+pub mod diff;
 pub mod md;
 
 use nanoid::{alphabet, nanoid};
@@ -6,3 +6,8 @@ use nanoid::{alphabet, nanoid};
 pub fn uuid() -> String {
     nanoid!(16, &alphabet::SAFE)
 }
+
+/// 访问令牌等需要难以猜测的随机串
+pub fn secret() -> String {
+    nanoid!(32, &alphabet::SAFE)
+}